@@ -0,0 +1,15 @@
+extern crate bolos_icons;
+extern crate cargo_bolos;
+
+fn main() {
+    bolos_icons::Generator::new()
+        .icon("BADGE_RUST", "icons/badge_rust.gif")
+        .icon("BADGE_BACK", "icons/badge_back.gif")
+        .write_to_out_dir("icons.rs")
+        .expect("Failed to convert icons");
+
+    // Name, version, icon and the allowed keys come from the
+    // [package.metadata.bolos] table of Cargo.toml
+    cargo_bolos::manifest::write_to_out_dir("app_manifest.rs")
+        .expect("Failed to generate the app manifest");
+}
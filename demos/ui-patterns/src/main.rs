@@ -1,44 +1,77 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(target_arch="arm", no_std)]
+#![cfg_attr(target_arch="arm", no_main)]
 
 #[macro_use]
 extern crate bolos;
 
 mod icon;
+#[cfg(test)]
+mod tests;
 
 use bolos::seproxyhal::MessageLoop;
 use bolos::runtime::exit;
-use bolos::time::Duration;
 use bolos::ui;
-use bolos::ui::menu;
-use bolos::state::{Store, BasicAction};
+use bolos::ui::{menu, review, progress};
+use bolos::state::Store;
+
+include!(concat!(env!("OUT_DIR"), "/app_manifest.rs"));
+
+persistent!(SETTINGS: Settings = Settings{
+    truncate_address: false,
+    recipient: Recipient::Charity,
+}, version: 1);
+
+// Number of spinner frames that the signing screen is shown for
+const SIGNING_TICKS: u8 = 10;
+
+#[derive(Copy, Clone)]
+enum Action {
+    Previous,
+    Next,
+    Confirm,
+    ProgressTick,
+}
+
+impl menu::MenuAction for Action {
+    fn action_for_previous_menu_item() -> Self {
+        Action::Previous
+    }
+
+    fn action_for_next_menu_item() -> Self {
+        Action::Next
+    }
+}
+
+impl review::ReviewAction for Action {
+    fn action_for_previous_step() -> Self {
+        Action::Previous
+    }
+
+    fn action_for_next_step() -> Self {
+        Action::Next
+    }
+
+    fn action_for_decision() -> Self {
+        Action::Confirm
+    }
+}
+
+impl progress::ProgressAction for Action {
+    fn action_for_progress_tick() -> Self {
+        Action::ProgressTick
+    }
+}
 
 enum UiState {
     MainMenu(MainMenuItem),
-    Demo(DemoState),
+    Demo,
+    Signing{ ticks_left: u8 },
     SettingsMenu(SettingsMenuItem),
     TruncateAddressMenu(TruncateAddressMenuItem),
     RecipientMenu(RecipientMenuItem),
     AboutMenu(AboutMenuItem),
 }
 
-#[derive(Copy, Clone)]
-enum DemoState {
-    Notice,
-    Address,
-    Recipient,
-}
-
-impl DemoState {
-    fn next(&self) -> Self {
-        match *self {
-            DemoState::Notice => DemoState::Address,
-            DemoState::Address => DemoState::Recipient,
-            DemoState::Recipient => DemoState::Notice,
-        }
-    }
-}
-
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum MainMenuItem {
     RunDemo,
@@ -75,16 +108,24 @@ enum AboutMenuItem {
     Back,
 }
 
+#[derive(Copy, Clone)]
 enum Recipient {
     Government,
     Charity,
     Myself,
 }
 
-struct AppState {
-    demo_confirms: u32,
+#[derive(Copy, Clone)]
+struct Settings {
     truncate_address: bool,
     recipient: Recipient,
+}
+
+struct AppState {
+    demo_confirms: u32,
+    demo_review: review::Review,
+    demo_progress: progress::Progress,
+    settings: Settings,
     ui_state: UiState,
     ui_version: u16,
 }
@@ -93,72 +134,123 @@ impl AppState {
     fn new() -> Self {
         Self{
             demo_confirms: 0,
-            truncate_address: false,
-            recipient: Recipient::Charity,
+            demo_review: review::Review::new(&[]),
+            demo_progress: progress::Progress::spinner(),
+            settings: SETTINGS.get(),
             ui_state: UiState::MainMenu(MainMenuItem::RunDemo),
             ui_version: 0,
         }
     }
 
+    fn demo_fields(&self) -> [review::Field<'static>; 2] {
+        [
+            review::Field{
+                title: "Address",
+                value: match self.settings.truncate_address {
+                    true => "4ecb..4d51",
+                    false => "4ecbde854d829816358041c8b393fa4d51",
+                },
+            },
+            review::Field{
+                title: "Recipient",
+                value: match self.settings.recipient {
+                    Recipient::Government => "Government",
+                    Recipient::Charity => "Charity",
+                    Recipient::Myself => "Myself",
+                },
+            },
+        ]
+    }
+
     fn update_ui(&mut self, new_state: UiState) {
         self.ui_version += 1;
         self.ui_state = new_state;
     }
+
+    fn update_settings(&mut self, new_settings: Settings) {
+        self.settings = new_settings;
+        SETTINGS.set(new_settings)
+            .expect("Failed to persist settings");
+    }
 }
 
 impl Store for AppState {
-    type Action = BasicAction;
+    type Action = Action;
 
     fn process_action(&mut self, action: Self::Action) {
         match self.ui_state {
             UiState::MainMenu(current_item) => {
                 match action {
-                    BasicAction::Previous => {
+                    Action::Previous => {
                         let new_item = menu::previous_item(current_item, self)
                             .unwrap_or(MainMenuItem::RunDemo);
                         self.update_ui(UiState::MainMenu(new_item));
                     },
-                    BasicAction::Next => {
+                    Action::Next => {
                         let new_item = menu::next_item(current_item, self)
                             .unwrap_or(MainMenuItem::Quit);
                         self.update_ui(UiState::MainMenu(new_item));
                     },
-                    BasicAction::Confirm => match current_item {
-                        MainMenuItem::RunDemo => self.update_ui(UiState::Demo(DemoState::Notice)),
+                    Action::Confirm => match current_item {
+                        MainMenuItem::RunDemo => {
+                            self.demo_review = review::Review::new(&self.demo_fields());
+                            self.update_ui(UiState::Demo);
+                        },
                         MainMenuItem::Settings => self.update_ui(UiState::SettingsMenu(SettingsMenuItem::TruncateAddress)),
                         MainMenuItem::Quit => exit(0),
                     },
+                    Action::ProgressTick => {},
                 }
             },
-            UiState::Demo(state) => {
+            UiState::Demo => {
+                let fields = self.demo_fields();
                 match action {
-                    BasicAction::Previous => {
-                        self.update_ui(UiState::MainMenu(MainMenuItem::RunDemo));
+                    Action::Previous => {
+                        self.demo_review.previous(&fields);
+                        self.update_ui(UiState::Demo);
                     },
-                    BasicAction::Confirm => {
-                        self.demo_confirms += 1;
-                        self.update_ui(UiState::MainMenu(MainMenuItem::RunDemo));
+                    Action::Next => {
+                        self.demo_review.next(&fields);
+                        self.update_ui(UiState::Demo);
                     },
-                    BasicAction::Next => {
-                        self.update_ui(UiState::Demo(state.next()));
+                    Action::Confirm => {
+                        if self.demo_review.step() == review::Step::Accept {
+                            self.demo_confirms += 1;
+                            self.demo_progress = progress::Progress::spinner();
+                            self.update_ui(UiState::Signing{ ticks_left: SIGNING_TICKS });
+                        } else {
+                            self.update_ui(UiState::MainMenu(MainMenuItem::RunDemo));
+                        }
                     },
+                    Action::ProgressTick => {},
+                }
+            },
+            UiState::Signing{ ticks_left } => {
+                // Pretend to be busy for a while before returning to the menu
+                if let Action::ProgressTick = action {
+                    if ticks_left > 1 {
+                        self.demo_progress.advance();
+                        self.update_ui(UiState::Signing{ ticks_left: ticks_left - 1 });
+                    } else {
+                        self.update_ui(UiState::MainMenu(MainMenuItem::RunDemo));
+                    }
                 }
             },
             UiState::SettingsMenu(current_item) => {
                 match action {
-                    BasicAction::Previous => {
+                    Action::Previous => {
                         let new_item = menu::previous_item(current_item, self)
                             .unwrap_or(SettingsMenuItem::TruncateAddress);
                         self.update_ui(UiState::SettingsMenu(new_item));
                     },
-                    BasicAction::Next => {
+                    Action::Next => {
                         let new_item = menu::next_item(current_item, self)
                             .unwrap_or(SettingsMenuItem::Back);
                         self.update_ui(UiState::SettingsMenu(new_item));
                     },
-                    BasicAction::Confirm => match current_item {
+                    Action::Confirm => match current_item {
                         SettingsMenuItem::TruncateAddress => {
-                            let menu_item = if self.truncate_address {
+                            let menu_item = if self.settings.truncate_address {
                                 TruncateAddressMenuItem::Yes
                             } else {
                                 TruncateAddressMenuItem::No
@@ -166,7 +258,7 @@ impl Store for AppState {
                             self.update_ui(UiState::TruncateAddressMenu(menu_item))
                         },
                         SettingsMenuItem::Recipient => {
-                            let menu_item = match self.recipient {
+                            let menu_item = match self.settings.recipient {
                                 Recipient::Government => RecipientMenuItem::Government,
                                 Recipient::Charity => RecipientMenuItem::Charity,
                                 Recipient::Myself => RecipientMenuItem::Myself,
@@ -176,67 +268,77 @@ impl Store for AppState {
                         SettingsMenuItem::About => self.update_ui(UiState::AboutMenu(AboutMenuItem::Version)),
                         SettingsMenuItem::Back => self.update_ui(UiState::MainMenu(MainMenuItem::Settings)),
                     },
+                    Action::ProgressTick => {},
                 }
             },
             UiState::TruncateAddressMenu(current_item) => {
                 match action {
-                    BasicAction::Previous => {
+                    Action::Previous => {
                         let new_item = menu::previous_item(current_item, self)
                             .unwrap_or(TruncateAddressMenuItem::Yes);
                         self.update_ui(UiState::TruncateAddressMenu(new_item));
                     },
-                    BasicAction::Next => {
+                    Action::Next => {
                         let new_item = menu::next_item(current_item, self)
                             .unwrap_or(TruncateAddressMenuItem::No);
                         self.update_ui(UiState::TruncateAddressMenu(new_item));
                     },
-                    BasicAction::Confirm => {
-                        self.truncate_address = match current_item {
-                            TruncateAddressMenuItem::Yes => true,
-                            TruncateAddressMenuItem::No => false,
-                        };
+                    Action::Confirm => {
+                        self.update_settings(Settings{
+                            truncate_address: match current_item {
+                                TruncateAddressMenuItem::Yes => true,
+                                TruncateAddressMenuItem::No => false,
+                            },
+                            ..self.settings
+                        });
                         self.update_ui(UiState::SettingsMenu(SettingsMenuItem::TruncateAddress));
                     },
+                    Action::ProgressTick => {},
                 }
             },
             UiState::RecipientMenu(current_item) => {
                 match action {
-                    BasicAction::Previous => {
+                    Action::Previous => {
                         let new_item = menu::previous_item(current_item, self)
                             .unwrap_or(RecipientMenuItem::Government);
                         self.update_ui(UiState::RecipientMenu(new_item));
                     },
-                    BasicAction::Next => {
+                    Action::Next => {
                         let new_item = menu::next_item(current_item, self)
                             .unwrap_or(RecipientMenuItem::Myself);
                         self.update_ui(UiState::RecipientMenu(new_item));
                     },
-                    BasicAction::Confirm => {
-                        self.recipient = match current_item {
-                            RecipientMenuItem::Government => Recipient::Government,
-                            RecipientMenuItem::Charity => Recipient::Charity,
-                            RecipientMenuItem::Myself => Recipient::Myself,
-                        };
+                    Action::Confirm => {
+                        self.update_settings(Settings{
+                            recipient: match current_item {
+                                RecipientMenuItem::Government => Recipient::Government,
+                                RecipientMenuItem::Charity => Recipient::Charity,
+                                RecipientMenuItem::Myself => Recipient::Myself,
+                            },
+                            ..self.settings
+                        });
                         self.update_ui(UiState::SettingsMenu(SettingsMenuItem::Recipient));
                     },
+                    Action::ProgressTick => {},
                 }
             },
             UiState::AboutMenu(current_item) => {
                 match action {
-                    BasicAction::Previous => {
+                    Action::Previous => {
                         let new_item = menu::previous_item(current_item, self)
                             .unwrap_or(AboutMenuItem::Version);
                         self.update_ui(UiState::AboutMenu(new_item));
                     },
-                    BasicAction::Next => {
+                    Action::Next => {
                         let new_item = menu::next_item(current_item, self)
                             .unwrap_or(AboutMenuItem::Back);
                         self.update_ui(UiState::AboutMenu(new_item));
                     },
-                    BasicAction::Confirm => match current_item {
+                    Action::Confirm => match current_item {
                         AboutMenuItem::Back => self.update_ui(UiState::SettingsMenu(SettingsMenuItem::About)),
                         _ => self.update_ui(UiState::AboutMenu(AboutMenuItem::Back)),
                     },
+                    Action::ProgressTick => {},
                 }
             },
         }
@@ -248,7 +350,7 @@ impl ui::Delegate for AppState {
         self.ui_version
     }
 
-    fn prepare_ui(&self, ctrl: &mut ui::Controller<Self::Action>) {
+    fn prepare_ui<'a>(&'a self, ctrl: &mut ui::Controller<'a, Self::Action>) {
         match self.ui_state {
             UiState::MainMenu(item) => menu::prepare_menu(item, self, ctrl),
             UiState::SettingsMenu(item) => menu::prepare_menu(item, self, ctrl),
@@ -256,93 +358,8 @@ impl ui::Delegate for AppState {
             UiState::RecipientMenu(item) => menu::prepare_menu(item, self, ctrl),
             UiState::AboutMenu(item) => menu::prepare_menu(item, self, ctrl),
 
-            UiState::Demo(state) => {
-                ctrl.set_button_actions(ui::ButtonAction::Map{
-                    left: Some(BasicAction::Previous),
-                    right: Some(BasicAction::Confirm),
-                    both: Some(BasicAction::Next),
-                });
-
-                ctrl.add_view(|| ui::RectangleView{
-                    frame: ui::Frame{ x: 0, y: 0, width: 128, height: 32 },
-                    fill: ui::FillMode::Fill,
-                    ..Default::default()
-                }.into());
-                ctrl.add_view(|| ui::IconView{
-                    position: ui::Position{ x: 3, y: 12 },
-                    icon: ui::SystemIcon::Cross.into(),
-                    ..Default::default()
-                }.into());
-                ctrl.add_view(|| ui::IconView{
-                    position: ui::Position{ x: 117, y: 13 },
-                    icon: ui::SystemIcon::Check.into(),
-                    ..Default::default()
-                }.into());
-
-                let line_1_base = ui::LabelLineView{
-                    frame: ui::Frame{ x: 0, y: 12, width: 128, height: 12 },
-                    font: ui::TextFont::OpenSansRegular11px,
-                    horizontal_alignment: ui::TextHorizontalAlignment::Center,
-                    ..Default::default()
-                };
-                let line_2_base = ui::LabelLineView{
-                    frame: ui::Frame{ x: 23, y: 26, width: 82, height: 12 },
-                    font: ui::TextFont::OpenSansExtraBold11px,
-                    horizontal_alignment: ui::TextHorizontalAlignment::Center,
-                    scroll: ui::ScrollMode::Once{ delay: Duration::from_secs(1), speed: 26 },
-                    ..Default::default()
-                };
-
-                match state {
-                    DemoState::Notice => {
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: "Confirm",
-                            font: ui::TextFont::OpenSansExtraBold11px,
-                            ..line_1_base
-                        }.into());
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: "details",
-                            scroll: ui::ScrollMode::Disabled,
-                            ..line_2_base
-                        }.into());
-                    },
-                    DemoState::Address => {
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: "Address",
-                            ..line_1_base
-                        }.into());
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: match self.truncate_address {
-                                true => "4ecb..4d51",
-                                false => "4ecbde854d829816358041c8b393fa4d51",
-                            },
-                            ..line_2_base
-                        }.into());
-                    },
-                    DemoState::Recipient => {
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: "Recipient",
-                            ..line_1_base
-                        }.into());
-                        ctrl.add_view(|| ui::LabelLineView{
-                            text: match self.recipient {
-                                Recipient::Government => "Government",
-                                Recipient::Charity => "Charity",
-                                Recipient::Myself => "Myself",
-                            },
-                            ..line_2_base
-                        }.into());
-                    },
-                }
-
-                ctrl.set_auto_action(ui::AutoAction::Countdown{
-                    min_wait_time: Some(Duration::from_secs(3)),
-                    max_wait_time: Some(Duration::from_secs(30)),
-                    wait_time: Duration::from_secs(1),
-                    wait_for_scroll: true,
-                    action: BasicAction::Next,
-                });
-            },
+            UiState::Demo => review::prepare_review(&self.demo_review, &self.demo_fields(), ctrl),
+            UiState::Signing{ .. } => progress::prepare_progress(&self.demo_progress, "Signing...", ctrl),
         }
     }
 }
@@ -353,18 +370,18 @@ impl menu::Delegate<MainMenuItem> for AppState {
             icon: Some(icon::badge_rust()),
             line_1: "Press buttons",
             line_2: "to start demo",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(MainMenuItem::Settings, || menu::ItemSpec{
             line_1: "Settings",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(MainMenuItem::Quit, || menu::ItemSpec{
             icon: Some(ui::SystemIcon::DashboardBadge.into()),
             line_1: "Quit",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
     }
@@ -374,23 +391,23 @@ impl menu::Delegate<SettingsMenuItem> for AppState {
     fn prepare_menu_item(&self, ctrl: &mut menu::Controller<SettingsMenuItem, Self::Action>) {
         ctrl.add_item(SettingsMenuItem::TruncateAddress, || menu::ItemSpec{
             line_1: "Truncate address",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(SettingsMenuItem::Recipient, || menu::ItemSpec{
             line_1: "Recipient",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(SettingsMenuItem::About, || menu::ItemSpec{
             line_1: "About",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(SettingsMenuItem::Back, || menu::ItemSpec{
             icon: Some(icon::badge_back()),
             line_1: "Back",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
     }
@@ -400,12 +417,12 @@ impl menu::Delegate<TruncateAddressMenuItem> for AppState {
     fn prepare_menu_item(&self, ctrl: &mut menu::Controller<TruncateAddressMenuItem, Self::Action>) {
         ctrl.add_item(TruncateAddressMenuItem::Yes, || menu::ItemSpec{
             line_1: "Yes",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(TruncateAddressMenuItem::No, || menu::ItemSpec{
             line_1: "No",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
     }
@@ -415,17 +432,17 @@ impl menu::Delegate<RecipientMenuItem> for AppState {
     fn prepare_menu_item(&self, ctrl: &mut menu::Controller<RecipientMenuItem, Self::Action>) {
         ctrl.add_item(RecipientMenuItem::Government, || menu::ItemSpec{
             line_1: "Government",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(RecipientMenuItem::Charity, || menu::ItemSpec{
             line_1: "Charity",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
         ctrl.add_item(RecipientMenuItem::Myself, || menu::ItemSpec{
             line_1: "Myself",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
     }
@@ -435,7 +452,7 @@ impl menu::Delegate<AboutMenuItem> for AppState {
     fn prepare_menu_item(&self, ctrl: &mut menu::Controller<AboutMenuItem, Self::Action>) {
         ctrl.add_item(AboutMenuItem::Version, || menu::ItemSpec{
             line_1: "Version",
-            line_2: "1.2.3",
+            line_2: APP_MANIFEST.version(),
             line_1_font: ui::TextFont::OpenSansRegular11px,
             ..Default::default()
         });
@@ -454,7 +471,7 @@ impl menu::Delegate<AboutMenuItem> for AppState {
         ctrl.add_item(AboutMenuItem::Back, || menu::ItemSpec{
             icon: Some(icon::badge_back()),
             line_1: "Back",
-            action: Some(BasicAction::Confirm),
+            action: Some(Action::Confirm),
             ..Default::default()
         });
     }
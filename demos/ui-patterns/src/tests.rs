@@ -0,0 +1,147 @@
+use bolos::crypto::bip32::{self, DerivationPath};
+use bolos::crypto::ecc::Curve;
+use bolos::error::SystemError;
+use bolos::simulator::{Simulator, Button, Syscall};
+use bolos::simulator::snapshot::{UiRunner, assert_snapshot};
+use bolos::time::Duration;
+use bolos::ui::review;
+use super::{SIGNING_TICKS, Action, AppState, UiState, MainMenuItem, SettingsMenuItem, AboutMenuItem};
+
+fn runner() -> UiRunner<Action, AppState> {
+    UiRunner::new(AppState::new())
+}
+
+#[test]
+fn main_menu() {
+    let mut runner = runner();
+    assert_snapshot(&runner.screen(), "snapshots/main_menu_run_demo.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/main_menu_settings.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/main_menu_quit.txt");
+}
+
+#[test]
+fn settings_menu() {
+    let mut runner = runner();
+    runner.press_button(Button::Right);
+    runner.press_button(Button::Both);
+    match runner.delegate().ui_state {
+        UiState::SettingsMenu(SettingsMenuItem::TruncateAddress) => {},
+        _ => panic!("Settings menu wasn't opened"),
+    }
+    assert_snapshot(&runner.screen(), "snapshots/settings_menu_truncate_address.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/settings_menu_recipient.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/settings_menu_about.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/settings_menu_back.txt");
+
+    runner.press_button(Button::Both);
+    match runner.delegate().ui_state {
+        UiState::MainMenu(MainMenuItem::Settings) => {},
+        _ => panic!("Main menu wasn't opened"),
+    }
+}
+
+#[test]
+fn about_menu() {
+    let mut runner = runner();
+    runner.update(|state| state.update_ui(UiState::AboutMenu(AboutMenuItem::Version)));
+    assert_snapshot(&runner.screen(), "snapshots/about_menu_version.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/about_menu_developer.txt");
+
+    runner.press_button(Button::Right);
+    assert_snapshot(&runner.screen(), "snapshots/about_menu_source_code.txt");
+}
+
+#[test]
+fn review_flow() {
+    let mut runner = runner();
+    runner.press_button(Button::Both);
+    match runner.delegate().ui_state {
+        UiState::Demo => {},
+        _ => panic!("Demo wasn't started"),
+    }
+
+    // Every page of every field, then the accept and reject screens
+    let mut page = 0;
+    while let review::Step::Field{ .. } = runner.delegate().demo_review.step() {
+        assert_snapshot(&runner.screen(), format!("snapshots/review_page_{}.txt", page));
+        runner.press_button(Button::Right);
+        page += 1;
+    }
+    assert!(page > 2, "Address didn't span several pages");
+
+    assert!(runner.delegate().demo_review.step() == review::Step::Accept);
+    assert_snapshot(&runner.screen(), "snapshots/review_accept.txt");
+
+    runner.press_button(Button::Right);
+    assert!(runner.delegate().demo_review.step() == review::Step::Reject);
+    assert_snapshot(&runner.screen(), "snapshots/review_reject.txt");
+
+    // Accepting counts as a confirmation and shows a spinner for a while
+    runner.press_button(Button::Left);
+    runner.press_button(Button::Both);
+    assert_eq!(runner.delegate().demo_confirms, 1);
+    match runner.delegate().ui_state {
+        UiState::Signing{ .. } => {},
+        _ => panic!("Signing wasn't started"),
+    }
+    assert_snapshot(&runner.screen(), "snapshots/signing_0.txt");
+
+    runner.advance(Duration::from_millis(200));
+    assert_snapshot(&runner.screen(), "snapshots/signing_1.txt");
+
+    for _ in 1..SIGNING_TICKS {
+        runner.advance(Duration::from_millis(200));
+    }
+    match runner.delegate().ui_state {
+        UiState::MainMenu(MainMenuItem::RunDemo) => {},
+        _ => panic!("Main menu wasn't opened"),
+    }
+}
+
+#[test]
+fn rejecting_returns_to_main_menu() {
+    let mut runner = runner();
+    runner.press_button(Button::Both);
+    while runner.delegate().demo_review.step() != review::Step::Reject {
+        runner.press_button(Button::Right);
+    }
+
+    runner.press_button(Button::Both);
+    assert_eq!(runner.delegate().demo_confirms, 0);
+    match runner.delegate().ui_state {
+        UiState::MainMenu(MainMenuItem::RunDemo) => {},
+        _ => panic!("Main menu wasn't opened"),
+    }
+}
+
+#[test]
+fn manifest_limits_derivation() {
+    let mut sim = Simulator::new();
+    sim.script_syscall(Syscall::OsPersoDeriveNodeBip32, Ok(0));
+
+    let bitcoin = DerivationPath::parse("m/44'/0'/0'/0/0").unwrap();
+    assert!(bip32::derive(Curve::Secp256k1, &bitcoin).is_ok());
+
+    let ethereum = DerivationPath::parse("m/44'/60'/0'/0/0").unwrap();
+    match bip32::derive(Curve::Secp256k1, &ethereum) {
+        Err(SystemError::Security) => {},
+        _ => panic!("Path outside of the manifest wasn't refused"),
+    }
+    let hardened = DerivationPath::parse("m/44'/0'/0'").unwrap();
+    match bip32::derive(Curve::Ed25519, &hardened) {
+        Err(SystemError::Security) => {},
+        _ => panic!("Curve outside of the manifest wasn't refused"),
+    }
+}
@@ -0,0 +1,370 @@
+use byteorder::{ByteOrder, BigEndian};
+use state::Store;
+
+const HEADER_SIZE: usize = 4;
+const STATUS_WORD_SIZE: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StatusWord {
+    Ok,
+    WrongLength,
+    SecurityStatusNotSatisfied,
+    ConditionsNotSatisfied,
+    IncorrectData,
+    NotEnoughMemory,
+    ReferencedDataNotFound,
+    IncorrectParameters,
+    InstructionNotSupported,
+    ClassNotSupported,
+    Unknown,
+    Custom(u16),
+}
+
+impl StatusWord {
+    pub fn to_wire_format(&self) -> u16 {
+        match self {
+            &StatusWord::Ok => 0x9000,
+            &StatusWord::WrongLength => 0x6700,
+            &StatusWord::SecurityStatusNotSatisfied => 0x6982,
+            &StatusWord::ConditionsNotSatisfied => 0x6985,
+            &StatusWord::IncorrectData => 0x6A80,
+            &StatusWord::NotEnoughMemory => 0x6A84,
+            &StatusWord::ReferencedDataNotFound => 0x6A88,
+            &StatusWord::IncorrectParameters => 0x6B00,
+            &StatusWord::InstructionNotSupported => 0x6D00,
+            &StatusWord::ClassNotSupported => 0x6E00,
+            &StatusWord::Unknown => 0x6F00,
+            &StatusWord::Custom(sw) => sw,
+        }
+    }
+}
+
+/// Parsed short command APDU
+pub struct Command<'a> {
+    pub cla: u8,
+    pub ins: u8,
+    pub p1: u8,
+    pub p2: u8,
+    pub lc: usize,
+    pub data: &'a [u8],
+    pub le: Option<usize>,
+}
+
+impl<'a> Command<'a> {
+    pub fn from_bytes(raw: &'a [u8]) -> Result<Self, StatusWord> {
+        if raw.len() < HEADER_SIZE {
+            return Err(StatusWord::WrongLength);
+        }
+
+        let (lc, le) = if raw.len() == HEADER_SIZE {
+            // Case 1: no data, no response data
+            (0, None)
+        } else if raw.len() == HEADER_SIZE + 1 {
+            // Case 2: no data, expecting response data
+            (0, Some(raw[4]))
+        } else {
+            let lc = raw[4] as usize;
+            if lc == 0 {
+                // Extended length APDUs aren't supported
+                return Err(StatusWord::WrongLength);
+            } else if raw.len() == HEADER_SIZE + 1 + lc {
+                // Case 3: data, no response data
+                (lc, None)
+            } else if raw.len() == HEADER_SIZE + 1 + lc + 1 {
+                // Case 4: data, expecting response data
+                (lc, Some(raw[HEADER_SIZE + 1 + lc]))
+            } else {
+                return Err(StatusWord::WrongLength);
+            }
+        };
+
+        let data_start = HEADER_SIZE + 1;
+        let data = if lc > 0 {
+            &raw[data_start..data_start+lc]
+        } else {
+            &[]
+        };
+
+        Ok(Self{
+            cla: raw[0],
+            ins: raw[1],
+            p1: raw[2],
+            p2: raw[3],
+            lc,
+            data,
+            // Le of 0 means that up to 256 bytes are expected
+            le: le.map(|le| if le == 0 { 256 } else { le as usize }),
+        })
+    }
+}
+
+/// Builder for response APDUs, the status word gets appended
+/// when the response is finished
+pub struct Response<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    deferred: bool,
+}
+
+impl<'a> Response<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self{
+            buf,
+            len: 0,
+            deferred: false,
+        }
+    }
+
+    /// Leaves the command without a response for now, for example until
+    /// the user has confirmed it. `Delegate::resume_command` is called on
+    /// the following events until it stops deferring the response.
+    pub fn defer(&mut self) {
+        self.deferred = true;
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        self.deferred
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.buf.len().saturating_sub(STATUS_WORD_SIZE)
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn append(&mut self, data: &[u8]) -> Result<(), StatusWord> {
+        let end = self.len + data.len();
+        if end > self.capacity() {
+            return Err(StatusWord::NotEnoughMemory);
+        }
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    pub fn append_u8(&mut self, value: u8) -> Result<(), StatusWord> {
+        self.append(&[value])
+    }
+
+    pub fn append_u16(&mut self, value: u16) -> Result<(), StatusWord> {
+        let mut tmp = [0; 2];
+        BigEndian::write_u16(&mut tmp, value);
+        self.append(&tmp)
+    }
+
+    pub fn append_u32(&mut self, value: u32) -> Result<(), StatusWord> {
+        let mut tmp = [0; 4];
+        BigEndian::write_u32(&mut tmp, value);
+        self.append(&tmp)
+    }
+
+    /// Appends the status word and returns the total length of the
+    /// response. Fails when the buffer doesn't have room for the status
+    /// word, which is only possible when it's shorter than 2 bytes.
+    pub fn finish(self, status: StatusWord) -> Result<usize, StatusWord> {
+        let end = self.len + STATUS_WORD_SIZE;
+        if end > self.buf.len() {
+            return Err(StatusWord::NotEnoughMemory);
+        }
+        BigEndian::write_u16(&mut self.buf[self.len..end], status.to_wire_format());
+        Ok(end)
+    }
+}
+
+pub trait Delegate: Store {
+    /// Instruction class that the application responds to
+    fn class(&self) -> u8;
+
+    /// Maps an instruction to an action, `None` for unsupported instructions
+    fn action_for_instruction(&self, ins: u8) -> Option<Self::Action>;
+
+    /// Processes the command for the action that the instruction mapped to.
+    /// By default the action is passed on to `Store::process_action` and an
+    /// empty response is sent.
+    fn process_command(
+        &mut self,
+        action: Self::Action,
+        _command: &Command,
+        _response: &mut Response,
+    ) -> Result<(), StatusWord> {
+        self.process_action(action);
+        Ok(())
+    }
+
+    /// Gives the delegate another chance to respond to a command whose
+    /// response it deferred. The response can be deferred again.
+    fn resume_command(
+        &mut self,
+        _action: Self::Action,
+        _command: &Command,
+        _response: &mut Response,
+    ) -> Result<(), StatusWord> {
+        Err(StatusWord::ConditionsNotSatisfied)
+    }
+
+    /// Called when the transport dropped a deferred command, which can
+    /// no longer be responded to
+    fn cancel_command(&mut self) {}
+}
+
+/// Parses the `raw` command APDU and dispatches it to the delegate,
+/// writing the response APDU into `buf` and returning its length. Returns
+/// `None` when the delegate deferred the response and fails when `buf`
+/// doesn't fit the status word.
+pub fn dispatch<D>(delegate: &mut D, raw: &[u8], buf: &mut [u8]) -> Result<Option<usize>, StatusWord>
+    where D: Delegate
+{
+    process(delegate, raw, buf, false)
+}
+
+/// Same as `dispatch`, but for a command whose response was deferred
+pub fn resume<D>(delegate: &mut D, raw: &[u8], buf: &mut [u8]) -> Result<Option<usize>, StatusWord>
+    where D: Delegate
+{
+    process(delegate, raw, buf, true)
+}
+
+fn process<D>(delegate: &mut D, raw: &[u8], buf: &mut [u8], resume: bool) -> Result<Option<usize>, StatusWord>
+    where D: Delegate
+{
+    let mut response = Response::new(buf);
+
+    let status = if raw.len() < HEADER_SIZE {
+        StatusWord::WrongLength
+    } else if raw[0] != delegate.class() {
+        StatusWord::ClassNotSupported
+    } else {
+        match delegate.action_for_instruction(raw[1]) {
+            None => StatusWord::InstructionNotSupported,
+            Some(action) => {
+                let result = Command::from_bytes(raw).and_then(|cmd| if resume {
+                    delegate.resume_command(action, &cmd, &mut response)
+                } else {
+                    delegate.process_command(action, &cmd, &mut response)
+                });
+                match result {
+                    Ok(()) if response.is_deferred() => return Ok(None),
+                    Ok(()) => StatusWord::Ok,
+                    Err(sw) => {
+                        response.clear();
+                        sw
+                    },
+                }
+            },
+        }
+    };
+
+    response.finish(status).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct App;
+
+    impl Store for App {
+        type Action = u8;
+    }
+
+    impl Delegate for App {
+        fn class(&self) -> u8 {
+            0xE0
+        }
+
+        fn action_for_instruction(&self, ins: u8) -> Option<Self::Action> {
+            if ins == 0x01 {
+                Some(ins)
+            } else {
+                None
+            }
+        }
+
+        fn process_command(
+            &mut self,
+            _action: Self::Action,
+            command: &Command,
+            response: &mut Response,
+        ) -> Result<(), StatusWord> {
+            response.append(command.data)
+        }
+    }
+
+    fn status_of(raw: &[u8]) -> u16 {
+        let mut buf = [0; 8];
+        let len = dispatch(&mut App, raw, &mut buf).unwrap().unwrap();
+        BigEndian::read_u16(&buf[len - 2..len])
+    }
+
+    #[test]
+    fn maps_errors_to_status_words() {
+        assert_eq!(status_of(&[0xE0, 0x01, 0x00]), 0x6700);
+        assert_eq!(status_of(&[0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA]), 0x6700);
+        assert_eq!(status_of(&[0xE0, 0x02, 0x00, 0x00]), 0x6D00);
+        assert_eq!(status_of(&[0xB0, 0x01, 0x00, 0x00]), 0x6E00);
+        assert_eq!(status_of(&[0xE0, 0x01, 0x00, 0x00]), 0x9000);
+    }
+
+    #[test]
+    fn responds_with_data_and_status_word() {
+        let mut buf = [0; 8];
+        let len = dispatch(&mut App, &[0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA, 0xBB], &mut buf);
+        assert_eq!(len, Ok(Some(4)));
+        assert_eq!(&buf[0..4], &[0xAA, 0xBB, 0x90, 0x00]);
+
+        // Data that doesn't fit is replaced with the error
+        let mut buf = [0; 3];
+        let len = dispatch(&mut App, &[0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA, 0xBB], &mut buf);
+        assert_eq!(len, Ok(Some(2)));
+        assert_eq!(&buf[0..2], &[0x6A, 0x84]);
+    }
+
+    #[test]
+    fn finish_needs_room_for_status_word() {
+        let mut buf = [0; 1];
+        assert_eq!(Response::new(&mut buf).finish(StatusWord::Ok), Err(StatusWord::NotEnoughMemory));
+        assert_eq!(dispatch(&mut App, &[0xE0, 0x01, 0x00, 0x00], &mut buf), Err(StatusWord::NotEnoughMemory));
+
+        let mut buf = [0; 2];
+        assert_eq!(Response::new(&mut buf).finish(StatusWord::Custom(0x6FAA)), Ok(2));
+        assert_eq!(buf, [0x6F, 0xAA]);
+    }
+
+    #[test]
+    fn parses_short_apdus() {
+        // Case 1, no data and no response data
+        let cmd = Command::from_bytes(&[0xE0, 0x01, 0x02, 0x03]).unwrap();
+        assert_eq!((cmd.cla, cmd.ins, cmd.p1, cmd.p2), (0xE0, 0x01, 0x02, 0x03));
+        assert_eq!((cmd.lc, cmd.data, cmd.le), (0, &[][..], None));
+
+        // Case 2, Le of 0 stands for 256
+        let cmd = Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x20]).unwrap();
+        assert_eq!((cmd.lc, cmd.le), (0, Some(0x20)));
+        let cmd = Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(cmd.le, Some(256));
+
+        // Case 3, data and no response data
+        let cmd = Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA, 0xBB]).unwrap();
+        assert_eq!((cmd.lc, cmd.data, cmd.le), (2, &[0xAA, 0xBB][..], None));
+
+        // Case 4, data and response data
+        let cmd = Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x01, 0xAA, 0x00]).unwrap();
+        assert_eq!((cmd.lc, cmd.data, cmd.le), (1, &[0xAA][..], Some(256)));
+    }
+
+    #[test]
+    fn refuses_malformed_and_extended_apdus() {
+        assert_eq!(Command::from_bytes(&[0xE0, 0x01, 0x00]).err(), Some(StatusWord::WrongLength));
+        // Lc doesn't match the data
+        assert_eq!(Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x03, 0xAA]).err(), Some(StatusWord::WrongLength));
+        assert_eq!(Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x01, 0xAA, 0xBB, 0xCC]).err(), Some(StatusWord::WrongLength));
+        // Extended length, Lc of 0 followed by 2 length bytes
+        assert_eq!(Command::from_bytes(&[0xE0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0xAA]).err(), Some(StatusWord::WrongLength));
+    }
+}
@@ -0,0 +1,280 @@
+use byteorder::{ByteOrder, BigEndian};
+use error::SystemError;
+use manifest;
+use syscall;
+use super::zeroize;
+use super::ecc::{Curve, PrivateKey, PRIVATE_KEY_SIZE};
+
+pub const MAX_DEPTH: usize = 10;
+pub const HARDENED: u32 = 0x8000_0000;
+
+pub(crate) const CHAIN_CODE_SIZE: usize = 32;
+// Some curves (Ed25519) produce an extended 64 byte private key
+pub(crate) const NODE_KEY_SIZE: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PathError {
+    /// Path has more than `MAX_DEPTH` components
+    TooDeep,
+    /// Component isn't a number or is out of range for its kind
+    InvalidComponent,
+    /// Encoded path length doesn't match the component count
+    InvalidLength,
+    /// Component that has to be hardened isn't
+    NotHardened,
+}
+
+#[derive(Clone, Copy)]
+pub struct DerivationPath {
+    depth: usize,
+    indices: [u32; MAX_DEPTH],
+}
+
+impl DerivationPath {
+    pub fn new(indices: &[u32]) -> Result<Self, PathError> {
+        if indices.len() > MAX_DEPTH {
+            return Err(PathError::TooDeep);
+        }
+
+        let mut path = Self{
+            depth: indices.len(),
+            indices: [0; MAX_DEPTH],
+        };
+        path.indices[0..indices.len()].copy_from_slice(indices);
+        Ok(path)
+    }
+
+    /// Parses the textual form of the path, e.g. `m/44'/0'/0'/0/0`. Both
+    /// `'` and `h` suffixes are accepted for hardened components.
+    pub fn parse(text: &str) -> Result<Self, PathError> {
+        let mut path = Self::new(&[])?;
+
+        let mut components = text.split('/');
+        if components.next() != Some("m") {
+            return Err(PathError::InvalidComponent);
+        }
+
+        for component in components {
+            if path.depth == MAX_DEPTH {
+                return Err(PathError::TooDeep);
+            }
+
+            let (digits, hardened) = if component.ends_with('\'')
+                || component.ends_with('h')
+                || component.ends_with('H') {
+                (&component[0..component.len()-1], true)
+            } else {
+                (component, false)
+            };
+
+            if digits.len() == 0 || !digits.bytes().all(|b| b >= b'0' && b <= b'9') {
+                return Err(PathError::InvalidComponent);
+            }
+            let index = digits.parse::<u32>()
+                .map_err(|_| PathError::InvalidComponent)?;
+            if index >= HARDENED {
+                return Err(PathError::InvalidComponent);
+            }
+
+            path.indices[path.depth] = if hardened {
+                index | HARDENED
+            } else {
+                index
+            };
+            path.depth += 1;
+        }
+
+        Ok(path)
+    }
+
+    /// Parses the path from APDU data, where the first byte is the
+    /// component count followed by 4 byte big-endian indices
+    pub fn from_apdu_bytes(raw: &[u8]) -> Result<Self, PathError> {
+        if raw.len() < 1 {
+            return Err(PathError::InvalidLength);
+        }
+
+        let depth = raw[0] as usize;
+        if depth > MAX_DEPTH {
+            return Err(PathError::TooDeep);
+        }
+        if raw.len() != 1 + 4 * depth {
+            return Err(PathError::InvalidLength);
+        }
+
+        let mut path = Self::new(&[])?;
+        for (i, chunk) in raw[1..].chunks(4).enumerate() {
+            path.indices[i] = BigEndian::read_u32(chunk);
+        }
+        path.depth = depth;
+        Ok(path)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices[0..self.depth]
+    }
+
+    pub fn is_hardened(&self, depth: usize) -> bool {
+        depth < self.depth && self.indices[depth] & HARDENED != 0
+    }
+
+    pub fn is_fully_hardened(&self) -> bool {
+        self.indices().iter().all(|i| i & HARDENED != 0)
+    }
+
+    pub fn starts_with(&self, prefix: &[u32]) -> bool {
+        self.indices().starts_with(prefix)
+    }
+
+    /// Checks that the path has at least `count` components and that
+    /// they're all hardened, e.g. 3 for the `purpose'/coin_type'/account'`
+    /// levels of BIP-44 paths.
+    ///
+    /// ```ignore
+    /// let path = DerivationPath::from_apdu_bytes(data)?.require_hardened(3)?;
+    /// ```
+    pub fn require_hardened(self, count: usize) -> Result<Self, PathError> {
+        if (0..count).all(|depth| self.is_hardened(depth)) {
+            Ok(self)
+        } else {
+            Err(PathError::NotHardened)
+        }
+    }
+}
+
+/// Private key and chain code of a derived node, the secrets are
+/// wiped from memory when the value is dropped
+pub struct ExtendedPrivateKey {
+    curve: Curve,
+    key: [u8; NODE_KEY_SIZE],
+    chain_code: [u8; CHAIN_CODE_SIZE],
+}
+
+impl ExtendedPrivateKey {
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    pub fn chain_code(&self) -> &[u8; CHAIN_CODE_SIZE] {
+        &self.chain_code
+    }
+
+    pub fn private_key(&self) -> Result<PrivateKey, SystemError> {
+        PrivateKey::from_bytes(self.curve, &self.key[0..PRIVATE_KEY_SIZE])
+    }
+}
+
+impl Drop for ExtendedPrivateKey {
+    fn drop(&mut self) {
+        zeroize(&mut self.key, 0);
+        zeroize(&mut self.chain_code, 0);
+    }
+}
+
+/// Derives the node at `path` from the device seed. Ed25519 only
+/// supports hardened derivation, so all components must be hardened.
+/// Curves and paths that the app manifest doesn't allow fail with
+/// `SystemError::Security`.
+pub fn derive(curve: Curve, path: &DerivationPath) -> Result<ExtendedPrivateKey, SystemError> {
+    if curve == Curve::Ed25519 && !path.is_fully_hardened() {
+        return Err(SystemError::InvalidParameter);
+    }
+    manifest::check_derivation(curve, path.indices())?;
+
+    let mut node = ExtendedPrivateKey{
+        curve,
+        key: [0; NODE_KEY_SIZE],
+        chain_code: [0; CHAIN_CODE_SIZE],
+    };
+    syscall::os_perso_derive_node_bip32(
+        curve.to_wire_format(),
+        path.indices(),
+        &mut node.key,
+        &mut node.chain_code,
+    ).map(|_| node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checks_depth() {
+        let indices = [HARDENED; MAX_DEPTH + 1];
+        assert_eq!(DerivationPath::new(&indices[0..MAX_DEPTH]).unwrap().depth(), MAX_DEPTH);
+        assert_eq!(DerivationPath::new(&indices).err(), Some(PathError::TooDeep));
+        assert_eq!(DerivationPath::new(&[]).unwrap().indices(), &[]);
+    }
+
+    #[test]
+    fn parse() {
+        let path = DerivationPath::parse("m/44'/0h/1H/0/7").unwrap();
+        assert_eq!(path.indices(), &[44 | HARDENED, HARDENED, 1 | HARDENED, 0, 7]);
+        assert_eq!(DerivationPath::parse("m").unwrap().depth(), 0);
+
+        assert_eq!(DerivationPath::parse("").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("44'/0'").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/'").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/+1").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/2147483648").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/4294967296").err(), Some(PathError::InvalidComponent));
+        assert_eq!(DerivationPath::parse("m/0/0/0/0/0/0/0/0/0/0/0").err(), Some(PathError::TooDeep));
+    }
+
+    #[test]
+    fn from_apdu_bytes() {
+        let path = DerivationPath::from_apdu_bytes(&[
+            2,
+            0x80, 0x00, 0x00, 0x2C,
+            0x00, 0x00, 0x01, 0x02,
+        ]).unwrap();
+        assert_eq!(path.indices(), &[44 | HARDENED, 0x102]);
+    }
+
+    #[test]
+    fn from_apdu_bytes_zero_depth() {
+        let path = DerivationPath::from_apdu_bytes(&[0]).unwrap();
+        assert_eq!(path.depth(), 0);
+        assert_eq!(path.indices(), &[]);
+    }
+
+    #[test]
+    fn from_apdu_bytes_wrong_length() {
+        assert_eq!(DerivationPath::from_apdu_bytes(&[]).err(), Some(PathError::InvalidLength));
+        assert_eq!(DerivationPath::from_apdu_bytes(&[0, 0]).err(), Some(PathError::InvalidLength));
+        assert_eq!(DerivationPath::from_apdu_bytes(&[1, 0, 0, 0]).err(), Some(PathError::InvalidLength));
+        assert_eq!(DerivationPath::from_apdu_bytes(&[1, 0, 0, 0, 0, 0]).err(), Some(PathError::InvalidLength));
+        assert_eq!(DerivationPath::from_apdu_bytes(&[2, 0, 0, 0, 0]).err(), Some(PathError::InvalidLength));
+    }
+
+    #[test]
+    fn from_apdu_bytes_too_deep() {
+        let mut raw = [0; 1 + 4 * (MAX_DEPTH + 1)];
+        raw[0] = MAX_DEPTH as u8;
+        assert_eq!(DerivationPath::from_apdu_bytes(&raw[0..1 + 4 * MAX_DEPTH]).unwrap().depth(), MAX_DEPTH);
+        raw[0] = MAX_DEPTH as u8 + 1;
+        assert_eq!(DerivationPath::from_apdu_bytes(&raw).err(), Some(PathError::TooDeep));
+        raw[0] = 0xFF;
+        assert_eq!(DerivationPath::from_apdu_bytes(&raw).err(), Some(PathError::TooDeep));
+    }
+
+    #[test]
+    fn require_hardened() {
+        let path = DerivationPath::new(&[44 | HARDENED, HARDENED, HARDENED, 0, 0]).unwrap();
+        assert!(path.require_hardened(0).is_ok());
+        assert!(path.require_hardened(3).is_ok());
+        assert_eq!(path.require_hardened(4).err(), Some(PathError::NotHardened));
+
+        let path = DerivationPath::new(&[44 | HARDENED, 0, HARDENED]).unwrap();
+        assert_eq!(path.require_hardened(3).err(), Some(PathError::NotHardened));
+
+        // Missing components aren't hardened either
+        let path = DerivationPath::new(&[44 | HARDENED, HARDENED]).unwrap();
+        assert_eq!(path.require_hardened(3).err(), Some(PathError::NotHardened));
+    }
+}
@@ -0,0 +1,196 @@
+use error::SystemError;
+use manifest;
+use syscall;
+use super::{CX_LAST, HashId, zeroize};
+
+const CX_RND_RFC6979: u32 = 3 << 9;
+const CX_ECCINFO_PARITY_ODD: u32 = 1 << 0;
+
+pub const PRIVATE_KEY_SIZE: usize = 32;
+pub const PUBLIC_KEY_MAX_SIZE: usize = 65;
+pub const SIGNATURE_MAX_SIZE: usize = 72;
+
+// Sizes of the key structures without the trailing padding
+pub(crate) const RAW_PRIVATE_KEY_SIZE: usize = 8 + PRIVATE_KEY_SIZE;
+pub(crate) const RAW_PUBLIC_KEY_SIZE: usize = 8 + PUBLIC_KEY_MAX_SIZE;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Secp256k1,
+    Secp256r1,
+    Ed25519,
+}
+
+impl Curve {
+    pub(crate) fn to_wire_format(&self) -> u32 {
+        match self {
+            &Curve::Secp256k1 => 0x21,
+            &Curve::Secp256r1 => 0x22,
+            &Curve::Ed25519 => 0x41,
+        }
+    }
+
+    fn is_weierstrass(&self) -> bool {
+        match self {
+            &Curve::Secp256k1 |
+            &Curve::Secp256r1 => true,
+            &Curve::Ed25519 => false,
+        }
+    }
+}
+
+// Layout matches cx_ecfp_private_key_t
+#[repr(C)]
+struct RawPrivateKey {
+    curve: u32,
+    d_len: u32,
+    d: [u8; PRIVATE_KEY_SIZE],
+}
+
+// Layout matches cx_ecfp_public_key_t
+#[repr(C)]
+struct RawPublicKey {
+    curve: u32,
+    w_len: u32,
+    w: [u8; PUBLIC_KEY_MAX_SIZE],
+}
+
+pub struct PrivateKey {
+    curve: Curve,
+    raw: RawPrivateKey,
+}
+
+impl PrivateKey {
+    pub fn from_bytes(curve: Curve, bytes: &[u8]) -> Result<Self, SystemError> {
+        if bytes.len() != PRIVATE_KEY_SIZE {
+            return Err(SystemError::InvalidParameter);
+        }
+
+        let mut key = Self{
+            curve,
+            raw: RawPrivateKey{
+                curve: 0,
+                d_len: 0,
+                d: [0; PRIVATE_KEY_SIZE],
+            },
+        };
+        let key_ptr = &mut key.raw as *mut RawPrivateKey as *mut u8;
+        syscall::cx_ecfp_init_private_key(curve.to_wire_format(), bytes, key_ptr)
+            .map(|_| key)
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey, SystemError> {
+        let mut key = PublicKey{
+            curve: self.curve,
+            raw: RawPublicKey{
+                curve: 0,
+                w_len: 0,
+                w: [0; PUBLIC_KEY_MAX_SIZE],
+            },
+        };
+        // The private key is left untouched when asked to keep it
+        let private_ptr = &self.raw as *const RawPrivateKey as *mut u8;
+        let public_ptr = &mut key.raw as *mut RawPublicKey as *mut u8;
+        syscall::cx_ecfp_generate_pair(self.curve.to_wire_format(), public_ptr, private_ptr, true)
+            .map(|_| key)
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        &self.raw as *const RawPrivateKey as *const u8
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        zeroize(&mut self.raw.d, 0);
+    }
+}
+
+pub struct PublicKey {
+    curve: Curve,
+    raw: RawPublicKey,
+}
+
+impl PublicKey {
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    /// Uncompressed point encoding (`0x04 || x || y`)
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw.w[0..self.raw.w_len as usize]
+    }
+}
+
+pub struct Signature {
+    len: usize,
+    bytes: [u8; SIGNATURE_MAX_SIZE],
+    info: u32,
+}
+
+impl Signature {
+    fn new() -> Self {
+        Self{
+            len: 0,
+            bytes: [0; SIGNATURE_MAX_SIZE],
+            info: 0,
+        }
+    }
+
+    /// DER encoded signature for ECDSA, 64 byte `R || S` for EdDSA
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[0..self.len]
+    }
+
+    /// Parity of the `R` point y coordinate, used to derive the
+    /// recovery id for ECDSA signatures
+    pub fn is_parity_odd(&self) -> bool {
+        self.info & CX_ECCINFO_PARITY_ODD != 0
+    }
+}
+
+/// Signs the `hash` with a deterministic (RFC 6979) nonce. Keys on curves
+/// that the app manifest doesn't allow fail with `SystemError::Security`.
+pub fn ecdsa_sign(key: &PrivateKey, hash: &[u8]) -> Result<Signature, SystemError> {
+    if !key.curve.is_weierstrass() {
+        return Err(SystemError::InvalidParameter);
+    }
+    manifest::check_curve(key.curve)?;
+
+    let mut sig = Signature::new();
+    syscall::cx_ecdsa_sign(
+        key.as_ptr(),
+        CX_RND_RFC6979 | CX_LAST,
+        HashId::Sha256 as u32,
+        hash,
+        &mut sig.bytes,
+        &mut sig.info,
+    ).map(|len| {
+        sig.len = len;
+        sig
+    })
+}
+
+pub fn eddsa_sign(key: &PrivateKey, message: &[u8]) -> Result<Signature, SystemError> {
+    if key.curve != Curve::Ed25519 {
+        return Err(SystemError::InvalidParameter);
+    }
+    manifest::check_curve(key.curve)?;
+
+    let mut sig = Signature::new();
+    syscall::cx_eddsa_sign(
+        key.as_ptr(),
+        CX_LAST,
+        HashId::Sha512 as u32,
+        message,
+        &mut sig.bytes,
+        &mut sig.info,
+    ).map(|len| {
+        sig.len = len;
+        sig
+    })
+}
@@ -0,0 +1,342 @@
+use error::SystemError;
+use syscall;
+use super::{CX_LAST, zeroize};
+
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError>;
+}
+
+fn update_context(ctx: *mut u8, data: &[u8]) -> Result<(), SystemError> {
+    syscall::cx_hash(ctx, 0, data, &mut [])
+        .map(|_| ())
+}
+
+fn finalize_context(ctx: *mut u8, out: &mut [u8]) -> Result<(), SystemError> {
+    syscall::cx_hash(ctx, CX_LAST, &[], out)
+        .map(|_| ())
+}
+
+// Context sizes match the firmware structures (cx_sha256_t, etc.)
+pub(crate) const SHA256_CONTEXT_SIZE: usize = 27;
+pub(crate) const SHA512_CONTEXT_SIZE: usize = 51;
+pub(crate) const RIPEMD160_CONTEXT_SIZE: usize = 24;
+pub(crate) const KECCAK_CONTEXT_SIZE: usize = 53;
+pub(crate) const BLAKE2B_CONTEXT_SIZE: usize = 32;
+
+pub struct Sha256 {
+    ctx: [u32; SHA256_CONTEXT_SIZE],
+}
+
+impl Sha256 {
+    pub fn new() -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; SHA256_CONTEXT_SIZE],
+        };
+        syscall::cx_sha256_init(h.ctx.as_mut_ptr() as *mut u8)
+            .map(|_| h)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 32], SystemError> {
+        let mut digest = [0; 32];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut digest)
+            .map(|_| digest)
+    }
+}
+
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+impl Hasher for Sha256 {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+}
+
+pub fn sha256(data: &[u8]) -> Result<[u8; 32], SystemError> {
+    let mut h = Sha256::new()?;
+    h.update(data)?;
+    h.finalize()
+}
+
+pub struct Sha512 {
+    ctx: [u32; SHA512_CONTEXT_SIZE],
+}
+
+impl Sha512 {
+    pub fn new() -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; SHA512_CONTEXT_SIZE],
+        };
+        syscall::cx_sha512_init(h.ctx.as_mut_ptr() as *mut u8)
+            .map(|_| h)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 64], SystemError> {
+        let mut digest = [0; 64];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut digest)
+            .map(|_| digest)
+    }
+}
+
+impl Drop for Sha512 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+impl Hasher for Sha512 {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+}
+
+pub fn sha512(data: &[u8]) -> Result<[u8; 64], SystemError> {
+    let mut h = Sha512::new()?;
+    h.update(data)?;
+    h.finalize()
+}
+
+pub struct Ripemd160 {
+    ctx: [u32; RIPEMD160_CONTEXT_SIZE],
+}
+
+impl Ripemd160 {
+    pub fn new() -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; RIPEMD160_CONTEXT_SIZE],
+        };
+        syscall::cx_ripemd160_init(h.ctx.as_mut_ptr() as *mut u8)
+            .map(|_| h)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 20], SystemError> {
+        let mut digest = [0; 20];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut digest)
+            .map(|_| digest)
+    }
+}
+
+impl Drop for Ripemd160 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+impl Hasher for Ripemd160 {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+}
+
+pub fn ripemd160(data: &[u8]) -> Result<[u8; 20], SystemError> {
+    let mut h = Ripemd160::new()?;
+    h.update(data)?;
+    h.finalize()
+}
+
+/// Original Keccak (as used by Ethereum), not the finalized SHA-3
+pub struct Keccak256 {
+    ctx: [u64; KECCAK_CONTEXT_SIZE],
+}
+
+impl Keccak256 {
+    pub fn new() -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; KECCAK_CONTEXT_SIZE],
+        };
+        syscall::cx_keccak_init(h.ctx.as_mut_ptr() as *mut u8, 256)
+            .map(|_| h)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 32], SystemError> {
+        let mut digest = [0; 32];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut digest)
+            .map(|_| digest)
+    }
+}
+
+impl Drop for Keccak256 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+impl Hasher for Keccak256 {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+}
+
+pub fn keccak256(data: &[u8]) -> Result<[u8; 32], SystemError> {
+    let mut h = Keccak256::new()?;
+    h.update(data)?;
+    h.finalize()
+}
+
+pub const BLAKE2B_MAX_DIGEST_SIZE: usize = 64;
+
+pub struct Blake2b {
+    ctx: [u64; BLAKE2B_CONTEXT_SIZE],
+    digest_size: usize,
+}
+
+impl Blake2b {
+    /// Creates a hasher that produces `digest_size` byte digests
+    pub fn new(digest_size: usize) -> Result<Self, SystemError> {
+        if digest_size == 0 || digest_size > BLAKE2B_MAX_DIGEST_SIZE {
+            return Err(SystemError::InvalidParameter);
+        }
+
+        let mut h = Self{
+            ctx: [0; BLAKE2B_CONTEXT_SIZE],
+            digest_size,
+        };
+        syscall::cx_blake2b_init(h.ctx.as_mut_ptr() as *mut u8, 8 * digest_size as u32)
+            .map(|_| h)
+    }
+
+    pub fn digest_size(&self) -> usize {
+        self.digest_size
+    }
+
+    /// Writes the digest into the beginning of `out`, which has to be
+    /// at least `digest_size` bytes long
+    pub fn finalize(mut self, out: &mut [u8]) -> Result<(), SystemError> {
+        if out.len() < self.digest_size {
+            return Err(SystemError::Overflow);
+        }
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut out[0..self.digest_size])
+    }
+}
+
+impl Drop for Blake2b {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+impl Hasher for Blake2b {
+    fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr;
+    use core::mem;
+    use std::vec::Vec;
+    use simulator::Simulator;
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const TWO_BLOCKS: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+    #[test]
+    fn sha256_known_answers() {
+        let _sim = Simulator::new();
+        assert_eq!(sha256(b"").unwrap(), &hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]);
+        assert_eq!(sha256(b"abc").unwrap(), &hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")[..]);
+        assert_eq!(sha256(TWO_BLOCKS).unwrap(), &hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")[..]);
+    }
+
+    #[test]
+    fn sha256_in_parts() {
+        let _sim = Simulator::new();
+        let mut h = Sha256::new().unwrap();
+        for chunk in [b'a'; 1000].chunks(7) {
+            h.update(chunk).unwrap();
+        }
+        assert_eq!(h.finalize().unwrap(), &hex("41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3")[..]);
+    }
+
+    #[test]
+    fn sha512_known_answers() {
+        let _sim = Simulator::new();
+        assert_eq!(&sha512(b"abc").unwrap()[..], &hex(
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        )[..]);
+        let two_blocks = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+            hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+        assert_eq!(&sha512(two_blocks).unwrap()[..], &hex(
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018\
+             501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        )[..]);
+    }
+
+    #[test]
+    fn ripemd160_known_answers() {
+        let _sim = Simulator::new();
+        assert_eq!(ripemd160(b"").unwrap(), &hex("9c1185a5c5e9fc54612808977ee8f548b2258d31")[..]);
+        assert_eq!(ripemd160(b"abc").unwrap(), &hex("8eb208f7e05d987a9b044a8e98c6b087f15a0bfc")[..]);
+        assert_eq!(ripemd160(TWO_BLOCKS).unwrap(), &hex("12a053384a9c0c88e405a06c27dcf49ada62eb2b")[..]);
+    }
+
+    #[test]
+    fn keccak256_known_answers() {
+        let _sim = Simulator::new();
+        assert_eq!(keccak256(b"").unwrap(), &hex("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")[..]);
+        assert_eq!(keccak256(b"abc").unwrap(), &hex("4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45")[..]);
+    }
+
+    #[test]
+    fn blake2b_known_answers() {
+        let _sim = Simulator::new();
+        let mut h = Blake2b::new(64).unwrap();
+        h.update(b"abc").unwrap();
+        let mut digest = [0; 64];
+        h.finalize(&mut digest).unwrap();
+        assert_eq!(&digest[..], &hex(
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        )[..]);
+
+        // A message that exactly fills the block
+        let block: Vec<u8> = (0..128).collect();
+        let mut h = Blake2b::new(32).unwrap();
+        h.update(&block).unwrap();
+        let mut digest = [0; 32];
+        h.finalize(&mut digest).unwrap();
+        assert_eq!(digest, &hex("c3582f71ebb2be66fa5dd750f80baae97554f3b015663c8be377cfcb2488c1d1")[..]);
+
+        let mut h = Blake2b::new(32).unwrap();
+        for chunk in [b'a'; 1000].chunks(100) {
+            h.update(chunk).unwrap();
+        }
+        let mut digest = [0; 32];
+        h.finalize(&mut digest).unwrap();
+        assert_eq!(digest, &hex("e00b0ddbf1e2cdaf5c898e1a5e8826ea3a2c339bcf2a478da2e5fca9ff126672")[..]);
+    }
+
+    #[test]
+    fn blake2b_checks_digest_size() {
+        let _sim = Simulator::new();
+        assert!(Blake2b::new(0).is_err());
+        assert!(Blake2b::new(BLAKE2B_MAX_DIGEST_SIZE + 1).is_err());
+
+        let h = Blake2b::new(32).unwrap();
+        assert_eq!(h.digest_size(), 32);
+        assert!(h.finalize(&mut [0; 31]).is_err());
+    }
+
+    #[test]
+    fn contexts_are_zeroized_on_drop() {
+        let _sim = Simulator::new();
+        let mut h = Sha256::new().unwrap();
+        h.update(b"secret").unwrap();
+        assert!(h.ctx.iter().any(|&v| v != 0));
+
+        unsafe { ptr::drop_in_place(&mut h) };
+        assert!(h.ctx.iter().all(|&v| v == 0));
+        mem::forget(h);
+    }
+}
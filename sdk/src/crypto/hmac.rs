@@ -0,0 +1,125 @@
+use error::SystemError;
+use syscall;
+use super::{CX_LAST, zeroize};
+
+// Context sizes match the firmware structures (cx_hmac_sha256_t, etc.)
+pub(crate) const HMAC_SHA256_CONTEXT_SIZE: usize = 59;
+pub(crate) const HMAC_SHA512_CONTEXT_SIZE: usize = 115;
+
+fn update_context(ctx: *mut u8, data: &[u8]) -> Result<(), SystemError> {
+    syscall::cx_hmac(ctx, 0, data, &mut [])
+        .map(|_| ())
+}
+
+fn finalize_context(ctx: *mut u8, out: &mut [u8]) -> Result<(), SystemError> {
+    syscall::cx_hmac(ctx, CX_LAST, &[], out)
+        .map(|_| ())
+}
+
+pub struct HmacSha256 {
+    ctx: [u32; HMAC_SHA256_CONTEXT_SIZE],
+}
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; HMAC_SHA256_CONTEXT_SIZE],
+        };
+        syscall::cx_hmac_sha256_init(h.ctx.as_mut_ptr() as *mut u8, key)
+            .map(|_| h)
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 32], SystemError> {
+        let mut mac = [0; 32];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut mac)
+            .map(|_| mac)
+    }
+}
+
+impl Drop for HmacSha256 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<[u8; 32], SystemError> {
+    let mut h = HmacSha256::new(key)?;
+    h.update(data)?;
+    h.finalize()
+}
+
+pub struct HmacSha512 {
+    ctx: [u32; HMAC_SHA512_CONTEXT_SIZE],
+}
+
+impl HmacSha512 {
+    pub fn new(key: &[u8]) -> Result<Self, SystemError> {
+        let mut h = Self{
+            ctx: [0; HMAC_SHA512_CONTEXT_SIZE],
+        };
+        syscall::cx_hmac_sha512_init(h.ctx.as_mut_ptr() as *mut u8, key)
+            .map(|_| h)
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        update_context(self.ctx.as_mut_ptr() as *mut u8, data)
+    }
+
+    pub fn finalize(mut self) -> Result<[u8; 64], SystemError> {
+        let mut mac = [0; 64];
+        finalize_context(self.ctx.as_mut_ptr() as *mut u8, &mut mac)
+            .map(|_| mac)
+    }
+}
+
+impl Drop for HmacSha512 {
+    fn drop(&mut self) {
+        zeroize(&mut self.ctx, 0);
+    }
+}
+
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> Result<[u8; 64], SystemError> {
+    let mut h = HmacSha512::new(key)?;
+    h.update(data)?;
+    h.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use simulator::Simulator;
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Test cases 2 and 6 of RFC 4231
+    const DATA: &[u8] = b"what do ya want for nothing?";
+    const LONG_KEY_DATA: &[u8] = b"Test Using Larger Than Block-Size Key - Hash Key First";
+
+    #[test]
+    fn hmac_sha256_known_answers() {
+        let _sim = Simulator::new();
+        assert_eq!(hmac_sha256(b"Jefe", DATA).unwrap(), &hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")[..]);
+        assert_eq!(hmac_sha256(&[0xAA; 131], LONG_KEY_DATA).unwrap(), &hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")[..]);
+    }
+
+    #[test]
+    fn hmac_sha512_known_answers() {
+        let _sim = Simulator::new();
+        let mut h = HmacSha512::new(b"Jefe").unwrap();
+        h.update(&DATA[0..10]).unwrap();
+        h.update(&DATA[10..]).unwrap();
+        assert_eq!(&h.finalize().unwrap()[..], &hex(
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        )[..]);
+    }
+}
@@ -0,0 +1,24 @@
+use core::ptr;
+
+pub mod hash;
+pub mod hmac;
+pub mod ecc;
+pub mod bip32;
+
+// Flags shared by the firmware crypto functions
+const CX_LAST: u32 = 1 << 0;
+
+#[repr(u32)]
+#[derive(Clone, Copy)]
+enum HashId {
+    Sha256 = 3,
+    Sha512 = 5,
+}
+
+/// Overwrites the secret material in a way that the compiler
+/// won't optimize away
+fn zeroize<T: Copy>(buf: &mut [T], zero: T) {
+    for v in buf.iter_mut() {
+        unsafe { ptr::write_volatile(v, zero) };
+    }
+}
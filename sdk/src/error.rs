@@ -1,3 +1,5 @@
+use apdu::StatusWord;
+
 #[derive(Debug)]
 #[repr(u16)]
 pub enum SystemError {
@@ -63,4 +65,18 @@ impl SystemError {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+/// Status word for responding to a command that failed with the error.
+/// Only the errors that the host can act on have a specific status word,
+/// the rest are internal failures of the app and map to `Unknown`.
+impl Into<StatusWord> for SystemError {
+    fn into(self) -> StatusWord {
+        match self {
+            SystemError::Security => StatusWord::SecurityStatusNotSatisfied,
+            SystemError::InvalidParameter => StatusWord::IncorrectData,
+            SystemError::InvalidState => StatusWord::ConditionsNotSatisfied,
+            _ => StatusWord::Unknown,
+        }
+    }
+}
@@ -1,9 +1,15 @@
 #![no_std]
 #![feature(asm, link_llvm_intrinsics)]
 #![feature(panic_implementation)]
+#![feature(const_fn)]
+#![feature(linkage)]
+#![cfg_attr(feature = "panic-screen", feature(panic_info_message))]
 #![allow(dead_code)]
 
 extern crate byteorder;
+#[cfg(not(target_arch = "arm"))]
+#[macro_use]
+extern crate std;
 
 pub mod error;
 mod syscall;
@@ -13,3 +19,9 @@ pub mod seproxyhal;
 pub mod ui;
 pub mod pic;
 pub mod state;
+pub mod apdu;
+pub mod crypto;
+pub mod storage;
+pub mod manifest;
+#[cfg(not(target_arch = "arm"))]
+pub mod simulator;
@@ -0,0 +1,171 @@
+use crypto::ecc::Curve;
+use error::SystemError;
+use pic::Pic;
+use ui::CustomIcon;
+
+/// Application flags that apps can request in their manifest, the rest
+/// are managed by the OS
+pub const APP_FLAGS: u32 = 0x0FF0;
+
+/// Declares the metadata of the application as the `APP_MANIFEST` static.
+/// The manifest is placed in the `.bolos_manifest` section of the
+/// application and can also be read at runtime with `manifest::get`. Only
+/// one manifest can be declared per application.
+///
+/// The SDK refuses to derive keys on curves or under paths that aren't
+/// declared in the manifest. Empty `curves` or `paths` lists don't
+/// restrict key derivation, the same way the device doesn't restrict apps
+/// that are installed without curves or paths, so apps that use keys
+/// should declare both. Paths deeper than `bip32::MAX_DEPTH`, unknown
+/// curves and flags outside of `APP_FLAGS` fail to compile.
+///
+/// The manifest has to match the install parameters of the app, so it's
+/// best generated from `[package.metadata.bolos]` by the build script with
+/// `cargo_bolos::manifest::write_to_out_dir`, which also validates the
+/// name and the version.
+///
+/// ```ignore
+/// app_manifest!{
+///     name: "Demo",
+///     version: "1.0.0",
+///     flags: 0,
+///     icon: Some(icon::APP_ICON),
+///     curves: [Secp256k1],
+///     paths: [[44 | HARDENED, 0 | HARDENED]],
+/// }
+/// ```
+#[macro_export]
+macro_rules! app_manifest {
+    (
+        name: $name:expr,
+        version: $version:expr,
+        flags: $flags:expr,
+        icon: $icon:expr,
+        curves: [$($curve:ident),*],
+        paths: [$([$($index:expr),*]),*] $(,)*
+    ) => {
+        #[link_section=".bolos_manifest"]
+        #[export_name="_bolos_app_manifest"]
+        pub static APP_MANIFEST: $crate::manifest::Manifest = {
+            #[allow(unused_imports)]
+            use $crate::crypto::bip32::HARDENED;
+
+            // Fails to compile when flags that apps can't request are set
+            #[allow(dead_code)]
+            struct FlagsCheck([(); ($flags & !$crate::manifest::APP_FLAGS == 0) as usize - 1]);
+
+            $crate::manifest::Manifest::new(
+                $name,
+                $version,
+                $flags,
+                $icon,
+                &[$($crate::crypto::ecc::Curve::$curve),*],
+                &[$({
+                    // Fails to compile when the path is too deep
+                    #[allow(dead_code)]
+                    struct DepthCheck([(); $crate::crypto::bip32::MAX_DEPTH
+                        - (0 $(+ app_manifest!(@one $index))*)]);
+                    &[$($index),*]
+                }),*],
+            )
+        };
+    };
+    (@one $index:expr) => { 1 };
+}
+
+/// Application metadata declared with the `app_manifest!` macro
+pub struct Manifest {
+    name: &'static str,
+    version: &'static str,
+    flags: u32,
+    icon: Option<CustomIcon<'static>>,
+    curves: &'static [Curve],
+    paths: &'static [&'static [u32]],
+}
+
+impl Manifest {
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str,
+        version: &'static str,
+        flags: u32,
+        icon: Option<CustomIcon<'static>>,
+        curves: &'static [Curve],
+        paths: &'static [&'static [u32]],
+    ) -> Self {
+        Self{ name, version, flags, icon, curves, paths }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name.pic()
+    }
+
+    pub fn version(&self) -> &'static str {
+        self.version.pic()
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn icon(&self) -> Option<&CustomIcon<'static>> {
+        self.icon.as_ref()
+    }
+
+    /// Curves that the application is allowed to derive keys on
+    pub fn curves(&self) -> &'static [Curve] {
+        self.curves.pic()
+    }
+
+    /// Path prefixes that the application is allowed to derive keys
+    /// under, the slices have to be passed through `.pic()` before use
+    pub fn paths(&self) -> &'static [&'static [u32]] {
+        self.paths.pic()
+    }
+
+    pub fn allows_curve(&self, curve: Curve) -> bool {
+        let curves = self.curves();
+        curves.len() == 0 || curves.contains(&curve)
+    }
+
+    /// Whether the `path` starts with one of the declared prefixes
+    pub fn allows_path(&self, path: &[u32]) -> bool {
+        let paths = self.paths();
+        paths.len() == 0 || paths.iter().any(|prefix| path.starts_with(prefix.pic()))
+    }
+}
+
+extern {
+    // Exported by the app_manifest! macro, null when the application
+    // doesn't declare a manifest
+    #[linkage = "extern_weak"]
+    static _bolos_app_manifest: *const Manifest;
+}
+
+/// Manifest of the running application, if it declared one
+pub fn get() -> Option<&'static Manifest> {
+    let ptr = unsafe { _bolos_app_manifest };
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*ptr.pic() })
+    }
+}
+
+/// Refuses to use keys on curves that the manifest doesn't allow, the
+/// same way the device does with the install parameters of the app
+pub(crate) fn check_curve(curve: Curve) -> Result<(), SystemError> {
+    match get() {
+        Some(manifest) if !manifest.allows_curve(curve) => Err(SystemError::Security),
+        _ => Ok(()),
+    }
+}
+
+/// Refuses to derive keys that the manifest doesn't allow
+pub(crate) fn check_derivation(curve: Curve, path: &[u32]) -> Result<(), SystemError> {
+    check_curve(curve)?;
+    match get() {
+        Some(manifest) if !manifest.allows_path(path) => Err(SystemError::Security),
+        _ => Ok(()),
+    }
+}
@@ -1,14 +1,16 @@
 use core::slice;
 use core::str;
 
+#[cfg(target_arch = "arm")]
 extern {
     static _nvram: u32;
     static _envram: u32;
 }
 
+#[cfg(target_arch = "arm")]
 #[inline(never)]
-fn runtime_offset() -> u32 {
-    let offset: u32;
+fn runtime_offset() -> usize {
+    let offset: usize;
     unsafe {
         asm!("mov r1, pc
               ldr $0, =.
@@ -21,19 +23,26 @@ fn runtime_offset() -> u32 {
     offset
 }
 
-// TODO: Add platform feature gate such that actual pointer translation
-//       would only happen on Ledger device. On other targets it should
-//       be a noop operation.
+// Pointers into the application flash have to be translated on the
+// device, where the code doesn't run at the address it was linked at
+#[cfg(target_arch = "arm")]
 #[inline(always)]
-fn translate(mut addr: u32) -> u32 {
-    let nvram_start = unsafe { &_nvram as *const u32 as u32 };
-    let nvram_end = unsafe { &_envram as *const u32 as u32 };
+fn translate(mut addr: usize) -> usize {
+    let nvram_start = unsafe { &_nvram as *const u32 as usize };
+    let nvram_end = unsafe { &_envram as *const u32 as usize };
     if addr >= nvram_start && addr < nvram_end {
         addr -= runtime_offset();
     }
     return addr;
 }
 
+// Off the device the code runs where it was linked
+#[cfg(not(target_arch = "arm"))]
+#[inline(always)]
+fn translate(addr: usize) -> usize {
+    addr
+}
+
 pub trait Pic {
     fn pic(self) -> Self;
 }
@@ -41,14 +50,14 @@ pub trait Pic {
 impl<T> Pic for *const T {
     #[inline(always)]
     fn pic(self) -> Self {
-        translate(self as u32) as Self
+        translate(self as usize) as Self
     }
 }
 
 impl<T> Pic for *mut T {
     #[inline(always)]
     fn pic(self) -> Self {
-        translate(self as u32) as Self
+        translate(self as usize) as Self
     }
 }
 
@@ -1,6 +1,8 @@
+#[cfg(target_arch = "arm")]
 use core::panic::PanicInfo;
 use super::syscall;
 
+#[cfg(target_arch = "arm")]
 #[doc(hidden)]
 pub unsafe fn init() {
     // Enable interrupts
@@ -12,6 +14,11 @@ pub unsafe fn init() {
         : "volatile");
 }
 
+#[cfg(not(target_arch = "arm"))]
+#[doc(hidden)]
+pub unsafe fn init() {
+}
+
 #[macro_export]
 macro_rules! entry {
     ($main:path) => {
@@ -36,9 +43,131 @@ pub fn exit(exit_code: u32) -> ! {
     };
 }
 
+// Set while the panic screen is shown, a panic from within it would
+// otherwise try to show the screen again and overflow the stack
+#[cfg(all(target_arch = "arm", feature = "panic-screen"))]
+static mut PANICKING: bool = false;
+
+// Off the device panics are handled by the standard library
+#[cfg(target_arch = "arm")]
 #[panic_implementation]
 fn panic(_info: &PanicInfo) -> ! {
-    // TODO: Implement a sub-eventloop that displays the panic message to the
-    //       developer (only in debug mode)
+    #[cfg(feature = "panic-screen")]
+    unsafe {
+        // The app is single threaded, nothing else can access the flag
+        if PANICKING {
+            exit(1)
+        }
+        PANICKING = true;
+        show_panic_screen(_info);
+    }
     exit(1)
 }
+
+/// Fixed size buffer for formatting text without an allocator, text that
+/// doesn't fit is cut off
+#[cfg(all(target_arch = "arm", feature = "panic-screen"))]
+struct TextBuffer {
+    buf: [u8; 128],
+    len: usize,
+}
+
+#[cfg(all(target_arch = "arm", feature = "panic-screen"))]
+impl TextBuffer {
+    fn new() -> Self {
+        Self{
+            buf: [0; 128],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        unsafe { ::core::str::from_utf8_unchecked(&self.buf[0..self.len]) }
+    }
+}
+
+#[cfg(all(target_arch = "arm", feature = "panic-screen"))]
+impl ::core::fmt::Write for TextBuffer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        for c in s.chars() {
+            let mut tmp = [0; 4];
+            let bytes = c.encode_utf8(&mut tmp).as_bytes();
+            let end = self.len + bytes.len();
+            if end > self.buf.len() {
+                break;
+            }
+            self.buf[self.len..end].copy_from_slice(bytes);
+            self.len = end;
+        }
+        Ok(())
+    }
+}
+
+/// Displays the panic location and message until a button is pressed
+#[cfg(all(target_arch = "arm", feature = "panic-screen"))]
+fn show_panic_screen(info: &PanicInfo) {
+    use core::fmt::Write;
+    use seproxyhal::MessageLoop;
+    use seproxyhal::event::{Event, ButtonPushEvent};
+    use time::Duration;
+    use ui;
+
+    let mut location = TextBuffer::new();
+    if let Some(loc) = info.location() {
+        write!(location, "{}:{}", loc.file(), loc.line()).is_ok();
+    }
+    let mut message = TextBuffer::new();
+    let result = match info.message() {
+        Some(msg) => message.write_fmt(*msg),
+        None => message.write_str("panic"),
+    };
+    result.is_ok();
+
+    let views: [ui::View; 3] = [
+        ui::RectangleView{
+            frame: ui::Frame{ x: 0, y: 0, width: 128, height: 32 },
+            fill: ui::FillMode::Fill,
+            ..Default::default()
+        }.into(),
+        ui::LabelLineView{
+            frame: ui::Frame{ x: 0, y: 12, width: 128, height: 12 },
+            font: ui::TextFont::OpenSansExtraBold11px,
+            horizontal_alignment: ui::TextHorizontalAlignment::Center,
+            scroll: ui::ScrollMode::Infinite{ delay: Duration::from_secs(1), speed: 26 },
+            text: location.as_str(),
+            ..Default::default()
+        }.into(),
+        ui::LabelLineView{
+            frame: ui::Frame{ x: 0, y: 26, width: 128, height: 12 },
+            font: ui::TextFont::OpenSansRegular11px,
+            horizontal_alignment: ui::TextHorizontalAlignment::Center,
+            scroll: ui::ScrollMode::Infinite{ delay: Duration::from_secs(1), speed: 26 },
+            text: message.as_str(),
+            ..Default::default()
+        }.into(),
+    ];
+
+    let mut next_view = 0;
+    let mut button_pressed = false;
+
+    for ch in MessageLoop::new() {
+        let show_next_view = match ch.event {
+            Event::StartLoop |
+            Event::DisplayProcessed(_) => true,
+            Event::ButtonPush(ButtonPushEvent{ flags }) => {
+                // Dismiss the screen once the button is released
+                if flags == 0 && button_pressed {
+                    return;
+                }
+                button_pressed = flags != 0;
+                false
+            },
+            _ => false,
+        };
+
+        if show_next_view && next_view < views.len() {
+            ch.send_status(views[next_view].to_display_status(0).into());
+            next_view += 1;
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use core::cmp::{min, max};
+use byteorder::{ByteOrder, BigEndian};
+use super::Channel;
+use super::io::{Io, IoError, APDU_BUFFER_SIZE};
+use super::event::{
+    Event, BleConnectionEvent, BleWriteRequestEvent, BleNotifyIndicateEvent,
+    BLE_WRITE_REQUEST_MAX_SIZE,
+};
+use super::command::{BleRadioPowerCommand, BleSendCommand};
+
+const MAX_PACKET_SIZE: usize = BLE_WRITE_REQUEST_MAX_SIZE;
+// Notifications carry up to MTU - 3 bytes of the characteristic value
+const ATT_HEADER_SIZE: u16 = 3;
+const DEFAULT_MTU: u16 = 23;
+
+const HEADER_SIZE: usize = 3;
+const FIRST_HEADER_SIZE: usize = HEADER_SIZE + 2;
+const MTU_REPLY_SIZE: usize = 6;
+
+#[repr(u8)]
+enum FrameTag {
+    Apdu = 0x05,
+    Mtu = 0x08,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Received {
+    /// More packets are needed to complete the APDU
+    Partial,
+    /// Host asked for the largest packet size the device can send
+    MtuRequest,
+    /// A complete APDU of the given length is in the buffer
+    Apdu(usize),
+    /// Packet didn't follow the framing rules and was discarded
+    Invalid,
+}
+
+/// Ledger's BLE transport framing, which is the same as the HID framing
+/// without the channel id. The packet size depends on the MTU of the
+/// connection:
+///
+/// ```text
+/// | tag (1) | seq (2) | [length (2), first packet only] | data |
+/// ```
+struct Framing {
+    packet_size: usize,
+    rx_sequence: u16,
+    rx_expected: usize,
+    rx_received: usize,
+    tx_sequence: u16,
+    tx_total: usize,
+    tx_sent: usize,
+}
+
+impl Framing {
+    fn new() -> Self {
+        Self{
+            packet_size: (DEFAULT_MTU - ATT_HEADER_SIZE) as usize,
+            rx_sequence: 0,
+            rx_expected: 0,
+            rx_received: 0,
+            tx_sequence: 0,
+            tx_total: 0,
+            tx_sent: 0,
+        }
+    }
+
+    fn reset(&mut self, mtu: u16) {
+        *self = Framing::new();
+        let mtu = max(mtu, DEFAULT_MTU);
+        self.packet_size = min((mtu - ATT_HEADER_SIZE) as usize, MAX_PACKET_SIZE);
+    }
+
+    /// Decodes a single characteristic write, appending any APDU data to `buf`
+    fn receive(&mut self, packet: &[u8], buf: &mut [u8]) -> Received {
+        if packet.len() < HEADER_SIZE {
+            return Received::Invalid;
+        }
+
+        let tag = packet[0];
+        let sequence = BigEndian::read_u16(&packet[1..3]);
+
+        if tag == FrameTag::Mtu as u8 {
+            return Received::MtuRequest;
+        } else if tag != FrameTag::Apdu as u8 {
+            return Received::Invalid;
+        }
+
+        let data = if sequence == 0 {
+            if packet.len() < FIRST_HEADER_SIZE {
+                return Received::Invalid;
+            }
+            let expected = BigEndian::read_u16(&packet[3..5]) as usize;
+            if expected > buf.len() {
+                self.rx_expected = 0;
+                return Received::Invalid;
+            }
+
+            self.rx_sequence = 0;
+            self.rx_expected = expected;
+            self.rx_received = 0;
+            &packet[FIRST_HEADER_SIZE..]
+        } else {
+            if self.rx_expected == 0 || sequence != self.rx_sequence.wrapping_add(1) {
+                self.rx_expected = 0;
+                return Received::Invalid;
+            }
+
+            self.rx_sequence = sequence;
+            &packet[HEADER_SIZE..]
+        };
+
+        let cnt = min(data.len(), self.rx_expected - self.rx_received);
+        buf[self.rx_received..self.rx_received+cnt].copy_from_slice(&data[0..cnt]);
+        self.rx_received += cnt;
+
+        if self.rx_received == self.rx_expected {
+            let len = self.rx_expected;
+            self.rx_expected = 0;
+            self.rx_received = 0;
+            Received::Apdu(len)
+        } else {
+            Received::Partial
+        }
+    }
+
+    fn make_mtu_reply(&self, packet: &mut [u8; MAX_PACKET_SIZE]) -> usize {
+        for b in packet[0..MTU_REPLY_SIZE].iter_mut() {
+            *b = 0;
+        }
+        packet[0] = FrameTag::Mtu as u8;
+        BigEndian::write_u16(&mut packet[3..5], 1);
+        packet[5] = self.packet_size as u8;
+        MTU_REPLY_SIZE
+    }
+
+    /// Prepares the framing for sending a response of `len` bytes
+    fn start_sending(&mut self, len: usize) {
+        self.tx_sequence = 0;
+        self.tx_total = len;
+        self.tx_sent = 0;
+    }
+
+    fn is_sending(&self) -> bool {
+        self.tx_sent < self.tx_total
+    }
+
+    /// Fills the `packet` with the next chunk of the response in `buf`,
+    /// returns the size of the packet or 0 when there's nothing to send
+    fn next_packet(&mut self, buf: &[u8], packet: &mut [u8; MAX_PACKET_SIZE]) -> usize {
+        if !self.is_sending() {
+            return 0;
+        }
+
+        packet[0] = FrameTag::Apdu as u8;
+        BigEndian::write_u16(&mut packet[1..3], self.tx_sequence);
+
+        let data_offset = if self.tx_sequence == 0 {
+            BigEndian::write_u16(&mut packet[3..5], self.tx_total as u16);
+            FIRST_HEADER_SIZE
+        } else {
+            HEADER_SIZE
+        };
+
+        let cnt = min(self.packet_size - data_offset, self.tx_total - self.tx_sent);
+        packet[data_offset..data_offset+cnt]
+            .copy_from_slice(&buf[self.tx_sent..self.tx_sent+cnt]);
+
+        self.tx_sent += cnt;
+        self.tx_sequence = self.tx_sequence.wrapping_add(1);
+        data_offset + cnt
+    }
+}
+
+/// Attribute handles of the characteristics that the host writes the
+/// APDUs to and receives the responses from
+#[derive(Clone, Copy)]
+pub struct Characteristics {
+    pub write: u16,
+    pub notify: u16,
+}
+
+/// BLE transport, which exchanges APDUs by the host writing to one
+/// characteristic and the app notifying the host of another
+pub struct Transport {
+    started: bool,
+    characteristics: Characteristics,
+    connection_handle: Option<u16>,
+    // Only one notification can be in flight at a time
+    notification_pending: bool,
+    framing: Framing,
+    rx_buffer: [u8; APDU_BUFFER_SIZE],
+    tx_buffer: [u8; APDU_BUFFER_SIZE],
+    // Length of the command APDU that is waiting for a response
+    pending: Option<usize>,
+}
+
+impl Transport {
+    pub fn new(characteristics: Characteristics) -> Self {
+        Self{
+            started: false,
+            characteristics,
+            connection_handle: None,
+            notification_pending: false,
+            framing: Framing::new(),
+            rx_buffer: [0; APDU_BUFFER_SIZE],
+            tx_buffer: [0; APDU_BUFFER_SIZE],
+            pending: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection_handle.is_some()
+    }
+
+    fn send_notification(&mut self, ch: &mut Channel, data: &[u8]) {
+        self.notification_pending = true;
+        ch.send_command(BleSendCommand{
+            handle: self.characteristics.notify,
+            data,
+        }.into());
+    }
+
+    fn send_next_packet(&mut self, ch: &mut Channel) {
+        let mut packet = [0; MAX_PACKET_SIZE];
+        let len = self.framing.next_packet(&self.tx_buffer, &mut packet);
+        if len > 0 {
+            self.send_notification(ch, &packet[0..len]);
+        }
+    }
+
+    fn process_connection(&mut self, ev: BleConnectionEvent) {
+        self.notification_pending = false;
+        self.pending = None;
+        self.framing.reset(ev.mtu);
+        self.connection_handle = if ev.connected {
+            Some(ev.connection_handle)
+        } else {
+            None
+        };
+    }
+
+    fn process_write(&mut self, ch: &mut Channel, ev: BleWriteRequestEvent) {
+        if ev.handle != self.characteristics.write || !self.is_connected() {
+            return;
+        }
+        // Ignore new requests until the previous one has been responded to
+        if self.pending.is_some() || self.framing.is_sending() || self.notification_pending {
+            return;
+        }
+
+        match self.framing.receive(ev.data(), &mut self.rx_buffer) {
+            Received::Apdu(len) => {
+                self.pending = Some(len);
+            },
+            Received::MtuRequest => {
+                let mut packet = [0; MAX_PACKET_SIZE];
+                let len = self.framing.make_mtu_reply(&mut packet);
+                self.send_notification(ch, &packet[0..len]);
+            },
+            Received::Partial |
+            Received::Invalid => {},
+        }
+    }
+
+    fn process_notify_indicate(&mut self, ch: &mut Channel, ev: BleNotifyIndicateEvent) {
+        if ev.handle != self.characteristics.notify {
+            return;
+        }
+        self.notification_pending = false;
+        self.send_next_packet(ch);
+    }
+}
+
+impl Io for Transport {
+    fn process_event(&mut self, ch: &mut Channel) -> bool {
+        if !self.started {
+            self.started = true;
+            ch.send_command(BleRadioPowerCommand{ on: true }.into());
+        }
+
+        match ch.event {
+            Event::BleConnection(ev) => {
+                self.process_connection(ev);
+                true
+            },
+            Event::BleWriteRequest(ev) => {
+                self.process_write(ch, ev);
+                true
+            },
+            Event::BleNotifyIndicate(ev) => {
+                self.process_notify_indicate(ch, ev);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn recv_apdu(&self) -> Option<&[u8]> {
+        match self.pending {
+            Some(len) => Some(&self.rx_buffer[0..len]),
+            None => None,
+        }
+    }
+
+    fn send_rapdu(&mut self, ch: &mut Channel, rapdu: &[u8]) -> Result<(), IoError> {
+        if ch.is_status_sent() {
+            return Err(IoError::StatusSent);
+        }
+        if !self.is_connected() {
+            return Err(IoError::Disconnected);
+        }
+        if self.pending.is_none() {
+            return Err(IoError::NoCommand);
+        }
+        if rapdu.len() > self.tx_buffer.len() {
+            return Err(IoError::Overflow);
+        }
+
+        self.tx_buffer[0..rapdu.len()].copy_from_slice(rapdu);
+        self.pending = None;
+        self.framing.start_sending(rapdu.len());
+        self.send_next_packet(ch);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    fn packet(sequence: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![FrameTag::Apdu as u8, (sequence >> 8) as u8, sequence as u8];
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    // First packet of an APDU, with the total length in front of the data
+    fn first_packet(len: usize, data: &[u8]) -> Vec<u8> {
+        let mut with_len = vec![(len >> 8) as u8, len as u8];
+        with_len.extend_from_slice(data);
+        packet(0, &with_len)
+    }
+
+    fn apdu(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn send(framing: &mut Framing, response: &[u8]) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        let mut packet = [0; MAX_PACKET_SIZE];
+        framing.start_sending(response.len());
+        loop {
+            let len = framing.next_packet(response, &mut packet);
+            if len == 0 {
+                break;
+            }
+            packets.push(packet[0..len].to_vec());
+        }
+        packets
+    }
+
+    #[test]
+    fn packet_size_follows_mtu() {
+        let mut framing = Framing::new();
+        assert_eq!(framing.packet_size, 20);
+
+        framing.reset(100);
+        assert_eq!(framing.packet_size, 97);
+
+        // MTU can't be smaller than the default one or the packets
+        // larger than what fits in an event
+        framing.reset(10);
+        assert_eq!(framing.packet_size, 20);
+        framing.reset(512);
+        assert_eq!(framing.packet_size, MAX_PACKET_SIZE);
+    }
+
+    #[test]
+    fn replies_with_packet_size_to_mtu_request() {
+        let mut framing = Framing::new();
+        framing.reset(100);
+        let mut buf = [0; APDU_BUFFER_SIZE];
+        assert_eq!(framing.receive(&[FrameTag::Mtu as u8, 0, 0], &mut buf), Received::MtuRequest);
+
+        let mut packet = [0; MAX_PACKET_SIZE];
+        let len = framing.make_mtu_reply(&mut packet);
+        assert_eq!(&packet[0..len], &[FrameTag::Mtu as u8, 0, 0, 0, 1, 97]);
+    }
+
+    #[test]
+    fn splits_response_by_default_mtu() {
+        let mut framing = Framing::new();
+        let response = apdu(40);
+        let packets = send(&mut framing, &response);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0], first_packet(40, &response[0..15]));
+        assert_eq!(packets[1], packet(1, &response[15..32]));
+        assert_eq!(packets[2], packet(2, &response[32..40]));
+        assert!(!framing.is_sending());
+    }
+
+    #[test]
+    fn splits_response_by_negotiated_mtu() {
+        let mut framing = Framing::new();
+        framing.reset(50);
+        let response = apdu(100);
+        let packets = send(&mut framing, &response);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0], first_packet(100, &response[0..42]));
+        assert_eq!(packets[1], packet(1, &response[42..86]));
+        assert_eq!(packets[2], packet(2, &response[86..100]));
+
+        framing.reset(512);
+        let response = apdu(APDU_BUFFER_SIZE);
+        let packets = send(&mut framing, &response);
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|p| p.len() <= MAX_PACKET_SIZE));
+        assert_eq!(packets[0].len(), MAX_PACKET_SIZE);
+    }
+
+    #[test]
+    fn reassembles_multi_packet_apdu() {
+        let mut framing = Framing::new();
+        let mut buf = [0; APDU_BUFFER_SIZE];
+        let apdu = apdu(40);
+        assert_eq!(framing.receive(&first_packet(40, &apdu[0..15]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(1, &apdu[15..32]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(2, &apdu[32..40]), &mut buf), Received::Apdu(40));
+        assert_eq!(&buf[0..40], &apdu[..]);
+    }
+
+    #[test]
+    fn rejects_broken_framing() {
+        let mut framing = Framing::new();
+        let mut buf = [0; APDU_BUFFER_SIZE];
+        let apdu = apdu(40);
+
+        framing.receive(&first_packet(40, &apdu[0..15]), &mut buf);
+        assert_eq!(framing.receive(&packet(2, &apdu[15..32]), &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&packet(1, &apdu[15..32]), &mut buf), Received::Invalid);
+
+        assert_eq!(framing.receive(&first_packet(APDU_BUFFER_SIZE + 1, &[]), &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&[0x01, 0, 0, 0, 1, 0], &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&[FrameTag::Apdu as u8, 0], &mut buf), Received::Invalid);
+    }
+}
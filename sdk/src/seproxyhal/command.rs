@@ -1,17 +1,285 @@
+use byteorder::{ByteOrder, BigEndian};
+use pic::Pic;
+use time::Duration;
 use super::packet::Packet;
 
+#[repr(u8)]
 enum CommandTag {
+    BleSend = 0x38,
+    BleRadioPower = 0x44,
+    SePowerOff = 0x46,
+    ScreenPower = 0x47,
+    MoreTime = 0x4B,
+    SetTickerInterval = 0x4E,
+    UsbConfig = 0x4F,
+    UsbEndpointPrepare = 0x50,
 }
 
-pub enum Command {
+pub struct SetTickerIntervalCommand {
+    pub interval: Duration,
 }
 
-impl Packet for Command {
+impl Packet for SetTickerIntervalCommand {
+    impl_packet!(self, CommandTag::SetTickerInterval, {
+        [S] 2 => {
+            let mut tmp = [0; 2];
+            let millis = ::core::cmp::min(self.interval.as_millis(), 0xFFFF);
+            BigEndian::write_u16(&mut tmp, millis as u16);
+            tmp
+        },
+    });
+}
+
+impl<'a> Into<Command<'a>> for SetTickerIntervalCommand {
+    fn into(self) -> Command<'a> {
+        Command::SetTickerInterval(self)
+    }
+}
+
+pub struct ScreenPowerCommand {
+    pub on: bool,
+}
+
+impl Packet for ScreenPowerCommand {
+    impl_packet!(self, CommandTag::ScreenPower, {
+        [S] 1 => [self.on as u8],
+    });
+}
+
+impl<'a> Into<Command<'a>> for ScreenPowerCommand {
+    fn into(self) -> Command<'a> {
+        Command::ScreenPower(self)
+    }
+}
+
+pub struct SePowerOffCommand {
+}
+
+impl Packet for SePowerOffCommand {
+    impl_packet!(self, CommandTag::SePowerOff, {});
+}
+
+impl<'a> Into<Command<'a>> for SePowerOffCommand {
+    fn into(self) -> Command<'a> {
+        Command::SePowerOff(self)
+    }
+}
+
+pub struct MoreTimeCommand {
+}
+
+impl Packet for MoreTimeCommand {
+    impl_packet!(self, CommandTag::MoreTime, {});
+}
+
+impl<'a> Into<Command<'a>> for MoreTimeCommand {
+    fn into(self) -> Command<'a> {
+        Command::MoreTime(self)
+    }
+}
+
+#[repr(u8)]
+enum UsbConfigType {
+    Connect = 0x01,
+    Disconnect = 0x02,
+    Address = 0x03,
+    Endpoints = 0x04,
+}
+
+pub struct UsbConnectCommand {
+}
+
+impl Packet for UsbConnectCommand {
+    impl_packet!(self, CommandTag::UsbConfig, {
+        [S] 1 => [UsbConfigType::Connect as u8],
+    });
+}
+
+impl<'a> Into<Command<'a>> for UsbConnectCommand {
+    fn into(self) -> Command<'a> {
+        Command::UsbConnect(self)
+    }
+}
+
+pub struct UsbDisconnectCommand {
+}
+
+impl Packet for UsbDisconnectCommand {
+    impl_packet!(self, CommandTag::UsbConfig, {
+        [S] 1 => [UsbConfigType::Disconnect as u8],
+    });
+}
+
+impl<'a> Into<Command<'a>> for UsbDisconnectCommand {
+    fn into(self) -> Command<'a> {
+        Command::UsbDisconnect(self)
+    }
+}
+
+pub struct UsbAddressCommand {
+    pub address: u8,
+}
+
+impl Packet for UsbAddressCommand {
+    impl_packet!(self, CommandTag::UsbConfig, {
+        [S] 2 => [UsbConfigType::Address as u8, self.address],
+    });
+}
+
+impl<'a> Into<Command<'a>> for UsbAddressCommand {
+    fn into(self) -> Command<'a> {
+        Command::UsbAddress(self)
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum UsbEndpointType {
+    Control = 0x00,
+    Isochronous = 0x01,
+    Bulk = 0x02,
+    Interrupt = 0x03,
+    Disabled = 0xFF,
+}
+
+pub struct UsbEndpointCommand {
+    pub endpoint: u8,
+    pub endpoint_type: UsbEndpointType,
+    pub max_packet_size: u8,
+}
+
+impl Packet for UsbEndpointCommand {
+    impl_packet!(self, CommandTag::UsbConfig, {
+        [S] 5 => [
+            UsbConfigType::Endpoints as u8,
+            1, // endpoint count
+            self.endpoint,
+            self.endpoint_type as u8,
+            self.max_packet_size,
+        ],
+    });
+}
+
+impl<'a> Into<Command<'a>> for UsbEndpointCommand {
+    fn into(self) -> Command<'a> {
+        Command::UsbEndpoint(self)
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum UsbEndpointPrepareDirection {
+    Setup = 0x10,
+    In = 0x20,
+    Out = 0x30,
+    Stall = 0x40,
+    Unstall = 0x80,
+}
+
+pub struct UsbEndpointPrepareCommand<'a> {
+    pub endpoint: u8,
+    pub direction: UsbEndpointPrepareDirection,
+    pub length: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a> Packet for UsbEndpointPrepareCommand<'a> {
+    impl_packet!(self, CommandTag::UsbEndpointPrepare, {
+        [S] 3 => [self.endpoint, self.direction as u8, self.length],
+        [S] self.data.len() => self.data.pic(),
+    });
+}
+
+impl<'a> Into<Command<'a>> for UsbEndpointPrepareCommand<'a> {
+    fn into(self) -> Command<'a> {
+        Command::UsbEndpointPrepare(self)
+    }
+}
+
+pub struct BleRadioPowerCommand {
+    pub on: bool,
+}
+
+impl Packet for BleRadioPowerCommand {
+    impl_packet!(self, CommandTag::BleRadioPower, {
+        [S] 1 => [self.on as u8],
+    });
+}
+
+impl<'a> Into<Command<'a>> for BleRadioPowerCommand {
+    fn into(self) -> Command<'a> {
+        Command::BleRadioPower(self)
+    }
+}
+
+/// Notifies the host of a new characteristic value
+pub struct BleSendCommand<'a> {
+    pub handle: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> Packet for BleSendCommand<'a> {
+    impl_packet!(self, CommandTag::BleSend, {
+        [S] 3 => {
+            let mut tmp = [0; 3];
+            BigEndian::write_u16(&mut tmp[0..2], self.handle);
+            tmp[2] = self.data.len() as u8;
+            tmp
+        },
+        [S] self.data.len() => self.data.pic(),
+    });
+}
+
+impl<'a> Into<Command<'a>> for BleSendCommand<'a> {
+    fn into(self) -> Command<'a> {
+        Command::BleSend(self)
+    }
+}
+
+pub enum Command<'a> {
+    SetTickerInterval(SetTickerIntervalCommand),
+    ScreenPower(ScreenPowerCommand),
+    SePowerOff(SePowerOffCommand),
+    MoreTime(MoreTimeCommand),
+    UsbConnect(UsbConnectCommand),
+    UsbDisconnect(UsbDisconnectCommand),
+    UsbAddress(UsbAddressCommand),
+    UsbEndpoint(UsbEndpointCommand),
+    UsbEndpointPrepare(UsbEndpointPrepareCommand<'a>),
+    BleRadioPower(BleRadioPowerCommand),
+    BleSend(BleSendCommand<'a>),
+}
+
+impl<'a> Packet for Command<'a> {
     fn bytes_size(&self) -> u16 {
-        0
+        match self {
+            &Command::SetTickerInterval(ref c) => c.bytes_size(),
+            &Command::ScreenPower(ref c) => c.bytes_size(),
+            &Command::SePowerOff(ref c) => c.bytes_size(),
+            &Command::MoreTime(ref c) => c.bytes_size(),
+            &Command::UsbConnect(ref c) => c.bytes_size(),
+            &Command::UsbDisconnect(ref c) => c.bytes_size(),
+            &Command::UsbAddress(ref c) => c.bytes_size(),
+            &Command::UsbEndpoint(ref c) => c.bytes_size(),
+            &Command::UsbEndpointPrepare(ref c) => c.bytes_size(),
+            &Command::BleRadioPower(ref c) => c.bytes_size(),
+            &Command::BleSend(ref c) => c.bytes_size(),
+        }
     }
 
-    fn to_bytes(&self, _buf: &mut [u8], _offset: usize) -> usize {
-        0
+    fn to_bytes(&self, buf: &mut [u8], offset: usize) -> usize {
+        match self {
+            &Command::SetTickerInterval(ref c) => c.to_bytes(buf, offset),
+            &Command::ScreenPower(ref c) => c.to_bytes(buf, offset),
+            &Command::SePowerOff(ref c) => c.to_bytes(buf, offset),
+            &Command::MoreTime(ref c) => c.to_bytes(buf, offset),
+            &Command::UsbConnect(ref c) => c.to_bytes(buf, offset),
+            &Command::UsbDisconnect(ref c) => c.to_bytes(buf, offset),
+            &Command::UsbAddress(ref c) => c.to_bytes(buf, offset),
+            &Command::UsbEndpoint(ref c) => c.to_bytes(buf, offset),
+            &Command::UsbEndpointPrepare(ref c) => c.to_bytes(buf, offset),
+            &Command::BleRadioPower(ref c) => c.to_bytes(buf, offset),
+            &Command::BleSend(ref c) => c.to_bytes(buf, offset),
+        }
     }
 }
@@ -1,20 +1,39 @@
+use core::cmp::min;
 use byteorder::{ByteOrder, BigEndian};
 
+pub const MAX_EVENT_SIZE: usize = 128;
+const HEADER_SIZE: usize = 3;
+
 #[repr(u8)]
 enum EventTag {
+    BleWriteRequest = 0x03,
     ButtonPush = 0x05,
+    BleConnection = 0x0A,
     DisplayProcessed = 0x0D,
     Ticker = 0x0E,
+    Usb = 0x0F,
+    UsbEndpointTransfer = 0x10,
+    BleNotifyIndicate = 0x1B,
 }
 
 impl EventTag {
     fn from_u8(value: u8) -> Option<Self> {
-        if value == EventTag::ButtonPush as u8 {
+        if value == EventTag::BleWriteRequest as u8 {
+            Some(EventTag::BleWriteRequest)
+        } else if value == EventTag::ButtonPush as u8 {
             Some(EventTag::ButtonPush)
+        } else if value == EventTag::BleConnection as u8 {
+            Some(EventTag::BleConnection)
         } else if value == EventTag::DisplayProcessed as u8 {
             Some(EventTag::DisplayProcessed)
         } else if value == EventTag::Ticker as u8 {
             Some(EventTag::Ticker)
+        } else if value == EventTag::Usb as u8 {
+            Some(EventTag::Usb)
+        } else if value == EventTag::UsbEndpointTransfer as u8 {
+            Some(EventTag::UsbEndpointTransfer)
+        } else if value == EventTag::BleNotifyIndicate as u8 {
+            Some(EventTag::BleNotifyIndicate)
         } else {
             None
         }
@@ -63,24 +82,242 @@ impl TickerEvent {
     }
 }
 
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UsbEventType {
+    Reset = 0x01,
+    StartOfFrame = 0x02,
+    Suspended = 0x04,
+    Resumed = 0x08,
+}
+
+impl UsbEventType {
+    fn from_u8(value: u8) -> Option<Self> {
+        if value == UsbEventType::Reset as u8 {
+            Some(UsbEventType::Reset)
+        } else if value == UsbEventType::StartOfFrame as u8 {
+            Some(UsbEventType::StartOfFrame)
+        } else if value == UsbEventType::Suspended as u8 {
+            Some(UsbEventType::Suspended)
+        } else if value == UsbEventType::Resumed as u8 {
+            Some(UsbEventType::Resumed)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct UsbEvent {
+    pub event_type: UsbEventType,
+}
+
+impl UsbEvent {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() != 1 {
+            None
+        } else {
+            UsbEventType::from_u8(raw[0]).map(|event_type| Self{
+                event_type,
+            })
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UsbTransferType {
+    Setup = 0x01,
+    In = 0x02,
+    Out = 0x04,
+}
+
+impl UsbTransferType {
+    fn from_u8(value: u8) -> Option<Self> {
+        if value == UsbTransferType::Setup as u8 {
+            Some(UsbTransferType::Setup)
+        } else if value == UsbTransferType::In as u8 {
+            Some(UsbTransferType::In)
+        } else if value == UsbTransferType::Out as u8 {
+            Some(UsbTransferType::Out)
+        } else {
+            None
+        }
+    }
+}
+
+pub const USB_ENDPOINT_TRANSFER_MAX_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct UsbEndpointTransferEvent {
+    pub endpoint: u8,
+    pub transfer_type: UsbTransferType,
+    length: u8,
+    buffer: [u8; USB_ENDPOINT_TRANSFER_MAX_SIZE],
+}
+
+impl UsbEndpointTransferEvent {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() < 3 {
+            return None;
+        }
+
+        let length = raw[2] as usize;
+        if length > USB_ENDPOINT_TRANSFER_MAX_SIZE || raw.len() < 3 + length {
+            return None;
+        }
+
+        let mut buffer = [0; USB_ENDPOINT_TRANSFER_MAX_SIZE];
+        buffer[0..length].copy_from_slice(&raw[3..3+length]);
+
+        UsbTransferType::from_u8(raw[1]).map(|transfer_type| Self{
+            endpoint: raw[0],
+            transfer_type,
+            length: length as u8,
+            buffer,
+        })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[0..self.length as usize]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BleConnectionEvent {
+    pub connected: bool,
+    pub connection_handle: u16,
+    /// Negotiated ATT MTU of the connection
+    pub mtu: u16,
+}
+
+impl BleConnectionEvent {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() != 5 {
+            None
+        } else {
+            Some(Self{
+                connected: raw[0] != 0,
+                connection_handle: BigEndian::read_u16(&raw[1..3]),
+                mtu: BigEndian::read_u16(&raw[3..5]),
+            })
+        }
+    }
+}
+
+pub const BLE_WRITE_REQUEST_MAX_SIZE: usize = MAX_EVENT_SIZE - HEADER_SIZE - 3;
+
+/// Host wrote to a characteristic
+#[derive(Clone, Copy)]
+pub struct BleWriteRequestEvent {
+    pub handle: u16,
+    length: u8,
+    buffer: [u8; BLE_WRITE_REQUEST_MAX_SIZE],
+}
+
+impl BleWriteRequestEvent {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() < 3 {
+            return None;
+        }
+
+        let length = raw[2] as usize;
+        if length > BLE_WRITE_REQUEST_MAX_SIZE || raw.len() < 3 + length {
+            return None;
+        }
+
+        let mut buffer = [0; BLE_WRITE_REQUEST_MAX_SIZE];
+        buffer[0..length].copy_from_slice(&raw[3..3+length]);
+
+        Some(Self{
+            handle: BigEndian::read_u16(&raw[0..2]),
+            length: length as u8,
+            buffer,
+        })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[0..self.length as usize]
+    }
+}
+
+/// Previously sent notification has been delivered to the host
+#[derive(Clone, Copy)]
+pub struct BleNotifyIndicateEvent {
+    pub handle: u16,
+}
+
+impl BleNotifyIndicateEvent {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() != 2 {
+            None
+        } else {
+            Some(Self{
+                handle: BigEndian::read_u16(&raw[0..2]),
+            })
+        }
+    }
+}
+
+pub const UNKNOWN_EVENT_MAX_DATA_SIZE: usize = MAX_EVENT_SIZE - HEADER_SIZE;
+
+/// Event that the SDK doesn't know how to decode, the raw data is
+/// kept around for the application to interpret
+#[derive(Clone, Copy)]
+pub struct UnknownEvent {
+    pub tag: u8,
+    length: u8,
+    buffer: [u8; UNKNOWN_EVENT_MAX_DATA_SIZE],
+}
+
+impl UnknownEvent {
+    pub fn new(tag: u8, data: &[u8]) -> Self {
+        let length = min(data.len(), UNKNOWN_EVENT_MAX_DATA_SIZE);
+        let mut buffer = [0; UNKNOWN_EVENT_MAX_DATA_SIZE];
+        buffer[0..length].copy_from_slice(&data[0..length]);
+
+        Self{
+            tag,
+            length: length as u8,
+            buffer,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[0..self.length as usize]
+    }
+}
+
 pub enum Event {
     StartLoop,
     ButtonPush(ButtonPushEvent),
     DisplayProcessed(DisplayProcessedEvent),
     Ticker(TickerEvent),
+    Usb(UsbEvent),
+    UsbEndpointTransfer(UsbEndpointTransferEvent),
+    BleConnection(BleConnectionEvent),
+    BleWriteRequest(BleWriteRequestEvent),
+    BleNotifyIndicate(BleNotifyIndicateEvent),
+    Unknown(UnknownEvent),
 }
 
 impl Event {
+    /// Decodes the raw event, events with unsupported tags or payloads
+    /// are returned as `Event::Unknown`. Returns `None` only when the
+    /// event header itself is malformed.
     pub fn from_bytes(raw: &[u8]) -> Option<Self> {
-        if raw.len() < 3 {
+        if raw.len() < HEADER_SIZE {
             return None;
         }
 
         let tag = EventTag::from_u8(raw[0]);
-        let data_len = BigEndian::read_u16(&raw[1..3]);
-        let data = &raw[3..3+(data_len as usize)];
+        let data_len = BigEndian::read_u16(&raw[1..3]) as usize;
+        if raw.len() < HEADER_SIZE + data_len {
+            return None;
+        }
+        let data = &raw[HEADER_SIZE..HEADER_SIZE+data_len];
 
-        match tag {
+        let event = match tag {
             Some(EventTag::ButtonPush) =>
                 ButtonPushEvent::from_bytes(data)
                     .map(|e| Event::ButtonPush(e)),
@@ -90,7 +327,24 @@ impl Event {
             Some(EventTag::Ticker) =>
                 TickerEvent::from_bytes(data)
                     .map(|e| Event::Ticker(e)),
+            Some(EventTag::Usb) =>
+                UsbEvent::from_bytes(data)
+                    .map(|e| Event::Usb(e)),
+            Some(EventTag::UsbEndpointTransfer) =>
+                UsbEndpointTransferEvent::from_bytes(data)
+                    .map(|e| Event::UsbEndpointTransfer(e)),
+            Some(EventTag::BleConnection) =>
+                BleConnectionEvent::from_bytes(data)
+                    .map(|e| Event::BleConnection(e)),
+            Some(EventTag::BleWriteRequest) =>
+                BleWriteRequestEvent::from_bytes(data)
+                    .map(|e| Event::BleWriteRequest(e)),
+            Some(EventTag::BleNotifyIndicate) =>
+                BleNotifyIndicateEvent::from_bytes(data)
+                    .map(|e| Event::BleNotifyIndicate(e)),
             None => None,
-        }
+        };
+
+        Some(event.unwrap_or_else(|| Event::Unknown(UnknownEvent::new(raw[0], data))))
     }
 }
@@ -0,0 +1,290 @@
+use core::cmp::min;
+use byteorder::{ByteOrder, BigEndian};
+
+pub const PACKET_SIZE: usize = 64;
+
+const HEADER_SIZE: usize = 5;
+const FIRST_HEADER_SIZE: usize = HEADER_SIZE + 2;
+
+#[repr(u8)]
+enum FrameTag {
+    Ping = 0x02,
+    Apdu = 0x05,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Received {
+    /// More packets are needed to complete the APDU
+    Partial,
+    /// Host pinged the channel, the packet should be echoed back
+    Ping,
+    /// A complete APDU of the given length is in the buffer
+    Apdu(usize),
+    /// Packet didn't follow the framing rules and was discarded
+    Invalid,
+}
+
+/// Ledger's HID transport framing, which splits APDUs into 64 byte packets
+/// with a channel id, tag and a sequence number in the header:
+///
+/// ```text
+/// | channel (2) | tag (1) | seq (2) | [length (2), first packet only] | data |
+/// ```
+pub struct Framing {
+    channel: u16,
+    rx_sequence: u16,
+    rx_expected: usize,
+    rx_received: usize,
+    tx_sequence: u16,
+    tx_total: usize,
+    tx_sent: usize,
+}
+
+impl Framing {
+    pub fn new() -> Self {
+        Self{
+            channel: 0,
+            rx_sequence: 0,
+            rx_expected: 0,
+            rx_received: 0,
+            tx_sequence: 0,
+            tx_total: 0,
+            tx_sent: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Framing::new();
+    }
+
+    /// Decodes a single HID packet, appending any APDU data to `buf`
+    pub fn receive(&mut self, packet: &[u8], buf: &mut [u8]) -> Received {
+        if packet.len() < HEADER_SIZE {
+            return Received::Invalid;
+        }
+
+        let channel = BigEndian::read_u16(&packet[0..2]);
+        let tag = packet[2];
+        let sequence = BigEndian::read_u16(&packet[3..5]);
+
+        if tag == FrameTag::Ping as u8 {
+            self.channel = channel;
+            return Received::Ping;
+        } else if tag != FrameTag::Apdu as u8 {
+            return Received::Invalid;
+        }
+
+        let data = if sequence == 0 {
+            if packet.len() < FIRST_HEADER_SIZE {
+                return Received::Invalid;
+            }
+            let expected = BigEndian::read_u16(&packet[5..7]) as usize;
+            if expected > buf.len() {
+                self.rx_expected = 0;
+                return Received::Invalid;
+            }
+
+            self.channel = channel;
+            self.rx_sequence = 0;
+            self.rx_expected = expected;
+            self.rx_received = 0;
+            &packet[FIRST_HEADER_SIZE..]
+        } else {
+            // Continuation packets have to follow the first one in order
+            // and on the same channel
+            if self.rx_expected == 0
+                || channel != self.channel
+                || sequence != self.rx_sequence.wrapping_add(1) {
+                self.rx_expected = 0;
+                return Received::Invalid;
+            }
+
+            self.rx_sequence = sequence;
+            &packet[HEADER_SIZE..]
+        };
+
+        let cnt = min(data.len(), self.rx_expected - self.rx_received);
+        buf[self.rx_received..self.rx_received+cnt].copy_from_slice(&data[0..cnt]);
+        self.rx_received += cnt;
+
+        if self.rx_received == self.rx_expected {
+            let len = self.rx_expected;
+            self.rx_expected = 0;
+            self.rx_received = 0;
+            Received::Apdu(len)
+        } else {
+            Received::Partial
+        }
+    }
+
+    /// Prepares the framing for sending a response of `len` bytes
+    pub fn start_sending(&mut self, len: usize) {
+        self.tx_sequence = 0;
+        self.tx_total = len;
+        self.tx_sent = 0;
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.tx_sent < self.tx_total
+    }
+
+    /// Fills the `packet` with the next chunk of the response in `buf`,
+    /// returns false when there's nothing more to send
+    pub fn next_packet(&mut self, buf: &[u8], packet: &mut [u8; PACKET_SIZE]) -> bool {
+        if !self.is_sending() {
+            return false;
+        }
+
+        for b in packet.iter_mut() {
+            *b = 0;
+        }
+        BigEndian::write_u16(&mut packet[0..2], self.channel);
+        packet[2] = FrameTag::Apdu as u8;
+        BigEndian::write_u16(&mut packet[3..5], self.tx_sequence);
+
+        let data_offset = if self.tx_sequence == 0 {
+            BigEndian::write_u16(&mut packet[5..7], self.tx_total as u16);
+            FIRST_HEADER_SIZE
+        } else {
+            HEADER_SIZE
+        };
+
+        let cnt = min(PACKET_SIZE - data_offset, self.tx_total - self.tx_sent);
+        packet[data_offset..data_offset+cnt]
+            .copy_from_slice(&buf[self.tx_sent..self.tx_sent+cnt]);
+
+        self.tx_sent += cnt;
+        self.tx_sequence = self.tx_sequence.wrapping_add(1);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    const CHANNEL: u16 = 0x0101;
+
+    fn packet(channel: u16, tag: u8, sequence: u16, data: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut packet = [0; PACKET_SIZE];
+        BigEndian::write_u16(&mut packet[0..2], channel);
+        packet[2] = tag;
+        BigEndian::write_u16(&mut packet[3..5], sequence);
+        packet[HEADER_SIZE..HEADER_SIZE + data.len()].copy_from_slice(data);
+        packet
+    }
+
+    // First packet of an APDU, with the total length in front of the data
+    fn first_packet(channel: u16, len: usize, data: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut with_len = [0; PACKET_SIZE - HEADER_SIZE];
+        BigEndian::write_u16(&mut with_len[0..2], len as u16);
+        with_len[2..2 + data.len()].copy_from_slice(data);
+        packet(channel, 0x05, 0, &with_len)
+    }
+
+    fn apdu(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn receives_single_packet_apdu() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        let apdu = apdu(5);
+        assert_eq!(framing.receive(&first_packet(CHANNEL, 5, &apdu), &mut buf), Received::Apdu(5));
+        assert_eq!(&buf[0..5], &apdu[..]);
+    }
+
+    #[test]
+    fn reassembles_multi_packet_apdu() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        let apdu = apdu(130);
+        assert_eq!(framing.receive(&first_packet(CHANNEL, 130, &apdu[0..57]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 1, &apdu[57..116]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 2, &apdu[116..]), &mut buf), Received::Apdu(130));
+        assert_eq!(&buf[0..130], &apdu[..]);
+    }
+
+    #[test]
+    fn rejects_out_of_order_sequence() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        let apdu = apdu(130);
+        assert_eq!(framing.receive(&first_packet(CHANNEL, 130, &apdu[0..57]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 2, &apdu[116..]), &mut buf), Received::Invalid);
+        // The APDU is dropped, the rest of it doesn't complete it anymore
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 1, &apdu[57..116]), &mut buf), Received::Invalid);
+
+        // Continuation without the first packet
+        let mut framing = Framing::new();
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 1, &apdu[57..116]), &mut buf), Received::Invalid);
+    }
+
+    #[test]
+    fn rejects_continuation_on_another_channel() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        let apdu = apdu(130);
+        assert_eq!(framing.receive(&first_packet(CHANNEL, 130, &apdu[0..57]), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&packet(0x0202, 0x05, 1, &apdu[57..116]), &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 1, &apdu[57..116]), &mut buf), Received::Invalid);
+    }
+
+    #[test]
+    fn rejects_length_that_doesnt_fit_buffer() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 100];
+        let apdu = apdu(101);
+        assert_eq!(framing.receive(&first_packet(CHANNEL, 101, &apdu[0..57]), &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x05, 1, &apdu[57..]), &mut buf), Received::Invalid);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        // Too short for the header or the length of the first packet
+        assert_eq!(framing.receive(&[0x01, 0x01, 0x05, 0x00], &mut buf), Received::Invalid);
+        assert_eq!(framing.receive(&[0x01, 0x01, 0x05, 0x00, 0x00, 0x00], &mut buf), Received::Invalid);
+        // Unknown tag
+        assert_eq!(framing.receive(&packet(CHANNEL, 0x03, 0, &[]), &mut buf), Received::Invalid);
+    }
+
+    #[test]
+    fn responds_on_channel_of_the_command() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        assert_eq!(framing.receive(&packet(0x0303, 0x02, 0, &[]), &mut buf), Received::Ping);
+        assert_eq!(framing.receive(&first_packet(0x0404, 1, &[0xAA]), &mut buf), Received::Apdu(1));
+
+        let mut out = [0; PACKET_SIZE];
+        framing.start_sending(2);
+        assert!(framing.next_packet(&[0x90, 0x00], &mut out));
+        assert_eq!(&out[0..9], &[0x04, 0x04, 0x05, 0x00, 0x00, 0x00, 0x02, 0x90, 0x00]);
+    }
+
+    #[test]
+    fn splits_response_into_packets() {
+        let mut framing = Framing::new();
+        let mut buf = [0; 260];
+        framing.receive(&first_packet(CHANNEL, 1, &[0xAA]), &mut buf);
+
+        let response = apdu(130);
+        let mut out = [0; PACKET_SIZE];
+        framing.start_sending(response.len());
+        assert!(framing.is_sending());
+
+        assert!(framing.next_packet(&response, &mut out));
+        assert_eq!(&out[..], &first_packet(CHANNEL, 130, &response[0..57])[..]);
+        assert!(framing.next_packet(&response, &mut out));
+        assert_eq!(&out[..], &packet(CHANNEL, 0x05, 1, &response[57..116])[..]);
+        assert!(framing.next_packet(&response, &mut out));
+        // The rest of the last packet is padded with zeroes
+        assert_eq!(&out[..], &packet(CHANNEL, 0x05, 2, &response[116..])[..]);
+
+        assert!(!framing.is_sending());
+        assert!(!framing.next_packet(&response, &mut out));
+    }
+}
@@ -0,0 +1,235 @@
+use core::marker::PhantomData;
+use byteorder::{ByteOrder, BigEndian};
+use apdu;
+use super::Channel;
+
+pub const APDU_BUFFER_SIZE: usize = 260;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IoError {
+    /// There is no command APDU waiting for a response
+    NoCommand,
+    /// Response APDU doesn't fit into the transport buffers
+    Overflow,
+    /// Status for the event has already been sent, responses can only be
+    /// sent before it
+    StatusSent,
+    /// Host went away before the response could be sent
+    Disconnected,
+}
+
+/// Transport that exchanges APDUs with the host. Every command APDU
+/// returned by `recv_apdu` gets exactly one response via `send_rapdu`,
+/// until then the transport doesn't accept new commands.
+///
+/// The response is sent as seproxyhal commands, which is why it needs
+/// the `Channel` of an event whose status hasn't been sent yet.
+pub trait Io {
+    /// Lets the transport react to the event, returns false when the
+    /// event wasn't meant for the transport
+    fn process_event(&mut self, ch: &mut Channel) -> bool;
+
+    /// Command APDU that is waiting for a response
+    fn recv_apdu(&self) -> Option<&[u8]>;
+
+    /// Starts sending the response to the command APDU returned by
+    /// `recv_apdu`, the rest of it is sent while processing later events
+    fn send_rapdu(&mut self, ch: &mut Channel, rapdu: &[u8]) -> Result<(), IoError>;
+}
+
+/// Dispatches the command APDUs received by the transport to the
+/// delegate. When the delegate defers the response, the command is held
+/// by the transport and resumed on every following event until the
+/// delegate responds to it. Responses that can't be sent during the
+/// event, resumed ones included, are kept and sent during the next one
+/// without resuming the delegate again. The middleware should
+/// come before the others so that it sees all of the events.
+///
+/// ```ignore
+/// MessageLoop::new().for_each(|ch| {
+///     let _ch = Some(ch)
+///         .and_then(|ch| apdu.process_event(ch, &mut usb, &mut state))
+///         .and_then(|ch| ui.process_event(ch, &mut state))
+///         .and_then(|ch| ui.redraw_if_needed(ch, &mut state));
+/// });
+/// ```
+pub struct Middleware<D> {
+    deferred: bool,
+    response: [u8; APDU_BUFFER_SIZE],
+    response_len: Option<usize>,
+    phantom_delegate: PhantomData<D>,
+}
+
+impl<D> Middleware<D>
+    where D: apdu::Delegate,
+{
+    pub fn new() -> Self {
+        Self{
+            deferred: false,
+            response: [0; APDU_BUFFER_SIZE],
+            response_len: None,
+            phantom_delegate: PhantomData,
+        }
+    }
+
+    /// Whether a command is waiting for the delegate to respond to it
+    pub fn has_deferred_command(&self) -> bool {
+        self.deferred
+    }
+
+    /// Whether a response is waiting for an event to be sent during
+    pub fn has_pending_response(&self) -> bool {
+        self.response_len.is_some()
+    }
+
+    /// Lets the transport process the event and passes the received
+    /// command on to the delegate. Returns the channel when the event
+    /// wasn't meant for the transport.
+    pub fn process_event<T>(&mut self, mut ch: Channel, io: &mut T, delegate: &mut D) -> Option<Channel>
+        where T: Io,
+    {
+        let handled = io.process_event(&mut ch);
+
+        // The transport keeps the command until the pending response is
+        // sent, so it mustn't be dispatched again
+        if self.response_len.is_none() {
+            self.process_command(io, delegate);
+        }
+        self.send_response(&mut ch, io);
+
+        if handled {
+            None
+        } else {
+            Some(ch)
+        }
+    }
+
+    fn process_command<T>(&mut self, io: &mut T, delegate: &mut D)
+        where T: Io,
+    {
+        let resumed = self.deferred;
+        match io.recv_apdu() {
+            Some(command) => {
+                let result = if resumed {
+                    apdu::resume(delegate, command, &mut self.response)
+                } else {
+                    apdu::dispatch(delegate, command, &mut self.response)
+                };
+                match result {
+                    Ok(len) => {
+                        self.deferred = len.is_none();
+                        self.response_len = len;
+                    },
+                    // The buffer always fits the status word
+                    Err(_) => {
+                        self.deferred = false;
+                        self.response_len = None;
+                    },
+                }
+            },
+            None => {
+                // Transport was reset while the command was deferred
+                self.deferred = false;
+                if resumed {
+                    delegate.cancel_command();
+                }
+            },
+        }
+    }
+
+    fn send_response<T>(&mut self, ch: &mut Channel, io: &mut T)
+        where T: Io,
+    {
+        let len = match self.response_len {
+            Some(len) => len,
+            None => return,
+        };
+        match io.send_rapdu(ch, &self.response[0..len]) {
+            // Try again during the next event
+            Err(IoError::StatusSent) => {},
+            Err(IoError::Overflow) => {
+                // Let the host know that the command failed instead
+                let status = apdu::StatusWord::Unknown.to_wire_format();
+                BigEndian::write_u16(&mut self.response[0..2], status);
+                self.response_len = Some(2);
+            },
+            // The command is gone together with the host
+            Err(IoError::NoCommand) | Err(IoError::Disconnected) => {
+                self.response_len = None;
+            },
+            Ok(()) => {
+                self.response_len = None;
+            },
+        }
+    }
+}
+
+/// Transport that isn't connected to anything, the commands are pushed
+/// and the responses are taken by the application itself. Useful for
+/// testing the APDU handling in the simulator.
+pub struct Loopback {
+    command: [u8; APDU_BUFFER_SIZE],
+    command_len: Option<usize>,
+    response: [u8; APDU_BUFFER_SIZE],
+    response_len: Option<usize>,
+}
+
+impl Loopback {
+    pub fn new() -> Self {
+        Self{
+            command: [0; APDU_BUFFER_SIZE],
+            command_len: None,
+            response: [0; APDU_BUFFER_SIZE],
+            response_len: None,
+        }
+    }
+
+    /// Makes the `command` available via `recv_apdu`, replacing the
+    /// previous command if it hasn't been responded to
+    pub fn push_apdu(&mut self, command: &[u8]) -> Result<(), IoError> {
+        if command.len() > self.command.len() {
+            return Err(IoError::Overflow);
+        }
+        self.command[0..command.len()].copy_from_slice(command);
+        self.command_len = Some(command.len());
+        Ok(())
+    }
+
+    /// Takes the response to the last command, if it has been sent
+    pub fn take_rapdu(&mut self) -> Option<&[u8]> {
+        match self.response_len.take() {
+            Some(len) => Some(&self.response[0..len]),
+            None => None,
+        }
+    }
+}
+
+impl Io for Loopback {
+    fn process_event(&mut self, _ch: &mut Channel) -> bool {
+        false
+    }
+
+    fn recv_apdu(&self) -> Option<&[u8]> {
+        match self.command_len {
+            Some(len) => Some(&self.command[0..len]),
+            None => None,
+        }
+    }
+
+    fn send_rapdu(&mut self, ch: &mut Channel, rapdu: &[u8]) -> Result<(), IoError> {
+        if ch.is_status_sent() {
+            return Err(IoError::StatusSent);
+        }
+        if self.command_len.is_none() {
+            return Err(IoError::NoCommand);
+        }
+        if rapdu.len() > self.response.len() {
+            return Err(IoError::Overflow);
+        }
+
+        self.response[0..rapdu.len()].copy_from_slice(rapdu);
+        self.response_len = Some(rapdu.len());
+        self.command_len = None;
+        Ok(())
+    }
+}
@@ -3,9 +3,14 @@ mod packet;
 pub mod event;
 pub mod command;
 pub mod status;
+mod hid;
+mod u2f;
+pub mod io;
+pub mod usb;
+pub mod ble;
 
 use syscall::{check_api_level, io_seproxyhal_spi_recv, io_seproxyhal_spi_is_status_sent};
-use self::event::Event;
+use self::event::{Event, UnknownEvent, MAX_EVENT_SIZE};
 use self::command::Command;
 use self::status::Status;
 
@@ -45,12 +50,17 @@ impl Iterator for MessageLoop {
         let ev = if first_loop && !is_status_sent {
             Event::StartLoop
         } else {
-            let mut buf = [0; 64];
+            let mut buf = [0; MAX_EVENT_SIZE];
             let read = io_seproxyhal_spi_recv(&mut buf, 0)
                 .expect("Unable to read event data");
 
-            Event::from_bytes(&buf[0..read])
-                .expect("Unsupported event")
+            let raw = &buf[0..read];
+            Event::from_bytes(raw).unwrap_or_else(|| {
+                // Malformed header, pass it on so that the event
+                // still gets a status reply
+                let tag = if raw.len() > 0 { raw[0] } else { 0 };
+                Event::Unknown(UnknownEvent::new(tag, &[]))
+            })
         };
         Some(Channel::new(ev))
     }
@@ -77,18 +87,20 @@ impl Channel {
         self.status_sent = true;
         packet::send(status).expect("Failed to send status")
     }
+
+    /// Whether the app or the supervisor has already replied to the
+    /// event, after which no more commands can be sent
+    pub fn is_status_sent(&self) -> bool {
+        self.status_sent || io_seproxyhal_spi_is_status_sent().unwrap()
+    }
 }
 
 impl Drop for Channel {
     fn drop(&mut self) {
         // Send a general status code when the app nor the
         // supervisor has sent one yet
-        let app_status_sent = self.status_sent;
-        if !app_status_sent {
-            let os_status_sent = io_seproxyhal_spi_is_status_sent().unwrap();
-            if !os_status_sent {
-                packet::send(status::GeneralStatus{}).is_ok();
-            }
+        if !self.is_status_sent() {
+            packet::send(status::GeneralStatus{}).is_ok();
         }
     }
 }
\ No newline at end of file
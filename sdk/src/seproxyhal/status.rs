@@ -174,6 +174,54 @@ impl<'a> Into<Status<'a>> for ScreenDisplayTextStatus<'a> {
     }
 }
 
+pub struct ScreenDisplayButtonStatus<'a> {
+    pub user_id: u8,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub stroke: u8,
+    pub radius: u8,
+    pub fill: u8,
+    pub foreground_color: u32,
+    pub background_color: u32,
+    pub font_id: u16,
+    pub text: &'a str,
+}
+
+impl<'a> Packet for ScreenDisplayButtonStatus<'a> {
+    impl_packet!(self, StatusTag::ScreenDisplay, {
+        [S] SCREEN_DISPLAY_HEADER_SIZE => make_screen_display_header(
+            ScreenDisplayStatusTypeId::Button,
+            self.user_id,
+            self.x,
+            self.y,
+            self.width,
+            self.height,
+            self.stroke,
+            self.radius,
+            self.fill,
+            self.foreground_color,
+            self.background_color,
+            self.font_id,
+            0,
+        ),
+        [S] self.text.pic().len() => self.text.pic().as_bytes(),
+    });
+}
+
+impl<'a> Into<ScreenDisplayStatus<'a>> for ScreenDisplayButtonStatus<'a> {
+    fn into(self) -> ScreenDisplayStatus<'a> {
+        ScreenDisplayStatus::Button(self)
+    }
+}
+
+impl<'a> Into<Status<'a>> for ScreenDisplayButtonStatus<'a> {
+    fn into(self) -> Status<'a> {
+        Status::ScreenDisplay(self.into())
+    }
+}
+
 pub struct ScreenDisplaySystemIconStatus {
     pub user_id: u8,
     pub x: i16,
@@ -270,6 +318,7 @@ impl<'a> Into<Status<'a>> for ScreenDisplayCustomIconStatus<'a> {
 pub enum ScreenDisplayStatus<'a> {
     Shape(ScreenDisplayShapeStatus),
     Text(ScreenDisplayTextStatus<'a>),
+    Button(ScreenDisplayButtonStatus<'a>),
     SystemIcon(ScreenDisplaySystemIconStatus),
     CustomIcon(ScreenDisplayCustomIconStatus<'a>),
 }
@@ -279,6 +328,7 @@ impl<'a> Packet for ScreenDisplayStatus<'a> {
         match self {
             &ScreenDisplayStatus::Shape(ref s) => s.bytes_size(),
             &ScreenDisplayStatus::Text(ref s) => s.bytes_size(),
+            &ScreenDisplayStatus::Button(ref s) => s.bytes_size(),
             &ScreenDisplayStatus::SystemIcon(ref s) => s.bytes_size(),
             &ScreenDisplayStatus::CustomIcon(ref s) => s.bytes_size(),
         }
@@ -288,6 +338,7 @@ impl<'a> Packet for ScreenDisplayStatus<'a> {
         match self {
             &ScreenDisplayStatus::Shape(ref s) => s.to_bytes(buf, offset),
             &ScreenDisplayStatus::Text(ref s) => s.to_bytes(buf, offset),
+            &ScreenDisplayStatus::Button(ref s) => s.to_bytes(buf, offset),
             &ScreenDisplayStatus::SystemIcon(ref s) => s.to_bytes(buf, offset),
             &ScreenDisplayStatus::CustomIcon(ref s) => s.to_bytes(buf, offset),
         }
@@ -0,0 +1,664 @@
+use core::cmp::min;
+use byteorder::{ByteOrder, BigEndian};
+use apdu::StatusWord;
+
+pub const PACKET_SIZE: usize = 64;
+
+// Largest authenticate request: extended APDU header (7), challenge and
+// application parameters (64), key handle (1 + 255) and Le (2)
+pub const MESSAGE_BUFFER_SIZE: usize = 7 + 64 + 1 + 255 + 2;
+
+const INIT_HEADER_SIZE: usize = 7;
+const CONT_HEADER_SIZE: usize = 5;
+
+const BROADCAST_CHANNEL: u32 = 0xFFFF_FFFF;
+const INIT_NONCE_SIZE: usize = 8;
+const PROTOCOL_VERSION: u8 = 2;
+const CAPABILITY_WINK: u8 = 0x01;
+
+const KEEP_ALIVE_PROCESSING: u8 = 0x01;
+
+const INS_AUTHENTICATE: u8 = 0x02;
+const INS_VERSION: u8 = 0x03;
+const AUTHENTICATE_KEY_HANDLE_OFFSET: usize = 64;
+const APDU_HEADER_SIZE: usize = 5;
+
+static VERSION: [u8; 6] = *b"U2F_V2";
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Ping = 0x81,
+    Message = 0x83,
+    Lock = 0x84,
+    Init = 0x86,
+    Wink = 0x88,
+    KeepAlive = 0xBB,
+    Error = 0xBF,
+}
+
+impl Command {
+    fn from_u8(value: u8) -> Option<Self> {
+        if value == Command::Ping as u8 {
+            Some(Command::Ping)
+        } else if value == Command::Message as u8 {
+            Some(Command::Message)
+        } else if value == Command::Lock as u8 {
+            Some(Command::Lock)
+        } else if value == Command::Init as u8 {
+            Some(Command::Init)
+        } else if value == Command::Wink as u8 {
+            Some(Command::Wink)
+        } else {
+            None
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    InvalidCommand = 0x01,
+    InvalidLength = 0x03,
+    InvalidSequence = 0x04,
+    ChannelBusy = 0x06,
+    InvalidChannel = 0x0B,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Received {
+    /// More packets are needed to complete the message
+    Partial,
+    /// A complete message of the given length is in the buffer
+    Message{ channel: u32, command: u8, len: usize },
+    /// The packet broke the framing rules, the error should be sent to
+    /// the channel
+    Error{ channel: u32, code: ErrorCode },
+    /// Packet was silently discarded
+    Ignored,
+}
+
+/// U2F HID framing, which splits messages into 64 byte packets with a
+/// channel id and either a command (first packet) or a sequence number:
+///
+/// ```text
+/// | channel (4) | command (1) | length (2) | data |
+/// | channel (4) | seq (1) | data |
+/// ```
+pub struct Framing {
+    last_channel: u32,
+    rx_channel: u32,
+    rx_command: u8,
+    rx_sequence: u8,
+    rx_expected: usize,
+    rx_received: usize,
+    tx_active: bool,
+    tx_channel: u32,
+    tx_command: u8,
+    tx_sequence: u8,
+    tx_total: usize,
+    tx_sent: usize,
+}
+
+impl Framing {
+    pub fn new() -> Self {
+        Self{
+            last_channel: 0,
+            rx_channel: 0,
+            rx_command: 0,
+            rx_sequence: 0,
+            rx_expected: 0,
+            rx_received: 0,
+            tx_active: false,
+            tx_channel: 0,
+            tx_command: 0,
+            tx_sequence: 0,
+            tx_total: 0,
+            tx_sent: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Framing::new();
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.rx_received < self.rx_expected
+    }
+
+    /// Decodes a single HID packet, appending the message data to `buf`
+    pub fn receive(&mut self, packet: &[u8], buf: &mut [u8]) -> Received {
+        if packet.len() < CONT_HEADER_SIZE {
+            return Received::Ignored;
+        }
+
+        let channel = BigEndian::read_u32(&packet[0..4]);
+        if channel == 0 {
+            return Received::Error{ channel, code: ErrorCode::InvalidChannel };
+        }
+
+        let data = if packet[4] & 0x80 != 0 {
+            if packet.len() < INIT_HEADER_SIZE {
+                return Received::Ignored;
+            }
+            let command = packet[4];
+            let is_init = command == Command::Init as u8;
+
+            // Only init requests may interrupt an unfinished message
+            if self.is_receiving() && !is_init {
+                let code = if channel == self.rx_channel {
+                    self.rx_expected = 0;
+                    ErrorCode::InvalidSequence
+                } else {
+                    ErrorCode::ChannelBusy
+                };
+                return Received::Error{ channel, code };
+            }
+            if channel == BROADCAST_CHANNEL && !is_init {
+                return Received::Error{ channel, code: ErrorCode::InvalidChannel };
+            }
+
+            let expected = BigEndian::read_u16(&packet[5..7]) as usize;
+            if expected > buf.len() {
+                self.rx_expected = 0;
+                return Received::Error{ channel, code: ErrorCode::InvalidLength };
+            }
+
+            self.rx_channel = channel;
+            self.rx_command = command;
+            self.rx_sequence = 0;
+            self.rx_expected = expected;
+            self.rx_received = 0;
+            &packet[INIT_HEADER_SIZE..]
+        } else {
+            // Stray continuation packets are ignored
+            if !self.is_receiving() || channel != self.rx_channel {
+                return Received::Ignored;
+            }
+            if packet[4] != self.rx_sequence {
+                self.rx_expected = 0;
+                return Received::Error{ channel, code: ErrorCode::InvalidSequence };
+            }
+
+            self.rx_sequence = self.rx_sequence.wrapping_add(1);
+            &packet[CONT_HEADER_SIZE..]
+        };
+
+        let cnt = min(data.len(), self.rx_expected - self.rx_received);
+        buf[self.rx_received..self.rx_received+cnt].copy_from_slice(&data[0..cnt]);
+        self.rx_received += cnt;
+
+        if self.rx_received == self.rx_expected {
+            let len = self.rx_expected;
+            self.rx_expected = 0;
+            self.rx_received = 0;
+            Received::Message{ channel, command: self.rx_command, len }
+        } else {
+            Received::Partial
+        }
+    }
+
+    /// Decodes a packet that arrived while the message buffer is still in
+    /// use, the hosts that start a new message are told to try again later
+    pub fn receive_while_busy(&self, packet: &[u8]) -> Received {
+        if packet.len() < INIT_HEADER_SIZE || packet[4] & 0x80 == 0 {
+            return Received::Ignored;
+        }
+
+        let channel = BigEndian::read_u32(&packet[0..4]);
+        if channel == 0 {
+            return Received::Error{ channel, code: ErrorCode::InvalidChannel };
+        }
+        Received::Error{ channel, code: ErrorCode::ChannelBusy }
+    }
+
+    /// Hands out a new channel id for a host that sent an init request
+    /// on the broadcast channel
+    pub fn allocate_channel(&mut self) -> u32 {
+        self.last_channel = self.last_channel.wrapping_add(1);
+        if self.last_channel == 0 || self.last_channel == BROADCAST_CHANNEL {
+            self.last_channel = 1;
+        }
+        self.last_channel
+    }
+
+    /// Prepares the framing for sending a reply of `len` bytes
+    pub fn start_sending(&mut self, channel: u32, command: u8, len: usize) {
+        self.tx_active = true;
+        self.tx_channel = channel;
+        self.tx_command = command;
+        self.tx_sequence = 0;
+        self.tx_total = len;
+        self.tx_sent = 0;
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.tx_active
+    }
+
+    /// Fills the `packet` with the next chunk of the reply in `buf`,
+    /// returns false when there's nothing more to send
+    pub fn next_packet(&mut self, buf: &[u8], packet: &mut [u8; PACKET_SIZE]) -> bool {
+        if !self.tx_active {
+            return false;
+        }
+
+        for b in packet.iter_mut() {
+            *b = 0;
+        }
+        BigEndian::write_u32(&mut packet[0..4], self.tx_channel);
+
+        let data_offset = if self.tx_sent == 0 {
+            packet[4] = self.tx_command;
+            BigEndian::write_u16(&mut packet[5..7], self.tx_total as u16);
+            INIT_HEADER_SIZE
+        } else {
+            packet[4] = self.tx_sequence;
+            self.tx_sequence = self.tx_sequence.wrapping_add(1);
+            CONT_HEADER_SIZE
+        };
+
+        let cnt = min(PACKET_SIZE - data_offset, self.tx_total - self.tx_sent);
+        packet[data_offset..data_offset+cnt]
+            .copy_from_slice(&buf[self.tx_sent..self.tx_sent+cnt]);
+
+        self.tx_sent += cnt;
+        // Even empty replies are sent as a single packet
+        self.tx_active = self.tx_sent < self.tx_total;
+        true
+    }
+}
+
+/// Builds a single packet reply, used for errors and keep-alive
+/// notifications that don't go through the regular reply buffer
+pub fn make_packet(channel: u32, command: Command, data: &[u8], packet: &mut [u8; PACKET_SIZE]) {
+    for b in packet.iter_mut() {
+        *b = 0;
+    }
+    BigEndian::write_u32(&mut packet[0..4], channel);
+    packet[4] = command as u8;
+    BigEndian::write_u16(&mut packet[5..7], data.len() as u16);
+    packet[INIT_HEADER_SIZE..INIT_HEADER_SIZE+data.len()].copy_from_slice(data);
+}
+
+pub fn make_error_packet(channel: u32, code: ErrorCode, packet: &mut [u8; PACKET_SIZE]) {
+    make_packet(channel, Command::Error, &[code as u8], packet)
+}
+
+pub fn make_keep_alive_packet(channel: u32, packet: &mut [u8; PACKET_SIZE]) {
+    make_packet(channel, Command::KeepAlive, &[KEEP_ALIVE_PROCESSING], packet)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Request {
+    /// The message contained an APDU of the given length, which has been
+    /// unwrapped to the start of the message buffer
+    Tunnelled(usize),
+    /// The message was answered in place, the reply of the given length
+    /// should be sent with the command
+    Reply(Command, usize),
+    Error(ErrorCode),
+}
+
+/// Processes a complete message of `len` bytes in `msg`. Replies are
+/// written back into `msg`.
+pub fn process_message(
+    framing: &mut Framing,
+    channel: u32,
+    command: u8,
+    msg: &mut [u8],
+    len: usize,
+    scramble_key: &[u8],
+) -> Request {
+    match Command::from_u8(command) {
+        Some(Command::Init) => {
+            if len != INIT_NONCE_SIZE {
+                return Request::Error(ErrorCode::InvalidLength);
+            }
+            let new_channel = if channel == BROADCAST_CHANNEL {
+                framing.allocate_channel()
+            } else {
+                channel
+            };
+            // Reply with the nonce, followed by the channel id and the
+            // device info
+            BigEndian::write_u32(&mut msg[8..12], new_channel);
+            msg[12] = PROTOCOL_VERSION;
+            msg[13] = 0; // major version
+            msg[14] = 0; // minor version
+            msg[15] = 0; // build version
+            msg[16] = CAPABILITY_WINK;
+            Request::Reply(Command::Init, 17)
+        },
+        Some(Command::Ping) => Request::Reply(Command::Ping, len),
+        Some(Command::Wink) => Request::Reply(Command::Wink, 0),
+        Some(Command::Lock) => Request::Reply(Command::Lock, 0),
+        Some(Command::Message) => process_raw_message(msg, len, scramble_key),
+        _ => Request::Error(ErrorCode::InvalidCommand),
+    }
+}
+
+fn process_raw_message(msg: &mut [u8], len: usize, scramble_key: &[u8]) -> Request {
+    if len < 4 {
+        return reply_status(msg, StatusWord::WrongLength);
+    }
+
+    // U2F uses extended length APDUs, but short ones are accepted as well
+    let (data_offset, lc) = if len == 4 {
+        (4, 0)
+    } else if msg[4] == 0 && len >= 7 {
+        (7, BigEndian::read_u16(&msg[5..7]) as usize)
+    } else {
+        (5, msg[4] as usize)
+    };
+    if data_offset + lc > len {
+        return reply_status(msg, StatusWord::WrongLength);
+    }
+
+    let ins = msg[1];
+    match ins {
+        INS_VERSION => {
+            msg[0..VERSION.len()].copy_from_slice(&VERSION);
+            let sw_len = write_status(&mut msg[VERSION.len()..], StatusWord::Ok);
+            Request::Reply(Command::Message, VERSION.len() + sw_len)
+        },
+        INS_AUTHENTICATE => {
+            match unwrap_key_handle(msg, data_offset, lc, scramble_key) {
+                Ok(apdu_len) => Request::Tunnelled(apdu_len),
+                Err(sw) => reply_status(msg, sw),
+            }
+        },
+        _ => reply_status(msg, StatusWord::InstructionNotSupported),
+    }
+}
+
+// Tunnelled APDUs are XOR-ed with the scramble key and passed to the
+// authenticate request in place of the key handle
+fn unwrap_key_handle(msg: &mut [u8], data_offset: usize, lc: usize, scramble_key: &[u8]) -> Result<usize, StatusWord> {
+    if lc <= AUTHENTICATE_KEY_HANDLE_OFFSET {
+        return Err(StatusWord::WrongLength);
+    }
+    let kh_len = msg[data_offset + AUTHENTICATE_KEY_HANDLE_OFFSET] as usize;
+    let kh_start = data_offset + AUTHENTICATE_KEY_HANDLE_OFFSET + 1;
+    if kh_start + kh_len != data_offset + lc || kh_len < APDU_HEADER_SIZE {
+        return Err(StatusWord::IncorrectData);
+    }
+
+    // The key handle always comes after its destination, so it can be
+    // unwrapped in place
+    for i in 0..kh_len {
+        let b = msg[kh_start + i];
+        msg[i] = if scramble_key.len() > 0 {
+            b ^ scramble_key[i % scramble_key.len()]
+        } else {
+            b
+        };
+    }
+
+    // Make sure that the key handle looks like an APDU
+    if msg[4] as usize != kh_len - APDU_HEADER_SIZE {
+        return Err(StatusWord::IncorrectData);
+    }
+    Ok(kh_len)
+}
+
+/// Wraps the response to a tunnelled APDU into `msg`, returning the
+/// length of the reply
+pub fn wrap_response(msg: &mut [u8], response: &[u8]) -> usize {
+    // The response APDU is passed to the host as the signature, which
+    // needs a successful status of its own
+    msg[0..response.len()].copy_from_slice(response);
+    response.len() + write_status(&mut msg[response.len()..], StatusWord::Ok)
+}
+
+fn write_status(buf: &mut [u8], status: StatusWord) -> usize {
+    BigEndian::write_u16(&mut buf[0..2], status.to_wire_format());
+    2
+}
+
+fn reply_status(msg: &mut [u8], status: StatusWord) -> Request {
+    Request::Reply(Command::Message, write_status(msg, status))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    const CHANNEL: u32 = 0x0102_0304;
+    const SCRAMBLE_KEY: &[u8] = b"w0w";
+
+    fn init_packet(channel: u32, command: u8, len: usize, data: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut packet = [0; PACKET_SIZE];
+        BigEndian::write_u32(&mut packet[0..4], channel);
+        packet[4] = command;
+        BigEndian::write_u16(&mut packet[5..7], len as u16);
+        packet[INIT_HEADER_SIZE..INIT_HEADER_SIZE + data.len()].copy_from_slice(data);
+        packet
+    }
+
+    fn cont_packet(channel: u32, sequence: u8, data: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut packet = [0; PACKET_SIZE];
+        BigEndian::write_u32(&mut packet[0..4], channel);
+        packet[4] = sequence;
+        packet[CONT_HEADER_SIZE..CONT_HEADER_SIZE + data.len()].copy_from_slice(data);
+        packet
+    }
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn scramble(data: &[u8]) -> Vec<u8> {
+        data.iter().enumerate()
+            .map(|(i, b)| b ^ SCRAMBLE_KEY[i % SCRAMBLE_KEY.len()])
+            .collect()
+    }
+
+    // Authenticate request, in an extended length APDU, with the given
+    // key handle
+    fn authenticate_request(key_handle: &[u8]) -> Vec<u8> {
+        let lc = AUTHENTICATE_KEY_HANDLE_OFFSET + 1 + key_handle.len();
+        let mut msg = vec![0x00, INS_AUTHENTICATE, 0x03, 0x00, 0x00, (lc >> 8) as u8, lc as u8];
+        msg.extend((0..AUTHENTICATE_KEY_HANDLE_OFFSET).map(|i| i as u8));
+        msg.push(key_handle.len() as u8);
+        msg.extend_from_slice(key_handle);
+        msg.extend_from_slice(&[0x00, 0x00]);
+        msg
+    }
+
+    #[test]
+    fn receives_single_packet_message() {
+        let mut framing = Framing::new();
+        let mut buf = [0; MESSAGE_BUFFER_SIZE];
+        let msg = message(10);
+        let packet = init_packet(CHANNEL, Command::Message as u8, 10, &msg);
+        assert_eq!(
+            framing.receive(&packet, &mut buf),
+            Received::Message{ channel: CHANNEL, command: Command::Message as u8, len: 10 },
+        );
+        assert_eq!(&buf[0..10], &msg[..]);
+    }
+
+    #[test]
+    fn reassembles_multi_packet_message() {
+        let mut framing = Framing::new();
+        let mut buf = [0; MESSAGE_BUFFER_SIZE];
+        let msg = message(150);
+        let command = Command::Message as u8;
+
+        let first = &msg[0..57];
+        assert_eq!(framing.receive(&init_packet(CHANNEL, command, 150, first), &mut buf), Received::Partial);
+        assert_eq!(framing.receive(&cont_packet(CHANNEL, 0, &msg[57..116]), &mut buf), Received::Partial);
+        assert_eq!(
+            framing.receive(&cont_packet(CHANNEL, 1, &msg[116..]), &mut buf),
+            Received::Message{ channel: CHANNEL, command, len: 150 },
+        );
+        assert_eq!(&buf[0..150], &msg[..]);
+    }
+
+    #[test]
+    fn rejects_out_of_order_sequence() {
+        let mut framing = Framing::new();
+        let mut buf = [0; MESSAGE_BUFFER_SIZE];
+        let msg = message(150);
+        let command = Command::Message as u8;
+
+        framing.receive(&init_packet(CHANNEL, command, 150, &msg[0..57]), &mut buf);
+        assert_eq!(
+            framing.receive(&cont_packet(CHANNEL, 1, &msg[57..116]), &mut buf),
+            Received::Error{ channel: CHANNEL, code: ErrorCode::InvalidSequence },
+        );
+
+        // The broken message was dropped
+        assert_eq!(framing.receive(&cont_packet(CHANNEL, 0, &msg[57..116]), &mut buf), Received::Ignored);
+    }
+
+    #[test]
+    fn refuses_other_channels_while_receiving() {
+        let mut framing = Framing::new();
+        let mut buf = [0; MESSAGE_BUFFER_SIZE];
+        let msg = message(150);
+        let command = Command::Message as u8;
+
+        framing.receive(&init_packet(CHANNEL, command, 150, &msg[0..57]), &mut buf);
+        assert_eq!(
+            framing.receive(&init_packet(CHANNEL + 1, command, 10, &msg[0..10]), &mut buf),
+            Received::Error{ channel: CHANNEL + 1, code: ErrorCode::ChannelBusy },
+        );
+        assert_eq!(framing.receive(&cont_packet(CHANNEL + 1, 0, &msg[57..116]), &mut buf), Received::Ignored);
+
+        // The unfinished message wasn't disturbed
+        assert_eq!(framing.receive(&cont_packet(CHANNEL, 0, &msg[57..116]), &mut buf), Received::Partial);
+    }
+
+    #[test]
+    fn rejects_invalid_channels_and_lengths() {
+        let mut framing = Framing::new();
+        let mut buf = [0; MESSAGE_BUFFER_SIZE];
+        let command = Command::Message as u8;
+
+        assert_eq!(
+            framing.receive(&init_packet(0, command, 1, &[0]), &mut buf),
+            Received::Error{ channel: 0, code: ErrorCode::InvalidChannel },
+        );
+        assert_eq!(
+            framing.receive(&init_packet(BROADCAST_CHANNEL, command, 1, &[0]), &mut buf),
+            Received::Error{ channel: BROADCAST_CHANNEL, code: ErrorCode::InvalidChannel },
+        );
+        assert_eq!(
+            framing.receive(&init_packet(CHANNEL, command, MESSAGE_BUFFER_SIZE + 1, &[0]), &mut buf),
+            Received::Error{ channel: CHANNEL, code: ErrorCode::InvalidLength },
+        );
+        assert_eq!(framing.receive(&[0x01, 0x02], &mut buf), Received::Ignored);
+    }
+
+    #[test]
+    fn answers_new_messages_with_busy_while_busy() {
+        let framing = Framing::new();
+        let command = Command::Message as u8;
+
+        assert_eq!(
+            framing.receive_while_busy(&init_packet(CHANNEL, command, 10, &[])),
+            Received::Error{ channel: CHANNEL, code: ErrorCode::ChannelBusy },
+        );
+        assert_eq!(
+            framing.receive_while_busy(&init_packet(BROADCAST_CHANNEL, Command::Init as u8, 8, &[])),
+            Received::Error{ channel: BROADCAST_CHANNEL, code: ErrorCode::ChannelBusy },
+        );
+        assert_eq!(framing.receive_while_busy(&cont_packet(CHANNEL, 0, &[])), Received::Ignored);
+    }
+
+    #[test]
+    fn splits_reply_into_packets() {
+        let mut framing = Framing::new();
+        let msg = message(150);
+        let mut packet = [0; PACKET_SIZE];
+
+        framing.start_sending(CHANNEL, Command::Message as u8, 150);
+        assert!(framing.next_packet(&msg, &mut packet));
+        assert_eq!(&packet[..], &init_packet(CHANNEL, Command::Message as u8, 150, &msg[0..57])[..]);
+        assert!(framing.next_packet(&msg, &mut packet));
+        assert_eq!(&packet[..], &cont_packet(CHANNEL, 0, &msg[57..116])[..]);
+        assert!(framing.next_packet(&msg, &mut packet));
+        assert_eq!(&packet[..], &cont_packet(CHANNEL, 1, &msg[116..])[..]);
+        assert!(!framing.is_sending());
+        assert!(!framing.next_packet(&msg, &mut packet));
+
+        // Empty replies still need a packet
+        framing.start_sending(CHANNEL, Command::Wink as u8, 0);
+        assert!(framing.next_packet(&msg, &mut packet));
+        assert_eq!(&packet[..], &init_packet(CHANNEL, Command::Wink as u8, 0, &[])[..]);
+        assert!(!framing.is_sending());
+    }
+
+    #[test]
+    fn init_on_broadcast_allocates_channel() {
+        let mut framing = Framing::new();
+        let mut msg = [0; MESSAGE_BUFFER_SIZE];
+        msg[0..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let request = process_message(&mut framing, BROADCAST_CHANNEL, Command::Init as u8, &mut msg, 8, SCRAMBLE_KEY);
+        assert_eq!(request, Request::Reply(Command::Init, 17));
+        assert_eq!(&msg[0..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(BigEndian::read_u32(&msg[8..12]), 1);
+        assert_eq!(msg[12], PROTOCOL_VERSION);
+
+        // Known channels keep their id
+        let request = process_message(&mut framing, CHANNEL, Command::Init as u8, &mut msg, 8, SCRAMBLE_KEY);
+        assert_eq!(request, Request::Reply(Command::Init, 17));
+        assert_eq!(BigEndian::read_u32(&msg[8..12]), CHANNEL);
+    }
+
+    #[test]
+    fn answers_version_request() {
+        let mut framing = Framing::new();
+        let mut msg = [0; MESSAGE_BUFFER_SIZE];
+        msg[0..7].copy_from_slice(&[0x00, INS_VERSION, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        let request = process_message(&mut framing, CHANNEL, Command::Message as u8, &mut msg, 7, SCRAMBLE_KEY);
+        assert_eq!(request, Request::Reply(Command::Message, 8));
+        assert_eq!(&msg[0..8], b"U2F_V2\x90\x00");
+    }
+
+    #[test]
+    fn unscrambles_tunnelled_apdu() {
+        let mut framing = Framing::new();
+        let apdu = [0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA, 0xBB];
+        let key_handle = scramble(&apdu);
+        assert_eq!(&key_handle[..], &[0x97, 0x31, 0x77, 0x77, 0x32, 0xDD, 0xCC]);
+
+        let request = authenticate_request(&key_handle);
+        let mut msg = [0; MESSAGE_BUFFER_SIZE];
+        msg[0..request.len()].copy_from_slice(&request);
+
+        let command = Command::Message as u8;
+        let request = process_message(&mut framing, CHANNEL, command, &mut msg, request.len(), SCRAMBLE_KEY);
+        assert_eq!(request, Request::Tunnelled(apdu.len()));
+        assert_eq!(&msg[0..apdu.len()], &apdu);
+    }
+
+    #[test]
+    fn refuses_key_handles_that_arent_apdus() {
+        let mut framing = Framing::new();
+        let apdu = [0xE0, 0x01, 0x00, 0x00, 0x02, 0xAA, 0xBB];
+        let command = Command::Message as u8;
+
+        // Regular key handles don't unscramble into an APDU
+        let request = authenticate_request(&apdu);
+        let mut msg = [0; MESSAGE_BUFFER_SIZE];
+        msg[0..request.len()].copy_from_slice(&request);
+        let request = process_message(&mut framing, CHANNEL, command, &mut msg, request.len(), SCRAMBLE_KEY);
+        assert_eq!(request, Request::Reply(Command::Message, 2));
+        assert_eq!(&msg[0..2], &[0x6A, 0x80]);
+
+        // Key handle must fill the rest of the request
+        let mut request = authenticate_request(&scramble(&apdu));
+        request[AUTHENTICATE_KEY_HANDLE_OFFSET + 7] -= 1;
+        let mut msg = [0; MESSAGE_BUFFER_SIZE];
+        msg[0..request.len()].copy_from_slice(&request);
+        let request = process_message(&mut framing, CHANNEL, command, &mut msg, request.len(), SCRAMBLE_KEY);
+        assert_eq!(request, Request::Reply(Command::Message, 2));
+        assert_eq!(&msg[0..2], &[0x6A, 0x80]);
+    }
+}
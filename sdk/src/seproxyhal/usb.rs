@@ -0,0 +1,736 @@
+use core::cmp::min;
+use byteorder::{ByteOrder, LittleEndian};
+use super::Channel;
+use super::io::{Io, IoError, APDU_BUFFER_SIZE};
+use super::event::{Event, UsbEvent, UsbEventType, UsbTransferType, UsbEndpointTransferEvent};
+use super::command::{
+    UsbConnectCommand, UsbDisconnectCommand, UsbAddressCommand, UsbEndpointCommand,
+    UsbEndpointType, UsbEndpointPrepareCommand, UsbEndpointPrepareDirection,
+};
+use super::hid;
+use super::u2f;
+use pic::Pic;
+
+const CONTROL_MAX_PACKET_SIZE: usize = 64;
+const CONTROL_OUT_ENDPOINT: u8 = 0x00;
+const CONTROL_IN_ENDPOINT: u8 = 0x80;
+const HID_OUT_ENDPOINT: u8 = 0x02;
+const HID_IN_ENDPOINT: u8 = 0x82;
+const U2F_OUT_ENDPOINT: u8 = 0x01;
+const U2F_IN_ENDPOINT: u8 = 0x81;
+
+const HID_INTERFACE: u8 = 0;
+const U2F_INTERFACE: u8 = 1;
+
+const REQUEST_TYPE_MASK: u8 = 0x60;
+const REQUEST_TYPE_STANDARD: u8 = 0x00;
+const REQUEST_TYPE_CLASS: u8 = 0x20;
+
+const REQUEST_GET_STATUS: u8 = 0x00;
+const REQUEST_CLEAR_FEATURE: u8 = 0x01;
+const REQUEST_SET_FEATURE: u8 = 0x03;
+const REQUEST_SET_ADDRESS: u8 = 0x05;
+const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_GET_CONFIGURATION: u8 = 0x08;
+const REQUEST_SET_CONFIGURATION: u8 = 0x09;
+const REQUEST_GET_INTERFACE: u8 = 0x0A;
+const REQUEST_SET_INTERFACE: u8 = 0x0B;
+
+const HID_REQUEST_GET_IDLE: u8 = 0x02;
+const HID_REQUEST_GET_PROTOCOL: u8 = 0x03;
+const HID_REQUEST_SET_IDLE: u8 = 0x0A;
+const HID_REQUEST_SET_PROTOCOL: u8 = 0x0B;
+
+const DESCRIPTOR_DEVICE: u8 = 0x01;
+const DESCRIPTOR_CONFIGURATION: u8 = 0x02;
+const DESCRIPTOR_STRING: u8 = 0x03;
+const DESCRIPTOR_HID: u8 = 0x21;
+const DESCRIPTOR_HID_REPORT: u8 = 0x22;
+
+static DEVICE_DESCRIPTOR: [u8; 18] = [
+    0x12, // bLength
+    DESCRIPTOR_DEVICE, // bDescriptorType
+    0x00, 0x02, // bcdUSB
+    0x00, // bDeviceClass
+    0x00, // bDeviceSubClass
+    0x00, // bDeviceProtocol
+    CONTROL_MAX_PACKET_SIZE as u8, // bMaxPacketSize
+    0x97, 0x2C, // idVendor
+    0x01, 0x00, // idProduct
+    0x01, 0x02, // bcdDevice
+    0x01, // iManufacturer
+    0x02, // iProduct
+    0x03, // iSerialNumber
+    0x01, // bNumConfigurations
+];
+
+const HID_DESCRIPTOR_OFFSET: usize = 18;
+const U2F_HID_DESCRIPTOR_OFFSET: usize = 50;
+const HID_DESCRIPTOR_SIZE: usize = 9;
+
+static CONFIGURATION_DESCRIPTOR: [u8; 73] = [
+    // Configuration
+    0x09, // bLength
+    DESCRIPTOR_CONFIGURATION, // bDescriptorType
+    0x49, 0x00, // wTotalLength
+    0x02, // bNumInterfaces
+    0x01, // bConfigurationValue
+    0x00, // iConfiguration
+    0xC0, // bmAttributes (self powered)
+    0x32, // bMaxPower (100mA)
+
+    // Interface 0 (HID)
+    0x09, // bLength
+    0x04, // bDescriptorType
+    HID_INTERFACE, // bInterfaceNumber
+    0x00, // bAlternateSetting
+    0x02, // bNumEndpoints
+    0x03, // bInterfaceClass (HID)
+    0x00, // bInterfaceSubClass
+    0x00, // bInterfaceProtocol
+    0x00, // iInterface
+
+    // HID
+    0x09, // bLength
+    DESCRIPTOR_HID, // bDescriptorType
+    0x11, 0x01, // bcdHID
+    0x00, // bCountryCode
+    0x01, // bNumDescriptors
+    DESCRIPTOR_HID_REPORT, // bDescriptorType
+    0x22, 0x00, // wItemLength
+
+    // Endpoint IN
+    0x07, // bLength
+    0x05, // bDescriptorType
+    HID_IN_ENDPOINT, // bEndpointAddress
+    0x03, // bmAttributes (interrupt)
+    hid::PACKET_SIZE as u8, 0x00, // wMaxPacketSize
+    0x01, // bInterval
+
+    // Endpoint OUT
+    0x07, // bLength
+    0x05, // bDescriptorType
+    HID_OUT_ENDPOINT, // bEndpointAddress
+    0x03, // bmAttributes (interrupt)
+    hid::PACKET_SIZE as u8, 0x00, // wMaxPacketSize
+    0x01, // bInterval
+
+    // Interface 1 (U2F)
+    0x09, // bLength
+    0x04, // bDescriptorType
+    U2F_INTERFACE, // bInterfaceNumber
+    0x00, // bAlternateSetting
+    0x02, // bNumEndpoints
+    0x03, // bInterfaceClass (HID)
+    0x00, // bInterfaceSubClass
+    0x00, // bInterfaceProtocol
+    0x00, // iInterface
+
+    // HID
+    0x09, // bLength
+    DESCRIPTOR_HID, // bDescriptorType
+    0x11, 0x01, // bcdHID
+    0x00, // bCountryCode
+    0x01, // bNumDescriptors
+    DESCRIPTOR_HID_REPORT, // bDescriptorType
+    0x22, 0x00, // wItemLength
+
+    // Endpoint IN
+    0x07, // bLength
+    0x05, // bDescriptorType
+    U2F_IN_ENDPOINT, // bEndpointAddress
+    0x03, // bmAttributes (interrupt)
+    u2f::PACKET_SIZE as u8, 0x00, // wMaxPacketSize
+    0x01, // bInterval
+
+    // Endpoint OUT
+    0x07, // bLength
+    0x05, // bDescriptorType
+    U2F_OUT_ENDPOINT, // bEndpointAddress
+    0x03, // bmAttributes (interrupt)
+    u2f::PACKET_SIZE as u8, 0x00, // wMaxPacketSize
+    0x01, // bInterval
+];
+
+static HID_REPORT_DESCRIPTOR: [u8; 34] = [
+    0x06, 0xA0, 0xFF, // Usage page (vendor defined)
+    0x09, 0x01, // Usage
+    0xA1, 0x01, // Collection (application)
+
+    0x09, 0x03, // Usage
+    0x15, 0x00, // Logical minimum
+    0x26, 0xFF, 0x00, // Logical maximum
+    0x75, 0x08, // Report size (8 bits)
+    0x95, 0x40, // Report count (64 fields)
+    0x81, 0x08, // Input (data, array, absolute, wrap)
+
+    0x09, 0x04, // Usage
+    0x15, 0x00, // Logical minimum
+    0x26, 0xFF, 0x00, // Logical maximum
+    0x75, 0x08, // Report size (8 bits)
+    0x95, 0x40, // Report count (64 fields)
+    0x91, 0x08, // Output (data, array, absolute, wrap)
+
+    0xC0, // End collection
+];
+
+static U2F_REPORT_DESCRIPTOR: [u8; 34] = [
+    0x06, 0xD0, 0xF1, // Usage page (FIDO alliance)
+    0x09, 0x01, // Usage (U2F authenticator device)
+    0xA1, 0x01, // Collection (application)
+
+    0x09, 0x20, // Usage (input report data)
+    0x15, 0x00, // Logical minimum
+    0x26, 0xFF, 0x00, // Logical maximum
+    0x75, 0x08, // Report size (8 bits)
+    0x95, 0x40, // Report count (64 fields)
+    0x81, 0x02, // Input (data, variable, absolute)
+
+    0x09, 0x21, // Usage (output report data)
+    0x15, 0x00, // Logical minimum
+    0x26, 0xFF, 0x00, // Logical maximum
+    0x75, 0x08, // Report size (8 bits)
+    0x95, 0x40, // Report count (64 fields)
+    0x91, 0x02, // Output (data, variable, absolute)
+
+    0xC0, // End collection
+];
+
+static STRING_LANGUAGES: [u8; 4] = [
+    0x04, DESCRIPTOR_STRING,
+    0x09, 0x04, // English (US)
+];
+
+static STRING_MANUFACTURER: [u8; 14] = [
+    0x0E, DESCRIPTOR_STRING,
+    b'L', 0, b'e', 0, b'd', 0, b'g', 0, b'e', 0, b'r', 0,
+];
+
+static STRING_PRODUCT: [u8; 14] = [
+    0x0E, DESCRIPTOR_STRING,
+    b'N', 0, b'a', 0, b'n', 0, b'o', 0, b' ', 0, b'S', 0,
+];
+
+static STRING_SERIAL: [u8; 10] = [
+    0x0A, DESCRIPTOR_STRING,
+    b'0', 0, b'0', 0, b'0', 0, b'1', 0,
+];
+
+static DEVICE_STATUS: [u8; 2] = [0, 0];
+static CONFIGURATION_VALUES: [u8; 2] = [0, 1];
+static ZERO: [u8; 1] = [0];
+
+struct SetupRequest {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+}
+
+impl SetupRequest {
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() != 8 {
+            None
+        } else {
+            Some(Self{
+                request_type: raw[0],
+                request: raw[1],
+                value: LittleEndian::read_u16(&raw[2..4]),
+                index: LittleEndian::read_u16(&raw[4..6]),
+                length: LittleEndian::read_u16(&raw[6..8]),
+            })
+        }
+    }
+}
+
+fn descriptor_for(value: u16, index: u16) -> Option<&'static [u8]> {
+    let descriptor_type = (value >> 8) as u8;
+    let descriptor_index = value as u8;
+    // HID class descriptors are addressed by the interface number
+    let interface = index as u8;
+
+    match (descriptor_type, descriptor_index) {
+        (DESCRIPTOR_DEVICE, _) => Some(&DEVICE_DESCRIPTOR),
+        (DESCRIPTOR_CONFIGURATION, _) => Some(&CONFIGURATION_DESCRIPTOR),
+        (DESCRIPTOR_STRING, 0) => Some(&STRING_LANGUAGES),
+        (DESCRIPTOR_STRING, 1) => Some(&STRING_MANUFACTURER),
+        (DESCRIPTOR_STRING, 2) => Some(&STRING_PRODUCT),
+        (DESCRIPTOR_STRING, 3) => Some(&STRING_SERIAL),
+        (DESCRIPTOR_HID, _) => {
+            let start = if interface == U2F_INTERFACE {
+                U2F_HID_DESCRIPTOR_OFFSET
+            } else {
+                HID_DESCRIPTOR_OFFSET
+            };
+            Some(&CONFIGURATION_DESCRIPTOR[start..start+HID_DESCRIPTOR_SIZE])
+        },
+        (DESCRIPTOR_HID_REPORT, _) if interface == U2F_INTERFACE => Some(&U2F_REPORT_DESCRIPTOR),
+        (DESCRIPTOR_HID_REPORT, _) => Some(&HID_REPORT_DESCRIPTOR),
+        _ => None,
+    }
+}
+
+static DEFAULT_U2F_SCRAMBLE_KEY: [u8; 3] = *b"w0w";
+
+// Interface that the command APDU came from
+#[derive(Clone, Copy)]
+enum PendingCommand {
+    Hid(usize),
+    U2f{ channel: u32, len: usize },
+}
+
+/// USB transport, which exchanges APDUs over Ledger's HID framing and
+/// tunnelled in U2F authenticate requests
+pub struct Transport {
+    started: bool,
+    configuration: u8,
+    control_data: &'static [u8],
+    control_needs_zlp: bool,
+    control_in_stage: bool,
+    framing: hid::Framing,
+    rx_buffer: [u8; APDU_BUFFER_SIZE],
+    tx_buffer: [u8; APDU_BUFFER_SIZE],
+    u2f_framing: u2f::Framing,
+    u2f_buffer: [u8; u2f::MESSAGE_BUFFER_SIZE],
+    u2f_scramble_key: &'static [u8],
+    pending: Option<PendingCommand>,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self{
+            started: false,
+            configuration: 0,
+            control_data: &[],
+            control_needs_zlp: false,
+            control_in_stage: false,
+            framing: hid::Framing::new(),
+            rx_buffer: [0; APDU_BUFFER_SIZE],
+            tx_buffer: [0; APDU_BUFFER_SIZE],
+            u2f_framing: u2f::Framing::new(),
+            u2f_buffer: [0; u2f::MESSAGE_BUFFER_SIZE],
+            u2f_scramble_key: &DEFAULT_U2F_SCRAMBLE_KEY,
+            pending: None,
+        }
+    }
+
+    /// Key that the APDUs tunnelled in U2F authenticate requests are
+    /// XOR-ed with, the host side has to use the same key
+    pub fn set_u2f_scramble_key(&mut self, key: &'static [u8]) {
+        self.u2f_scramble_key = key;
+    }
+
+    fn reset(&mut self, ch: &mut Channel) {
+        self.configuration = 0;
+        self.control_data = &[];
+        self.control_needs_zlp = false;
+        self.control_in_stage = false;
+        self.framing.reset();
+        self.u2f_framing.reset();
+        self.pending = None;
+
+        ch.send_command(UsbEndpointCommand{
+            endpoint: CONTROL_OUT_ENDPOINT,
+            endpoint_type: UsbEndpointType::Control,
+            max_packet_size: CONTROL_MAX_PACKET_SIZE as u8,
+        }.into());
+        ch.send_command(UsbEndpointCommand{
+            endpoint: CONTROL_IN_ENDPOINT,
+            endpoint_type: UsbEndpointType::Control,
+            max_packet_size: CONTROL_MAX_PACKET_SIZE as u8,
+        }.into());
+    }
+
+    fn configure(&mut self, ch: &mut Channel, configuration: u8) {
+        self.configuration = configuration;
+
+        let endpoint_type = if configuration == 0 {
+            UsbEndpointType::Disabled
+        } else {
+            UsbEndpointType::Interrupt
+        };
+        ch.send_command(UsbEndpointCommand{
+            endpoint: HID_IN_ENDPOINT,
+            endpoint_type,
+            max_packet_size: hid::PACKET_SIZE as u8,
+        }.into());
+        ch.send_command(UsbEndpointCommand{
+            endpoint: HID_OUT_ENDPOINT,
+            endpoint_type,
+            max_packet_size: hid::PACKET_SIZE as u8,
+        }.into());
+        ch.send_command(UsbEndpointCommand{
+            endpoint: U2F_IN_ENDPOINT,
+            endpoint_type,
+            max_packet_size: u2f::PACKET_SIZE as u8,
+        }.into());
+        ch.send_command(UsbEndpointCommand{
+            endpoint: U2F_OUT_ENDPOINT,
+            endpoint_type,
+            max_packet_size: u2f::PACKET_SIZE as u8,
+        }.into());
+
+        if configuration != 0 {
+            self.framing.reset();
+            self.u2f_framing.reset();
+            self.pending = None;
+            self.prepare_hid_receive(ch);
+            self.prepare_u2f_receive(ch);
+        }
+    }
+
+    fn stall_control(&mut self, ch: &mut Channel) {
+        self.control_in_stage = false;
+        for endpoint in [CONTROL_IN_ENDPOINT, CONTROL_OUT_ENDPOINT].iter() {
+            ch.send_command(UsbEndpointPrepareCommand{
+                endpoint: *endpoint,
+                direction: UsbEndpointPrepareDirection::Stall,
+                length: 0,
+                data: &[],
+            }.into());
+        }
+    }
+
+    fn send_control_status(&mut self, ch: &mut Channel) {
+        self.control_in_stage = false;
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: CONTROL_IN_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::In,
+            length: 0,
+            data: &[],
+        }.into());
+    }
+
+    fn send_control_data(&mut self, ch: &mut Channel, data: &'static [u8], max_length: u16) {
+        let len = min(data.len(), max_length as usize);
+        self.control_data = &data[0..len];
+        // Host expects a zero length packet when the data ends on a packet
+        // boundary and is shorter than what was requested
+        self.control_needs_zlp = len < max_length as usize
+            && len % CONTROL_MAX_PACKET_SIZE == 0;
+        self.control_in_stage = true;
+        self.send_next_control_chunk(ch);
+    }
+
+    fn send_next_control_chunk(&mut self, ch: &mut Channel) {
+        let data = self.control_data;
+        let cnt = min(data.len(), CONTROL_MAX_PACKET_SIZE);
+        self.control_data = &data[cnt..];
+
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: CONTROL_IN_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::In,
+            length: cnt as u8,
+            data: &data[0..cnt],
+        }.into());
+    }
+
+    fn process_setup(&mut self, ch: &mut Channel, req: SetupRequest) {
+        match (req.request_type & REQUEST_TYPE_MASK, req.request) {
+            (REQUEST_TYPE_STANDARD, REQUEST_GET_STATUS) => {
+                self.send_control_data(ch, &DEVICE_STATUS, req.length);
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_SET_ADDRESS) => {
+                ch.send_command(UsbAddressCommand{
+                    address: req.value as u8,
+                }.into());
+                self.send_control_status(ch);
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_GET_DESCRIPTOR) => {
+                match descriptor_for(req.value, req.index) {
+                    Some(descriptor) => self.send_control_data(ch, descriptor, req.length),
+                    None => self.stall_control(ch),
+                }
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_GET_CONFIGURATION) => {
+                let idx = min(self.configuration as usize, 1);
+                self.send_control_data(ch, &CONFIGURATION_VALUES[idx..idx+1], req.length);
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_SET_CONFIGURATION) => {
+                if req.value > 1 {
+                    self.stall_control(ch);
+                } else {
+                    self.configure(ch, req.value as u8);
+                    self.send_control_status(ch);
+                }
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_GET_INTERFACE) => {
+                self.send_control_data(ch, &ZERO, req.length);
+            },
+            (REQUEST_TYPE_STANDARD, REQUEST_SET_INTERFACE) |
+            (REQUEST_TYPE_STANDARD, REQUEST_CLEAR_FEATURE) |
+            (REQUEST_TYPE_STANDARD, REQUEST_SET_FEATURE) => {
+                self.send_control_status(ch);
+            },
+            (REQUEST_TYPE_CLASS, HID_REQUEST_GET_IDLE) |
+            (REQUEST_TYPE_CLASS, HID_REQUEST_GET_PROTOCOL) => {
+                self.send_control_data(ch, &ZERO, req.length);
+            },
+            (REQUEST_TYPE_CLASS, HID_REQUEST_SET_IDLE) |
+            (REQUEST_TYPE_CLASS, HID_REQUEST_SET_PROTOCOL) => {
+                self.send_control_status(ch);
+            },
+            _ => self.stall_control(ch),
+        }
+    }
+
+    fn process_control_in(&mut self, ch: &mut Channel) {
+        if !self.control_in_stage {
+            // Status stage was acknowledged by the host
+            return;
+        }
+
+        if self.control_data.len() > 0 {
+            self.send_next_control_chunk(ch);
+        } else if self.control_needs_zlp {
+            self.control_needs_zlp = false;
+            self.send_next_control_chunk(ch);
+        } else {
+            // Data stage is done, wait for the host to acknowledge it
+            self.control_in_stage = false;
+            ch.send_command(UsbEndpointPrepareCommand{
+                endpoint: CONTROL_OUT_ENDPOINT,
+                direction: UsbEndpointPrepareDirection::Out,
+                length: 0,
+                data: &[],
+            }.into());
+        }
+    }
+
+    fn prepare_hid_receive(&mut self, ch: &mut Channel) {
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: HID_OUT_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::Out,
+            length: hid::PACKET_SIZE as u8,
+            data: &[],
+        }.into());
+    }
+
+    fn send_hid_packet(&mut self, ch: &mut Channel, packet: &[u8]) {
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: HID_IN_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::In,
+            length: packet.len() as u8,
+            data: packet,
+        }.into());
+    }
+
+    fn send_next_hid_packet(&mut self, ch: &mut Channel) {
+        let mut packet = [0; hid::PACKET_SIZE];
+        if self.framing.next_packet(&self.tx_buffer, &mut packet) {
+            self.send_hid_packet(ch, &packet);
+        }
+    }
+
+    fn process_hid_out(&mut self, ch: &mut Channel, packet: &[u8]) {
+        // Ignore new requests until the previous one has been responded to
+        if !self.framing.is_sending() && self.pending.is_none() {
+            match self.framing.receive(packet, &mut self.rx_buffer) {
+                hid::Received::Apdu(len) => {
+                    self.pending = Some(PendingCommand::Hid(len));
+                },
+                hid::Received::Ping => {
+                    self.send_hid_packet(ch, packet);
+                },
+                hid::Received::Partial |
+                hid::Received::Invalid => {},
+            }
+        }
+
+        self.prepare_hid_receive(ch);
+    }
+
+    fn prepare_u2f_receive(&mut self, ch: &mut Channel) {
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: U2F_OUT_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::Out,
+            length: u2f::PACKET_SIZE as u8,
+            data: &[],
+        }.into());
+    }
+
+    fn send_u2f_packet(&mut self, ch: &mut Channel, packet: &[u8]) {
+        ch.send_command(UsbEndpointPrepareCommand{
+            endpoint: U2F_IN_ENDPOINT,
+            direction: UsbEndpointPrepareDirection::In,
+            length: packet.len() as u8,
+            data: packet,
+        }.into());
+    }
+
+    fn send_next_u2f_packet(&mut self, ch: &mut Channel) {
+        let mut packet = [0; u2f::PACKET_SIZE];
+        if self.u2f_framing.next_packet(&self.u2f_buffer, &mut packet) {
+            self.send_u2f_packet(ch, &packet);
+        }
+    }
+
+    fn send_u2f_error(&mut self, ch: &mut Channel, channel: u32, code: u2f::ErrorCode) {
+        let mut packet = [0; u2f::PACKET_SIZE];
+        u2f::make_error_packet(channel, code, &mut packet);
+        self.send_u2f_packet(ch, &packet);
+    }
+
+    fn process_u2f_message(&mut self, ch: &mut Channel, channel: u32, command: u8, len: usize) {
+        let request = u2f::process_message(
+            &mut self.u2f_framing,
+            channel,
+            command,
+            &mut self.u2f_buffer,
+            len,
+            self.u2f_scramble_key.pic(),
+        );
+
+        match request {
+            u2f::Request::Tunnelled(_) if self.pending.is_some() => {
+                self.send_u2f_error(ch, channel, u2f::ErrorCode::ChannelBusy);
+            },
+            u2f::Request::Tunnelled(len) => {
+                self.pending = Some(PendingCommand::U2f{ channel, len });
+            },
+            u2f::Request::Reply(command, len) => {
+                self.u2f_framing.start_sending(channel, command as u8, len);
+                self.send_next_u2f_packet(ch);
+            },
+            u2f::Request::Error(code) => {
+                self.send_u2f_error(ch, channel, code);
+            },
+        }
+    }
+
+    fn is_u2f_busy(&self) -> bool {
+        match self.pending {
+            Some(PendingCommand::U2f{ .. }) => true,
+            _ => self.u2f_framing.is_sending(),
+        }
+    }
+
+    fn process_u2f_out(&mut self, ch: &mut Channel, packet: &[u8]) {
+        // The message buffer is in use until the response has been sent,
+        // the host is kept waiting with keep-alive messages meanwhile
+        let received = if self.is_u2f_busy() {
+            self.u2f_framing.receive_while_busy(packet)
+        } else {
+            self.u2f_framing.receive(packet, &mut self.u2f_buffer)
+        };
+
+        match received {
+            u2f::Received::Message{ channel, command, len } => {
+                self.process_u2f_message(ch, channel, command, len);
+            },
+            u2f::Received::Error{ channel, code } => {
+                self.send_u2f_error(ch, channel, code);
+            },
+            u2f::Received::Partial |
+            u2f::Received::Ignored => {},
+        }
+
+        self.prepare_u2f_receive(ch);
+    }
+
+    fn process_ticker(&mut self, ch: &mut Channel) {
+        // Let the host know that the command is still being processed
+        if let Some(PendingCommand::U2f{ channel, .. }) = self.pending {
+            let mut packet = [0; u2f::PACKET_SIZE];
+            u2f::make_keep_alive_packet(channel, &mut packet);
+            self.send_u2f_packet(ch, &packet);
+        }
+    }
+
+    fn process_transfer(&mut self, ch: &mut Channel, ev: UsbEndpointTransferEvent) {
+        let endpoint = ev.endpoint & 0x7F;
+
+        match (endpoint, ev.transfer_type) {
+            (0, UsbTransferType::Setup) => {
+                match SetupRequest::from_bytes(ev.data()) {
+                    Some(req) => self.process_setup(ch, req),
+                    None => self.stall_control(ch),
+                }
+            },
+            (0, UsbTransferType::In) => {
+                self.process_control_in(ch);
+            },
+            (0, UsbTransferType::Out) => {},
+            (n, UsbTransferType::Out) if n == HID_OUT_ENDPOINT & 0x7F => {
+                self.process_hid_out(ch, ev.data());
+            },
+            (n, UsbTransferType::In) if n == HID_IN_ENDPOINT & 0x7F => {
+                self.send_next_hid_packet(ch);
+            },
+            (n, UsbTransferType::Out) if n == U2F_OUT_ENDPOINT & 0x7F => {
+                self.process_u2f_out(ch, ev.data());
+            },
+            (n, UsbTransferType::In) if n == U2F_IN_ENDPOINT & 0x7F => {
+                self.send_next_u2f_packet(ch);
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Io for Transport {
+    fn process_event(&mut self, ch: &mut Channel) -> bool {
+        if !self.started {
+            // Make the host see a freshly plugged in device
+            self.started = true;
+            ch.send_command(UsbDisconnectCommand{}.into());
+            ch.send_command(UsbConnectCommand{}.into());
+        }
+
+        match ch.event {
+            Event::Usb(UsbEvent{ event_type: UsbEventType::Reset }) => {
+                self.reset(ch);
+                true
+            },
+            Event::Usb(_) => true,
+            Event::UsbEndpointTransfer(ev) => {
+                self.process_transfer(ch, ev);
+                true
+            },
+            Event::Ticker(_) => {
+                self.process_ticker(ch);
+                false
+            },
+            _ => false,
+        }
+    }
+
+    fn recv_apdu(&self) -> Option<&[u8]> {
+        match self.pending {
+            Some(PendingCommand::Hid(len)) => Some(&self.rx_buffer[0..len]),
+            Some(PendingCommand::U2f{ len, .. }) => Some(&self.u2f_buffer[0..len]),
+            None => None,
+        }
+    }
+
+    fn send_rapdu(&mut self, ch: &mut Channel, rapdu: &[u8]) -> Result<(), IoError> {
+        if ch.is_status_sent() {
+            return Err(IoError::StatusSent);
+        }
+
+        match self.pending {
+            Some(PendingCommand::Hid(_)) => {
+                if rapdu.len() > self.tx_buffer.len() {
+                    return Err(IoError::Overflow);
+                }
+                self.tx_buffer[0..rapdu.len()].copy_from_slice(rapdu);
+                self.framing.start_sending(rapdu.len());
+                self.send_next_hid_packet(ch);
+            },
+            Some(PendingCommand::U2f{ channel, .. }) => {
+                // Response gets a status word of its own appended
+                if rapdu.len() + 2 > self.u2f_buffer.len() {
+                    return Err(IoError::Overflow);
+                }
+                let len = u2f::wrap_response(&mut self.u2f_buffer, rapdu);
+                self.u2f_framing.start_sending(channel, u2f::Command::Message as u8, len);
+                self.send_next_u2f_packet(ch);
+            },
+            None => return Err(IoError::NoCommand),
+        }
+
+        self.pending = None;
+        Ok(())
+    }
+}
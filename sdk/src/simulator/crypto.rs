@@ -0,0 +1,787 @@
+//! Software versions of the firmware hash functions, so that the apps get
+//! real digests in the simulator. Same as on the device, the state of the
+//! hash lives in the context that the app passes to the syscalls.
+
+use core::{ptr, slice};
+use core::cmp::min;
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+use error::SystemError;
+
+// Algorithm ids of the firmware, stored at the start of the context
+const CX_RIPEMD160: u32 = 1;
+const CX_SHA256: u32 = 3;
+const CX_SHA512: u32 = 5;
+const CX_KECCAK: u32 = 6;
+const CX_BLAKE2B: u32 = 9;
+
+const CX_LAST: usize = 1 << 0;
+
+// Contexts start with the algorithm id, followed by the state of the hash
+const STATE_OFFSET: usize = 4;
+
+trait Digest: Copy {
+    fn update(&mut self, data: &[u8]);
+    fn digest_size(&self) -> usize;
+    /// Writes `digest_size` bytes of the digest to `out`
+    fn finish(self, out: &mut [u8]);
+}
+
+// Hashes that HMAC can be built on
+trait BlockDigest: Digest {
+    const BLOCK_SIZE: usize;
+    fn new() -> Self;
+}
+
+unsafe fn init<D: Digest>(ctx: usize, algorithm: u32, digest: D) {
+    ptr::write_unaligned(ctx as *mut u32, algorithm);
+    ptr::write_unaligned((ctx + STATE_OFFSET) as *mut D, digest);
+}
+
+unsafe fn algorithm(ctx: usize) -> u32 {
+    ptr::read_unaligned(ctx as *const u32)
+}
+
+// Feeds the data to the hash in the context, the digest is written to
+// `out` when the data is the last part of the message
+unsafe fn process<D: Digest>(ctx: usize, mode: usize, data: &[u8], out: usize) -> Result<u32, SystemError> {
+    let mut digest = ptr::read_unaligned((ctx + STATE_OFFSET) as *const D);
+    digest.update(data);
+    ptr::write_unaligned((ctx + STATE_OFFSET) as *mut D, digest);
+
+    if mode & CX_LAST == 0 {
+        return Ok(0);
+    }
+    let size = digest.digest_size();
+    if out != 0 {
+        digest.finish(slice::from_raw_parts_mut(out as *mut u8, size));
+    }
+    Ok(size as u32)
+}
+
+pub(super) fn sha256_init(params: &[usize]) -> Result<u32, SystemError> {
+    unsafe { init(params[0], CX_SHA256, Sha256::new()) };
+    Ok(0)
+}
+
+pub(super) fn sha512_init(params: &[usize]) -> Result<u32, SystemError> {
+    unsafe { init(params[0], CX_SHA512, Sha512::new()) };
+    Ok(0)
+}
+
+pub(super) fn ripemd160_init(params: &[usize]) -> Result<u32, SystemError> {
+    unsafe { init(params[0], CX_RIPEMD160, Ripemd160::new()) };
+    Ok(0)
+}
+
+pub(super) fn keccak_init(params: &[usize]) -> Result<u32, SystemError> {
+    let digest = Keccak::new(params[1] / 8).ok_or(SystemError::InvalidParameter)?;
+    unsafe { init(params[0], CX_KECCAK, digest) };
+    Ok(0)
+}
+
+pub(super) fn blake2b_init(params: &[usize]) -> Result<u32, SystemError> {
+    let digest = Blake2b::new(params[1] / 8).ok_or(SystemError::InvalidParameter)?;
+    unsafe { init(params[0], CX_BLAKE2B, digest) };
+    Ok(0)
+}
+
+pub(super) fn hash(params: &[usize]) -> Result<u32, SystemError> {
+    let (ctx, mode, out) = (params[0], params[1], params[4]);
+    unsafe {
+        let data = slice::from_raw_parts(params[2] as *const u8, params[3]);
+        match algorithm(ctx) {
+            CX_SHA256 => process::<Sha256>(ctx, mode, data, out),
+            CX_SHA512 => process::<Sha512>(ctx, mode, data, out),
+            CX_RIPEMD160 => process::<Ripemd160>(ctx, mode, data, out),
+            CX_KECCAK => process::<Keccak>(ctx, mode, data, out),
+            CX_BLAKE2B => process::<Blake2b>(ctx, mode, data, out),
+            _ => Err(SystemError::InvalidParameter),
+        }
+    }
+}
+
+pub(super) fn hmac_sha256_init(params: &[usize]) -> Result<u32, SystemError> {
+    unsafe {
+        let key = slice::from_raw_parts(params[1] as *const u8, params[2]);
+        init(params[0], CX_SHA256, Hmac::<Sha256>::new(key));
+    }
+    Ok(0)
+}
+
+pub(super) fn hmac_sha512_init(params: &[usize]) -> Result<u32, SystemError> {
+    unsafe {
+        let key = slice::from_raw_parts(params[1] as *const u8, params[2]);
+        init(params[0], CX_SHA512, Hmac::<Sha512>::new(key));
+    }
+    Ok(0)
+}
+
+pub(super) fn hmac(params: &[usize]) -> Result<u32, SystemError> {
+    let (ctx, mode, out) = (params[0], params[1], params[4]);
+    unsafe {
+        let data = slice::from_raw_parts(params[2] as *const u8, params[3]);
+        match algorithm(ctx) {
+            CX_SHA256 => process::<Hmac<Sha256>>(ctx, mode, data, out),
+            CX_SHA512 => process::<Hmac<Sha512>>(ctx, mode, data, out),
+            _ => Err(SystemError::InvalidParameter),
+        }
+    }
+}
+
+// The states only use 32-bit counters, the contexts of the firmware
+// don't have room for anything larger
+#[derive(Clone, Copy)]
+struct BlockBuffer<B> {
+    buf: B,
+    len: u32,
+    blocks: u32,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> BlockBuffer<B> {
+    /// Appends the data to the buffer, `compress` is called for every
+    /// block that fills up
+    fn update<F>(&mut self, mut data: &[u8], mut compress: F)
+        where F: FnMut(&[u8])
+    {
+        while data.len() > 0 {
+            let start = self.len as usize;
+            let buf = self.buf.as_mut();
+            let cnt = min(buf.len() - start, data.len());
+            buf[start..start + cnt].copy_from_slice(&data[0..cnt]);
+            self.len += cnt as u32;
+            data = &data[cnt..];
+
+            if self.len as usize == buf.len() {
+                compress(buf);
+                self.len = 0;
+                self.blocks = self.blocks.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Length of the message so far in bits
+    fn bit_len(&self) -> u64 {
+        (self.blocks as u64 * self.buf.as_ref().len() as u64 + self.len as u64) * 8
+    }
+}
+
+static SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[derive(Clone, Copy)]
+struct Sha256 {
+    h: [u32; 8],
+    buf: BlockBuffer<[u8; 64]>,
+}
+
+impl Sha256 {
+    fn compress(h: &mut [u32; 8], block: &[u8]) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = BigEndian::read_u32(&block[i * 4..]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut v = *h;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+        }
+        for i in 0..8 {
+            h[i] = h[i].wrapping_add(v[i]);
+        }
+    }
+}
+
+impl Digest for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.buf.update(data, |block| Sha256::compress(h, block));
+    }
+
+    fn digest_size(&self) -> usize {
+        32
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        let mut trailer = [0; 72];
+        trailer[0] = 0x80;
+        let buf_len = self.buf.len as usize;
+        let pad_len = if buf_len < 56 { 56 - buf_len } else { 120 - buf_len };
+        BigEndian::write_u64(&mut trailer[pad_len..], self.buf.bit_len());
+        self.update(&trailer[0..pad_len + 8]);
+
+        for (i, word) in self.h.iter().enumerate() {
+            BigEndian::write_u32(&mut out[i * 4..], *word);
+        }
+    }
+}
+
+impl BlockDigest for Sha256 {
+    const BLOCK_SIZE: usize = 64;
+
+    fn new() -> Self {
+        Self{
+            h: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+            ],
+            buf: BlockBuffer{ buf: [0; 64], len: 0, blocks: 0 },
+        }
+    }
+}
+
+static SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[derive(Clone, Copy)]
+struct Sha512 {
+    h: [u64; 8],
+    buf: BlockBuffer<[u8; 128]>,
+}
+
+impl Sha512 {
+    fn compress(h: &mut [u64; 8], block: &[u8]) {
+        let mut w = [0u64; 80];
+        for i in 0..16 {
+            w[i] = BigEndian::read_u64(&block[i * 8..]);
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut v = *h;
+        for i in 0..80 {
+            let s1 = v[4].rotate_right(14) ^ v[4].rotate_right(18) ^ v[4].rotate_right(41);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA512_K[i]).wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(28) ^ v[0].rotate_right(34) ^ v[0].rotate_right(39);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+        }
+        for i in 0..8 {
+            h[i] = h[i].wrapping_add(v[i]);
+        }
+    }
+}
+
+impl Digest for Sha512 {
+    fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.buf.update(data, |block| Sha512::compress(h, block));
+    }
+
+    fn digest_size(&self) -> usize {
+        64
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        // The length takes 128 bits, of which only the lower half is used
+        let mut trailer = [0; 144];
+        trailer[0] = 0x80;
+        let buf_len = self.buf.len as usize;
+        let pad_len = if buf_len < 112 { 112 - buf_len } else { 240 - buf_len };
+        BigEndian::write_u64(&mut trailer[pad_len + 8..], self.buf.bit_len());
+        self.update(&trailer[0..pad_len + 16]);
+
+        for (i, word) in self.h.iter().enumerate() {
+            BigEndian::write_u64(&mut out[i * 8..], *word);
+        }
+    }
+}
+
+impl BlockDigest for Sha512 {
+    const BLOCK_SIZE: usize = 128;
+
+    fn new() -> Self {
+        Self{
+            h: [
+                0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+                0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+            ],
+            buf: BlockBuffer{ buf: [0; 128], len: 0, blocks: 0 },
+        }
+    }
+}
+
+// Message word order, rotations and constants of the left and right lines
+static RIPEMD160_R: [usize; 80] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8,
+    3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12,
+    1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2,
+    4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+];
+static RIPEMD160_RP: [usize; 80] = [
+    5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12,
+    6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2,
+    15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13,
+    8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14,
+    12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+];
+static RIPEMD160_S: [u32; 80] = [
+    11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8,
+    7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12,
+    11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5,
+    11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12,
+    9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+];
+static RIPEMD160_SP: [u32; 80] = [
+    8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6,
+    9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11,
+    9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5,
+    15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8,
+    8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+];
+static RIPEMD160_K: [u32; 5] = [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e];
+static RIPEMD160_KP: [u32; 5] = [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000];
+
+#[derive(Clone, Copy)]
+struct Ripemd160 {
+    h: [u32; 5],
+    buf: BlockBuffer<[u8; 64]>,
+}
+
+impl Ripemd160 {
+    fn new() -> Self {
+        Self{
+            h: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            buf: BlockBuffer{ buf: [0; 64], len: 0, blocks: 0 },
+        }
+    }
+
+    fn f(round: usize, x: u32, y: u32, z: u32) -> u32 {
+        match round {
+            0 => x ^ y ^ z,
+            1 => (x & y) | (!x & z),
+            2 => (x | !y) ^ z,
+            3 => (x & z) | (y & !z),
+            _ => x ^ (y | !z),
+        }
+    }
+
+    fn compress(h: &mut [u32; 5], block: &[u8]) {
+        let mut x = [0u32; 16];
+        for i in 0..16 {
+            x[i] = LittleEndian::read_u32(&block[i * 4..]);
+        }
+
+        let (mut al, mut bl, mut cl, mut dl, mut el) = (h[0], h[1], h[2], h[3], h[4]);
+        let (mut ar, mut br, mut cr, mut dr, mut er) = (h[0], h[1], h[2], h[3], h[4]);
+        for j in 0..80 {
+            let round = j / 16;
+
+            let t = al.wrapping_add(Ripemd160::f(round, bl, cl, dl))
+                .wrapping_add(x[RIPEMD160_R[j]])
+                .wrapping_add(RIPEMD160_K[round])
+                .rotate_left(RIPEMD160_S[j])
+                .wrapping_add(el);
+            al = el;
+            el = dl;
+            dl = cl.rotate_left(10);
+            cl = bl;
+            bl = t;
+
+            let t = ar.wrapping_add(Ripemd160::f(4 - round, br, cr, dr))
+                .wrapping_add(x[RIPEMD160_RP[j]])
+                .wrapping_add(RIPEMD160_KP[round])
+                .rotate_left(RIPEMD160_SP[j])
+                .wrapping_add(er);
+            ar = er;
+            er = dr;
+            dr = cr.rotate_left(10);
+            cr = br;
+            br = t;
+        }
+
+        let t = h[1].wrapping_add(cl).wrapping_add(dr);
+        h[1] = h[2].wrapping_add(dl).wrapping_add(er);
+        h[2] = h[3].wrapping_add(el).wrapping_add(ar);
+        h[3] = h[4].wrapping_add(al).wrapping_add(br);
+        h[4] = h[0].wrapping_add(bl).wrapping_add(cr);
+        h[0] = t;
+    }
+}
+
+impl Digest for Ripemd160 {
+    fn update(&mut self, data: &[u8]) {
+        let h = &mut self.h;
+        self.buf.update(data, |block| Ripemd160::compress(h, block));
+    }
+
+    fn digest_size(&self) -> usize {
+        20
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        let mut trailer = [0; 72];
+        trailer[0] = 0x80;
+        let buf_len = self.buf.len as usize;
+        let pad_len = if buf_len < 56 { 56 - buf_len } else { 120 - buf_len };
+        LittleEndian::write_u64(&mut trailer[pad_len..], self.buf.bit_len());
+        self.update(&trailer[0..pad_len + 8]);
+
+        for (i, word) in self.h.iter().enumerate() {
+            LittleEndian::write_u32(&mut out[i * 4..], *word);
+        }
+    }
+}
+
+static KECCAK_RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+// Rotations and destination lanes of the combined rho and pi steps
+static KECCAK_ROTC: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+static KECCAK_PILN: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+// Original Keccak padding, which Ethereum uses, instead of the SHA-3 one
+const KECCAK_PADDING: u8 = 0x01;
+
+#[derive(Clone, Copy)]
+struct Keccak {
+    state: [u64; 25],
+    rate: u32,
+    pos: u32,
+    digest_size: u32,
+}
+
+impl Keccak {
+    fn new(digest_size: usize) -> Option<Self> {
+        match digest_size {
+            28 | 32 | 48 | 64 => Some(Self{
+                state: [0; 25],
+                rate: 200 - 2 * digest_size as u32,
+                pos: 0,
+                digest_size: digest_size as u32,
+            }),
+            _ => None,
+        }
+    }
+
+    fn permute(a: &mut [u64; 25]) {
+        for rc in KECCAK_RC.iter() {
+            let mut bc = [0u64; 5];
+            for i in 0..5 {
+                bc[i] = a[i] ^ a[i + 5] ^ a[i + 10] ^ a[i + 15] ^ a[i + 20];
+            }
+            for i in 0..5 {
+                let t = bc[(i + 4) % 5] ^ bc[(i + 1) % 5].rotate_left(1);
+                for j in 0..5 {
+                    a[j * 5 + i] ^= t;
+                }
+            }
+
+            let mut t = a[1];
+            for i in 0..24 {
+                let j = KECCAK_PILN[i];
+                let next = a[j];
+                a[j] = t.rotate_left(KECCAK_ROTC[i]);
+                t = next;
+            }
+
+            for j in 0..5 {
+                let row = [a[j * 5], a[j * 5 + 1], a[j * 5 + 2], a[j * 5 + 3], a[j * 5 + 4]];
+                for i in 0..5 {
+                    a[j * 5 + i] ^= !row[(i + 1) % 5] & row[(i + 2) % 5];
+                }
+            }
+
+            a[0] ^= *rc;
+        }
+    }
+
+    fn xor_byte(&mut self, pos: u32, b: u8) {
+        self.state[pos as usize / 8] ^= (b as u64) << (8 * (pos % 8));
+    }
+}
+
+impl Digest for Keccak {
+    fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            let pos = self.pos;
+            self.xor_byte(pos, b);
+            self.pos += 1;
+            if self.pos == self.rate {
+                Keccak::permute(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    fn digest_size(&self) -> usize {
+        self.digest_size as usize
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        let (pos, last) = (self.pos, self.rate - 1);
+        self.xor_byte(pos, KECCAK_PADDING);
+        self.xor_byte(last, 0x80);
+        Keccak::permute(&mut self.state);
+
+        for i in 0..self.digest_size() {
+            out[i] = (self.state[i / 8] >> (8 * (i % 8))) as u8;
+        }
+    }
+}
+
+static BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+#[derive(Clone, Copy)]
+struct Blake2b {
+    h: [u64; 8],
+    buf: [u8; 128],
+    buf_len: u32,
+    digest_size: u32,
+    len: u64,
+}
+
+impl Blake2b {
+    fn new(digest_size: usize) -> Option<Self> {
+        if digest_size == 0 || digest_size > 64 {
+            return None;
+        }
+
+        // Parameter block without a key, salt or personalization, the
+        // initial values are shared with SHA-512
+        let mut h = Sha512::new().h;
+        h[0] ^= 0x0101_0000 ^ digest_size as u64;
+        Some(Self{
+            h,
+            buf: [0; 128],
+            buf_len: 0,
+            digest_size: digest_size as u32,
+            len: 0,
+        })
+    }
+
+    fn g(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+        v[d] = (v[d] ^ v[a]).rotate_right(32);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(24);
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+        v[d] = (v[d] ^ v[a]).rotate_right(16);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(63);
+    }
+
+    fn compress(h: &mut [u64; 8], block: &[u8], len: u64, is_last: bool) {
+        let mut m = [0u64; 16];
+        for i in 0..16 {
+            m[i] = LittleEndian::read_u64(&block[i * 8..]);
+        }
+
+        let mut v = [0u64; 16];
+        v[0..8].copy_from_slice(h);
+        v[8..16].copy_from_slice(&Sha512::new().h);
+        v[12] ^= len;
+        if is_last {
+            v[14] = !v[14];
+        }
+
+        for round in 0..12 {
+            let s = &BLAKE2B_SIGMA[round % 10];
+            Blake2b::g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            Blake2b::g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            Blake2b::g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            Blake2b::g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            Blake2b::g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            Blake2b::g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            Blake2b::g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            Blake2b::g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+        for i in 0..8 {
+            h[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+impl Digest for Blake2b {
+    fn update(&mut self, mut data: &[u8]) {
+        // The last block is compressed differently, so a full buffer is
+        // only compressed once more data arrives
+        while data.len() > 0 {
+            if self.buf_len as usize == self.buf.len() {
+                self.len += self.buf_len as u64;
+                Blake2b::compress(&mut self.h, &self.buf, self.len, false);
+                self.buf_len = 0;
+            }
+            let start = self.buf_len as usize;
+            let cnt = min(self.buf.len() - start, data.len());
+            self.buf[start..start + cnt].copy_from_slice(&data[0..cnt]);
+            self.buf_len += cnt as u32;
+            data = &data[cnt..];
+        }
+    }
+
+    fn digest_size(&self) -> usize {
+        self.digest_size as usize
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        self.len += self.buf_len as u64;
+        for b in self.buf[self.buf_len as usize..].iter_mut() {
+            *b = 0;
+        }
+        Blake2b::compress(&mut self.h, &self.buf, self.len, true);
+
+        let mut digest = [0; 64];
+        for (i, word) in self.h.iter().enumerate() {
+            LittleEndian::write_u64(&mut digest[i * 8..], *word);
+        }
+        let size = self.digest_size();
+        out[0..size].copy_from_slice(&digest[0..size]);
+    }
+}
+
+// HMAC with the outer hash already fed with the padded key, so that the
+// key itself doesn't have to be kept around
+#[derive(Clone, Copy)]
+struct Hmac<D> {
+    inner: D,
+    outer: D,
+}
+
+impl<D: BlockDigest> Hmac<D> {
+    fn new(key: &[u8]) -> Self {
+        let mut block = [0; 128];
+        if key.len() > D::BLOCK_SIZE {
+            let mut h = D::new();
+            h.update(key);
+            let size = h.digest_size();
+            h.finish(&mut block[0..size]);
+        } else {
+            block[0..key.len()].copy_from_slice(key);
+        }
+        let block = &mut block[0..D::BLOCK_SIZE];
+
+        let mut inner = D::new();
+        for b in block.iter_mut() {
+            *b ^= 0x36;
+        }
+        inner.update(block);
+
+        let mut outer = D::new();
+        for b in block.iter_mut() {
+            *b ^= 0x36 ^ 0x5c;
+        }
+        outer.update(block);
+
+        Self{ inner, outer }
+    }
+}
+
+impl<D: BlockDigest> Digest for Hmac<D> {
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    fn digest_size(&self) -> usize {
+        self.inner.digest_size()
+    }
+
+    fn finish(mut self, out: &mut [u8]) {
+        let size = self.digest_size();
+        let mut inner_digest = [0; 64];
+        self.inner.finish(&mut inner_digest[0..size]);
+        self.outer.update(&inner_digest[0..size]);
+        self.outer.finish(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::size_of;
+    use crypto::{hash, hmac};
+    use super::*;
+
+    #[test]
+    fn states_fit_in_the_contexts() {
+        assert!(STATE_OFFSET + size_of::<Sha256>() <= 4 * hash::SHA256_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Sha512>() <= 4 * hash::SHA512_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Ripemd160>() <= 4 * hash::RIPEMD160_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Keccak>() <= 8 * hash::KECCAK_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Blake2b>() <= 8 * hash::BLAKE2B_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Hmac<Sha256>>() <= 4 * hmac::HMAC_SHA256_CONTEXT_SIZE);
+        assert!(STATE_OFFSET + size_of::<Hmac<Sha512>>() <= 4 * hmac::HMAC_SHA512_CONTEXT_SIZE);
+    }
+
+    #[test]
+    fn keccak_permutation_matches_sha3() {
+        // SHA3-256 only differs from the original Keccak by the padding
+        let mut h = Keccak::new(32).unwrap();
+        h.update(b"abc");
+        let (pos, last) = (h.pos, h.rate - 1);
+        h.xor_byte(pos, 0x06);
+        h.xor_byte(last, 0x80);
+        Keccak::permute(&mut h.state);
+
+        let mut digest = [0; 32];
+        for i in 0..32 {
+            digest[i] = (h.state[i / 8] >> (8 * (i % 8))) as u8;
+        }
+        assert_eq!(digest, [
+            0x3a, 0x98, 0x5d, 0xa7, 0x4f, 0xe2, 0x25, 0xb2, 0x04, 0x5c, 0x17, 0x2d, 0x6b, 0xd3, 0x90, 0xbd,
+            0x85, 0x5f, 0x08, 0x6e, 0x3e, 0x9d, 0x52, 0x5b, 0x46, 0xbf, 0xe2, 0x45, 0x11, 0x43, 0x15, 0x32,
+        ]);
+    }
+}
@@ -0,0 +1,120 @@
+/// Size of a single glyph in the simulator's font
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+/// Number of glyph rows above the baseline
+pub const GLYPH_ASCENT: usize = 6;
+
+const FIRST_CHAR: u32 = 0x20;
+const LAST_CHAR: u32 = 0x7E;
+const FALLBACK_CHAR: char = '?';
+
+// Printable ASCII range of the public domain 5x7 misc-fixed font. Each
+// row is stored MSB first, using the top 5 bits of the byte.
+static GLYPHS: [[u8; GLYPH_HEIGHT]; (LAST_CHAR - FIRST_CHAR + 1) as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x20, 0x20, 0x20, 0x20, 0x00, 0x20, 0x00], // '!'
+    [0x50, 0x50, 0x50, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x00, 0x50, 0xF8, 0x50, 0xF8, 0x50, 0x00], // '#'
+    [0x00, 0x70, 0xA0, 0x70, 0x28, 0x70, 0x00], // '$'
+    [0x80, 0x90, 0x20, 0x40, 0x90, 0x10, 0x00], // '%'
+    [0x00, 0x40, 0xA0, 0x40, 0xA0, 0x50, 0x00], // '&'
+    [0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x20, 0x40, 0x40, 0x40, 0x40, 0x20, 0x00], // '('
+    [0x40, 0x20, 0x20, 0x20, 0x20, 0x40, 0x00], // ')'
+    [0x00, 0x50, 0x20, 0x70, 0x20, 0x50, 0x00], // '*'
+    [0x00, 0x20, 0x20, 0xF8, 0x20, 0x20, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x30, 0x20, 0x40], // ','
+    [0x00, 0x00, 0x00, 0xF0, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x60, 0x60, 0x00], // '.'
+    [0x00, 0x10, 0x20, 0x40, 0x80, 0x00, 0x00], // '/'
+    [0x20, 0x50, 0x50, 0x50, 0x50, 0x20, 0x00], // '0'
+    [0x20, 0x60, 0x20, 0x20, 0x20, 0x70, 0x00], // '1'
+    [0x60, 0x90, 0x10, 0x20, 0x40, 0xF0, 0x00], // '2'
+    [0xF0, 0x10, 0x60, 0x10, 0x90, 0x60, 0x00], // '3'
+    [0x20, 0x60, 0xA0, 0xF0, 0x20, 0x20, 0x00], // '4'
+    [0xF0, 0x80, 0xE0, 0x10, 0x90, 0x60, 0x00], // '5'
+    [0x60, 0x80, 0xE0, 0x90, 0x90, 0x60, 0x00], // '6'
+    [0xF0, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00], // '7'
+    [0x60, 0x90, 0x60, 0x90, 0x90, 0x60, 0x00], // '8'
+    [0x60, 0x90, 0x90, 0x70, 0x10, 0x60, 0x00], // '9'
+    [0x00, 0x60, 0x60, 0x00, 0x60, 0x60, 0x00], // ':'
+    [0x00, 0x60, 0x60, 0x00, 0x60, 0x40, 0x80], // ';'
+    [0x00, 0x10, 0x20, 0x40, 0x20, 0x10, 0x00], // '<'
+    [0x00, 0x00, 0xF0, 0x00, 0xF0, 0x00, 0x00], // '='
+    [0x00, 0x40, 0x20, 0x10, 0x20, 0x40, 0x00], // '>'
+    [0x20, 0x50, 0x10, 0x20, 0x00, 0x20, 0x00], // '?'
+    [0x60, 0x90, 0xB0, 0xB0, 0x80, 0x60, 0x00], // '@'
+    [0x60, 0x90, 0x90, 0xF0, 0x90, 0x90, 0x00], // 'A'
+    [0xE0, 0x90, 0xE0, 0x90, 0x90, 0xE0, 0x00], // 'B'
+    [0x60, 0x90, 0x80, 0x80, 0x90, 0x60, 0x00], // 'C'
+    [0xE0, 0x90, 0x90, 0x90, 0x90, 0xE0, 0x00], // 'D'
+    [0xF0, 0x80, 0xE0, 0x80, 0x80, 0xF0, 0x00], // 'E'
+    [0xF0, 0x80, 0xE0, 0x80, 0x80, 0x80, 0x00], // 'F'
+    [0x60, 0x90, 0x80, 0xB0, 0x90, 0x70, 0x00], // 'G'
+    [0x90, 0x90, 0xF0, 0x90, 0x90, 0x90, 0x00], // 'H'
+    [0x70, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'I'
+    [0x10, 0x10, 0x10, 0x10, 0x90, 0x60, 0x00], // 'J'
+    [0x90, 0xA0, 0xC0, 0xC0, 0xA0, 0x90, 0x00], // 'K'
+    [0x80, 0x80, 0x80, 0x80, 0x80, 0xF0, 0x00], // 'L'
+    [0x90, 0xF0, 0xF0, 0x90, 0x90, 0x90, 0x00], // 'M'
+    [0x90, 0xD0, 0xD0, 0xB0, 0xB0, 0x90, 0x00], // 'N'
+    [0x60, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'O'
+    [0xE0, 0x90, 0x90, 0xE0, 0x80, 0x80, 0x00], // 'P'
+    [0x60, 0x90, 0x90, 0x90, 0xD0, 0x60, 0x10], // 'Q'
+    [0xE0, 0x90, 0x90, 0xE0, 0xA0, 0x90, 0x00], // 'R'
+    [0x60, 0x90, 0x40, 0x20, 0x90, 0x60, 0x00], // 'S'
+    [0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // 'T'
+    [0x90, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'U'
+    [0x90, 0x90, 0x90, 0x90, 0x60, 0x60, 0x00], // 'V'
+    [0x90, 0x90, 0x90, 0xF0, 0xF0, 0x90, 0x00], // 'W'
+    [0x90, 0x90, 0x60, 0x60, 0x90, 0x90, 0x00], // 'X'
+    [0x50, 0x50, 0x50, 0x20, 0x20, 0x20, 0x00], // 'Y'
+    [0xF0, 0x10, 0x20, 0x40, 0x80, 0xF0, 0x00], // 'Z'
+    [0x70, 0x40, 0x40, 0x40, 0x40, 0x70, 0x00], // '['
+    [0x00, 0x80, 0x40, 0x20, 0x10, 0x00, 0x00], // '\\'
+    [0x70, 0x10, 0x10, 0x10, 0x10, 0x70, 0x00], // ']'
+    [0x20, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x00], // '_'
+    [0x40, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x70, 0x90, 0xB0, 0x50, 0x00], // 'a'
+    [0x80, 0x80, 0xE0, 0x90, 0x90, 0xE0, 0x00], // 'b'
+    [0x00, 0x00, 0x60, 0x80, 0x80, 0x60, 0x00], // 'c'
+    [0x10, 0x10, 0x70, 0x90, 0x90, 0x70, 0x00], // 'd'
+    [0x00, 0x00, 0x60, 0xB0, 0xC0, 0x60, 0x00], // 'e'
+    [0x20, 0x50, 0x40, 0xE0, 0x40, 0x40, 0x00], // 'f'
+    [0x00, 0x00, 0x70, 0x90, 0x60, 0x80, 0x70], // 'g'
+    [0x80, 0x80, 0xE0, 0x90, 0x90, 0x90, 0x00], // 'h'
+    [0x20, 0x00, 0x60, 0x20, 0x20, 0x70, 0x00], // 'i'
+    [0x10, 0x00, 0x10, 0x10, 0x10, 0x50, 0x20], // 'j'
+    [0x80, 0x80, 0xA0, 0xC0, 0xA0, 0x90, 0x00], // 'k'
+    [0x60, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'l'
+    [0x00, 0x00, 0xA0, 0xF0, 0x90, 0x90, 0x00], // 'm'
+    [0x00, 0x00, 0xE0, 0x90, 0x90, 0x90, 0x00], // 'n'
+    [0x00, 0x00, 0x60, 0x90, 0x90, 0x60, 0x00], // 'o'
+    [0x00, 0x00, 0xE0, 0x90, 0x90, 0xE0, 0x80], // 'p'
+    [0x00, 0x00, 0x70, 0x90, 0x90, 0x70, 0x10], // 'q'
+    [0x00, 0x00, 0xE0, 0x90, 0x80, 0x80, 0x00], // 'r'
+    [0x00, 0x00, 0x70, 0xC0, 0x30, 0xE0, 0x00], // 's'
+    [0x40, 0x40, 0xE0, 0x40, 0x40, 0x30, 0x00], // 't'
+    [0x00, 0x00, 0x90, 0x90, 0x90, 0x70, 0x00], // 'u'
+    [0x00, 0x00, 0x50, 0x50, 0x50, 0x20, 0x00], // 'v'
+    [0x00, 0x00, 0x90, 0x90, 0xF0, 0xF0, 0x00], // 'w'
+    [0x00, 0x00, 0x90, 0x60, 0x60, 0x90, 0x00], // 'x'
+    [0x00, 0x00, 0x90, 0x90, 0x50, 0x20, 0x40], // 'y'
+    [0x00, 0x00, 0xF0, 0x20, 0x40, 0xF0, 0x00], // 'z'
+    [0x10, 0x20, 0x60, 0x20, 0x20, 0x10, 0x00], // '{'
+    [0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // '|'
+    [0x40, 0x20, 0x30, 0x20, 0x20, 0x40, 0x00], // '}'
+    [0x50, 0xA0, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+/// Bitmap of the character, characters outside of the printable ASCII
+/// range are drawn as a question mark
+pub fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let code = c as u32;
+    if code >= FIRST_CHAR && code <= LAST_CHAR {
+        &GLYPHS[(code - FIRST_CHAR) as usize]
+    } else {
+        glyph(FALLBACK_CHAR)
+    }
+}
@@ -0,0 +1,343 @@
+mod crypto;
+mod font;
+mod screen;
+mod syscalls;
+pub mod snapshot;
+
+use core::slice;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::vec::Vec;
+use byteorder::{ByteOrder, BigEndian};
+use error::SystemError;
+use time::Duration;
+use seproxyhal::{MessageLoop, Channel};
+
+pub use self::screen::{Framebuffer, SCREEN_WIDTH, SCREEN_HEIGHT};
+pub use self::syscalls::{Syscall, SyscallRecord};
+
+// The system UI never takes over the screen in the simulator
+const UX_OK: u32 = 0xB0105011;
+
+const HEADER_SIZE: usize = 3;
+
+const EVENT_BUTTON_PUSH: u8 = 0x05;
+const EVENT_DISPLAY_PROCESSED: u8 = 0x0D;
+const EVENT_TICKER: u8 = 0x0E;
+
+const STATUS_GENERAL: u8 = 0x60;
+const STATUS_SCREEN_DISPLAY: u8 = 0x65;
+const STATUS_MASK: u8 = 0xF0;
+
+const TICKER_INTERVAL_MS: usize = 100;
+
+struct State {
+    events: VecDeque<Vec<u8>>,
+    status_sent: bool,
+    tx_buffer: Vec<u8>,
+    commands: Vec<Vec<u8>>,
+    framebuffer: Framebuffer,
+    rng_state: u32,
+    syscalls: Vec<SyscallRecord>,
+    scripted: Vec<ScriptedSyscall>,
+}
+
+impl State {
+    fn new() -> Self {
+        Self{
+            events: VecDeque::new(),
+            // The app starts without an event to reply to
+            status_sent: false,
+            tx_buffer: Vec::new(),
+            commands: Vec::new(),
+            framebuffer: Framebuffer::new(),
+            rng_state: 0x2545F491,
+            syscalls: Vec::new(),
+            scripted: Vec::new(),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize, SystemError> {
+        if !self.status_sent {
+            return Err(SystemError::IoState);
+        }
+        let event = match self.events.pop_front() {
+            Some(event) => event,
+            None => return Err(SystemError::IoReset),
+        };
+        if event.len() > buf.len() {
+            return Err(SystemError::IoOverflow);
+        }
+
+        buf[0..event.len()].copy_from_slice(&event);
+        self.status_sent = false;
+        Ok(event.len())
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), SystemError> {
+        // Packets can be split across several writes
+        self.tx_buffer.extend_from_slice(data);
+
+        while self.tx_buffer.len() >= HEADER_SIZE {
+            let len = HEADER_SIZE + BigEndian::read_u16(&self.tx_buffer[1..3]) as usize;
+            if self.tx_buffer.len() < len {
+                break;
+            }
+            let packet: Vec<u8> = self.tx_buffer.drain(0..len).collect();
+            self.process_packet(&packet)?;
+        }
+
+        Ok(())
+    }
+
+    fn process_packet(&mut self, packet: &[u8]) -> Result<(), SystemError> {
+        let tag = packet[0];
+
+        // Every event gets exactly one status, commands have to be sent
+        // before it
+        if self.status_sent {
+            return Err(SystemError::IoState);
+        }
+
+        if tag & STATUS_MASK == STATUS_GENERAL {
+            self.status_sent = true;
+            if tag == STATUS_SCREEN_DISPLAY {
+                self.framebuffer.draw(&packet[HEADER_SIZE..]);
+                self.events.push_back(vec![EVENT_DISPLAY_PROCESSED, 0, 0]);
+            }
+        } else {
+            self.commands.push(packet.to_vec());
+        }
+
+        Ok(())
+    }
+
+    fn fill_random(&mut self, buf: &mut [u8]) {
+        // Deterministic xorshift, so that the runs are reproducible
+        for b in buf.iter_mut() {
+            let mut x = self.rng_state;
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.rng_state = x;
+            *b = x as u8;
+        }
+    }
+}
+
+struct ScriptedSyscall {
+    syscall: Syscall,
+    result: Result<u32, SystemError>,
+    outputs: Vec<Vec<u8>>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::new());
+}
+
+#[doc(hidden)]
+pub fn supervisor_call(syscall_id: (u32, u32), params: &[usize]) -> Result<u32, SystemError> {
+    let syscall = match Syscall::from_id(syscall_id.0) {
+        Some(syscall) => syscall,
+        None => return Err(SystemError::NotSupported),
+    };
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        state.syscalls.push(SyscallRecord{
+            syscall,
+            params: params.to_vec(),
+        });
+        if let Some(idx) = state.scripted.iter().position(|s| s.syscall == syscall) {
+            let scripted = state.scripted.remove(idx);
+            for (&(param, size), output) in syscall.output_params().iter().zip(scripted.outputs.iter()) {
+                assert!(params[param] != 0, "{:?} has no output buffer for {:?}", syscall, output);
+                let size = size.of(params);
+                assert!(output.len() <= size,
+                    "{:?} output of {} bytes doesn't fit the {} byte buffer", syscall, output.len(), size);
+                let buf = unsafe { slice::from_raw_parts_mut(params[param] as *mut u8, output.len()) };
+                buf.copy_from_slice(output);
+            }
+            return scripted.result;
+        }
+
+        match syscall {
+            Syscall::OsSchedExit => panic!("Application exited with code {}", params[0]),
+            Syscall::CheckApiLevel => Ok(0),
+            Syscall::OsUx => Ok(UX_OK),
+            Syscall::NvmWrite => {
+                // NVRAM of the app is regular memory off the device
+                let dst = unsafe { slice::from_raw_parts_mut(params[0] as *mut u8, params[2]) };
+                let src = unsafe { slice::from_raw_parts(params[1] as *const u8, params[2]) };
+                dst.copy_from_slice(src);
+                Ok(0)
+            },
+            Syscall::CxSha256Init => crypto::sha256_init(params),
+            Syscall::CxSha512Init => crypto::sha512_init(params),
+            Syscall::CxRipemd160Init => crypto::ripemd160_init(params),
+            Syscall::CxKeccakInit => crypto::keccak_init(params),
+            Syscall::CxBlake2bInit => crypto::blake2b_init(params),
+            Syscall::CxHash => crypto::hash(params),
+            Syscall::CxHmacSha256Init => crypto::hmac_sha256_init(params),
+            Syscall::CxHmacSha512Init => crypto::hmac_sha512_init(params),
+            Syscall::CxHmac => crypto::hmac(params),
+            Syscall::CxRng => {
+                let buf = unsafe { slice::from_raw_parts_mut(params[0] as *mut u8, params[1]) };
+                state.fill_random(buf);
+                Ok(0)
+            },
+            Syscall::IoSeproxyhalSpiIsStatusSent => Ok(state.status_sent as u32),
+            Syscall::IoSeproxyhalSpiRecv => {
+                let buf = unsafe { slice::from_raw_parts_mut(params[0] as *mut u8, params[1]) };
+                state.recv(buf).map(|n| n as u32)
+            },
+            Syscall::IoSeproxyhalSpiSend => {
+                let buf = unsafe { slice::from_raw_parts(params[0] as *const u8, params[1]) };
+                state.send(buf).map(|_| 0)
+            },
+            _ => Err(SystemError::NotSupported),
+        }
+    })
+}
+
+#[derive(Clone, Copy)]
+pub enum Button {
+    Left,
+    Right,
+    Both,
+}
+
+impl Button {
+    fn to_wire_format(&self) -> u8 {
+        match self {
+            &Button::Left => 1 << 1,
+            &Button::Right => 1 << 2,
+            &Button::Both => (1 << 1) | (1 << 2),
+        }
+    }
+}
+
+/// Simulated SE proxy HAL for running apps on the host. Each simulator
+/// starts from a blank screen, with `StartLoop` as the first event. The
+/// syscalls that the app makes are recorded and their results can be
+/// scripted, syscalls that aren't simulated fail with `NotSupported`.
+/// Hashes and HMACs are computed in software.
+///
+/// ```ignore
+/// let mut sim = Simulator::new();
+/// sim.run(|ch| { ui.process_event(ch, &mut app); });
+/// sim.press_button(Button::Right);
+/// sim.run(|ch| { ui.process_event(ch, &mut app); });
+/// println!("{}", sim.framebuffer());
+/// ```
+pub struct Simulator {
+    message_loop: MessageLoop,
+    started: bool,
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        STATE.with(|state| *state.borrow_mut() = State::new());
+
+        Self{
+            message_loop: MessageLoop::new(),
+            started: false,
+        }
+    }
+
+    /// Queues a raw seproxyhal event
+    pub fn push_event(&mut self, raw: &[u8]) {
+        STATE.with(|state| state.borrow_mut().events.push_back(raw.to_vec()));
+    }
+
+    /// Queues the events of pressing and releasing the button
+    pub fn press_button(&mut self, button: Button) {
+        self.push_event(&[EVENT_BUTTON_PUSH, 0, 1, button.to_wire_format()]);
+        self.push_event(&[EVENT_BUTTON_PUSH, 0, 1, 0]);
+    }
+
+    /// Queues the events of holding down the button for the duration
+    pub fn hold_button(&mut self, button: Button, duration: Duration) {
+        for _ in 0..ticks_in(duration) + 1 {
+            self.push_event(&[EVENT_BUTTON_PUSH, 0, 1, button.to_wire_format()]);
+        }
+        self.push_event(&[EVENT_BUTTON_PUSH, 0, 1, 0]);
+    }
+
+    /// Queues a single ticker event
+    pub fn tick(&mut self) {
+        self.push_event(&[EVENT_TICKER, 0, 0]);
+    }
+
+    /// Queues the ticker events that would arrive during the duration
+    pub fn advance(&mut self, duration: Duration) {
+        for _ in 0..ticks_in(duration) {
+            self.tick();
+        }
+    }
+
+    /// Passes the queued events, including the ones generated while
+    /// processing them, to `f` until there are none left
+    pub fn run<F>(&mut self, mut f: F)
+        where F: FnMut(Channel)
+    {
+        loop {
+            let has_events = STATE.with(|state| !state.borrow().events.is_empty());
+            if self.started && !has_events {
+                break;
+            }
+            self.started = true;
+
+            let ch = self.message_loop.next().unwrap();
+            f(ch);
+        }
+    }
+
+    pub fn framebuffer(&self) -> Framebuffer {
+        STATE.with(|state| state.borrow().framebuffer)
+    }
+
+    /// Raw command packets that the app has sent since the last call
+    pub fn take_commands(&mut self) -> Vec<Vec<u8>> {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.commands.drain(..).collect()
+        })
+    }
+
+    /// Syscalls that the app has made since the last call
+    pub fn take_syscalls(&mut self) -> Vec<SyscallRecord> {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.syscalls.drain(..).collect()
+        })
+    }
+
+    /// Makes the next call of `syscall` return `result` instead of being
+    /// simulated, e.g. to inject `SystemError::IoReset`. Results scripted
+    /// for the same syscall are returned in order. Output buffers of the
+    /// syscall are left untouched.
+    pub fn script_syscall(&mut self, syscall: Syscall, result: Result<u32, SystemError>) {
+        self.script_syscall_output(syscall, result, &[]);
+    }
+
+    /// Same as `script_syscall`, but also fills the output buffers of the
+    /// syscall with `outputs`, in the order of the syscall parameters. For
+    /// example the key and chain code of `OsPersoDeriveNodeBip32` or the
+    /// signature and info of `CxEcdsaSign`. The syscall panics when an
+    /// output doesn't fit the buffer that the SDK passes in.
+    pub fn script_syscall_output(&mut self, syscall: Syscall, result: Result<u32, SystemError>, outputs: &[&[u8]]) {
+        assert!(outputs.len() <= syscall.output_params().len(),
+            "{:?} doesn't have {} output buffers", syscall, outputs.len());
+        STATE.with(|state| state.borrow_mut().scripted.push(ScriptedSyscall{
+            syscall,
+            result,
+            outputs: outputs.iter().map(|output| output.to_vec()).collect(),
+        }));
+    }
+}
+
+fn ticks_in(duration: Duration) -> usize {
+    duration.as_millis() / TICKER_INTERVAL_MS
+}
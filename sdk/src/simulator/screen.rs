@@ -0,0 +1,457 @@
+use core::cmp::min;
+use core::fmt;
+use std::string::String;
+use byteorder::{ByteOrder, LittleEndian};
+use ui::TextFont;
+use super::font::{self, GLYPH_WIDTH, GLYPH_ASCENT};
+
+pub const SCREEN_WIDTH: usize = 128;
+pub const SCREEN_HEIGHT: usize = 32;
+
+const HEADER_SIZE: usize = 28;
+
+const TYPE_BUTTON: u8 = 1;
+const TYPE_LABEL: u8 = 2;
+const TYPE_RECTANGLE: u8 = 3;
+const TYPE_LINE: u8 = 4;
+const TYPE_ICON: u8 = 5;
+const TYPE_CIRCLE: u8 = 6;
+const TYPE_LABEL_LINE: u8 = 7;
+
+const FILL: u8 = 1;
+
+const FONT_ID_MASK: u16 = 0x0FFF;
+const ALIGN_CENTER: u16 = 0x8000;
+const ALIGN_RIGHT: u16 = 0x4000;
+const ALIGN_MIDDLE: u16 = 0x2000;
+const ALIGN_BOTTOM: u16 = 0x1000;
+
+const ICON_CHECK: u8 = 6;
+const ICON_CROSS: u8 = 7;
+const ICON_LEFT: u8 = 9;
+const ICON_RIGHT: u8 = 10;
+const ICON_UP: u8 = 11;
+const ICON_DOWN: u8 = 12;
+const ICON_DASHBOARD_BADGE: u8 = 15;
+
+/// Monochrome image of the Nano S screen, lit pixels are the ones drawn
+/// with a non-black color
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    rows: [u128; SCREEN_HEIGHT],
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Self{
+            rows: [0; SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+            false
+        } else {
+            self.rows[y] & (1 << x) != 0
+        }
+    }
+
+    fn set_pixel(&mut self, x: i32, y: i32, on: bool) {
+        if x < 0 || y < 0 || x >= SCREEN_WIDTH as i32 || y >= SCREEN_HEIGHT as i32 {
+            return;
+        }
+        if on {
+            self.rows[y as usize] |= 1 << x;
+        } else {
+            self.rows[y as usize] &= !(1 << x);
+        }
+    }
+
+    fn fill_rect(&mut self, frame: &Rect, on: bool) {
+        for y in frame.y..frame.y + frame.height {
+            for x in frame.x..frame.x + frame.width {
+                self.set_pixel(x, y, on);
+            }
+        }
+    }
+
+    /// Renders the payload of a screen display status onto the framebuffer
+    pub fn draw(&mut self, data: &[u8]) {
+        if data.len() < HEADER_SIZE {
+            return;
+        }
+
+        let hdr = Header::from_bytes(data);
+        let extra = &data[HEADER_SIZE..];
+
+        match hdr.type_id {
+            TYPE_RECTANGLE => self.draw_rectangle(&hdr),
+            TYPE_LINE => self.fill_rect(&hdr.frame, hdr.foreground),
+            TYPE_CIRCLE => self.draw_circle(&hdr),
+            TYPE_ICON => self.draw_icon(&hdr, extra),
+            TYPE_BUTTON => {
+                self.draw_rectangle(&hdr);
+                let text_on = if hdr.fill == FILL { hdr.background } else { hdr.foreground };
+                let font = Font::from_id(hdr.font_id);
+                let baseline = hdr.frame.y + (hdr.frame.height + font.ascent()) / 2;
+                self.draw_text(&hdr, extra, baseline, text_on);
+            },
+            TYPE_LABEL => {
+                if hdr.fill == FILL {
+                    self.fill_rect(&hdr.frame, hdr.background);
+                }
+                let font = Font::from_id(hdr.font_id);
+                let height = font.height();
+                let top = if hdr.font_id & ALIGN_MIDDLE != 0 {
+                    hdr.frame.y + (hdr.frame.height - height) / 2
+                } else if hdr.font_id & ALIGN_BOTTOM != 0 {
+                    hdr.frame.y + hdr.frame.height - height
+                } else {
+                    hdr.frame.y
+                };
+                self.draw_text(&hdr, extra, top + font.ascent(), hdr.foreground);
+            },
+            TYPE_LABEL_LINE => {
+                // Label lines are positioned by their baseline
+                let baseline = hdr.frame.y;
+                if hdr.fill == FILL {
+                    let font = Font::from_id(hdr.font_id);
+                    self.fill_rect(&Rect{
+                        x: hdr.frame.x,
+                        y: baseline - font.ascent(),
+                        width: hdr.frame.width,
+                        height: font.height(),
+                    }, hdr.background);
+                }
+                self.draw_text(&hdr, extra, baseline, hdr.foreground);
+            },
+            _ => {},
+        }
+    }
+
+    fn draw_rectangle(&mut self, hdr: &Header) {
+        let frame = &hdr.frame;
+        let radius = min(hdr.radius, min(frame.width, frame.height) / 2);
+        let stroke = hdr.stroke as i32;
+        let inner = Rect{
+            x: frame.x + stroke,
+            y: frame.y + stroke,
+            width: frame.width - 2 * stroke,
+            height: frame.height - 2 * stroke,
+        };
+
+        for y in frame.y..frame.y + frame.height {
+            for x in frame.x..frame.x + frame.width {
+                if !frame.contains_rounded(x, y, radius) {
+                    continue;
+                }
+                if hdr.fill == FILL {
+                    self.set_pixel(x, y, hdr.foreground);
+                } else if stroke > 0 && !inner.contains_rounded(x, y, radius - stroke) {
+                    self.set_pixel(x, y, hdr.foreground);
+                }
+            }
+        }
+    }
+
+    fn draw_circle(&mut self, hdr: &Header) {
+        let radius = hdr.radius;
+        let inner_radius = radius - hdr.stroke as i32;
+        let cx = hdr.frame.x + radius;
+        let cy = hdr.frame.y + radius;
+
+        for y in cy - radius..cy + radius + 1 {
+            for x in cx - radius..cx + radius + 1 {
+                let d = (x - cx) * (x - cx) + (y - cy) * (y - cy);
+                if d > radius * radius {
+                    continue;
+                }
+                if hdr.fill == FILL || d > inner_radius * inner_radius {
+                    self.set_pixel(x, y, hdr.foreground);
+                }
+            }
+        }
+    }
+
+    fn draw_icon(&mut self, hdr: &Header, extra: &[u8]) {
+        let frame = &hdr.frame;
+
+        if hdr.icon_id != 0 {
+            if let Some(rows) = system_icon(hdr.icon_id) {
+                for (dy, row) in rows.iter().enumerate() {
+                    for (dx, c) in row.bytes().enumerate() {
+                        self.set_pixel(frame.x + dx as i32, frame.y + dy as i32, c == b'#');
+                    }
+                }
+            }
+            return;
+        }
+
+        // Custom icons carry the bits per pixel, the color palette and
+        // the LSB first packed bitmap after the header
+        if extra.len() < 1 {
+            return;
+        }
+        let bpp = extra[0] as usize;
+        if bpp == 0 || bpp > 8 {
+            return;
+        }
+        let colors_len = 4 * (1 << bpp);
+        if extra.len() < 1 + colors_len {
+            return;
+        }
+        let colors = &extra[1..1 + colors_len];
+        let bitmap = &extra[1 + colors_len..];
+        let mask = (1 << bpp) - 1;
+
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let bit = ((y * frame.width + x) as usize) * bpp;
+                if bit / 8 >= bitmap.len() {
+                    return;
+                }
+                let idx = ((bitmap[bit / 8] as usize) >> (bit % 8)) & mask;
+                let color = LittleEndian::read_u32(&colors[4 * idx..4 * idx + 4]);
+                self.set_pixel(frame.x + x, frame.y + y, color != 0);
+            }
+        }
+    }
+
+    fn draw_text(&mut self, hdr: &Header, text: &[u8], baseline: i32, on: bool) {
+        let frame = &hdr.frame;
+        let font = Font::from_id(hdr.font_id);
+        let text = String::from_utf8_lossy(text);
+        let text_width = font.width_for_text(&text);
+
+        let mut pen_x = if hdr.font_id & ALIGN_CENTER != 0 {
+            frame.x + (frame.width - text_width) / 2
+        } else if hdr.font_id & ALIGN_RIGHT != 0 {
+            frame.x + frame.width - text_width
+        } else {
+            frame.x
+        };
+        let top = baseline - GLYPH_ASCENT as i32 * font.scale;
+
+        for c in text.chars() {
+            let advance = font.advance(c);
+            if advance == 0 {
+                continue;
+            }
+            // Glyphs are centred within their advance
+            let glyph_x = pen_x + (advance - (GLYPH_WIDTH as i32) * font.scale) / 2;
+            let rows = font::glyph(c);
+            for (gy, row) in rows.iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    if row & (0x80 >> gx) == 0 {
+                        continue;
+                    }
+                    for sy in 0..font.scale {
+                        for sx in 0..font.scale + font.weight {
+                            let x = glyph_x + (gx as i32) * font.scale + sx;
+                            let y = top + (gy as i32) * font.scale + sy;
+                            // Text is clipped to the width of the frame
+                            if frame.width > 0 && (x < frame.x || x >= frame.x + frame.width) {
+                                continue;
+                            }
+                            self.set_pixel(x, y, on);
+                        }
+                    }
+                }
+            }
+            pen_x += advance;
+        }
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                f.write_str(if self.pixel(x, y) { "#" } else { "." })?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\n{}", self)
+    }
+}
+
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn contains_rounded(&self, x: i32, y: i32, radius: i32) -> bool {
+        if x < self.x || y < self.y
+            || x >= self.x + self.width || y >= self.y + self.height {
+            return false;
+        }
+        if radius <= 0 {
+            return true;
+        }
+
+        // Only the corners are affected by the radius
+        let (left, right) = (self.x + radius, self.x + self.width - radius - 1);
+        let (top, bottom) = (self.y + radius, self.y + self.height - radius - 1);
+        let cx = if x < left { left } else if x > right { right } else { return true };
+        let cy = if y < top { top } else if y > bottom { bottom } else { return true };
+        (x - cx) * (x - cx) + (y - cy) * (y - cy) <= radius * radius
+    }
+}
+
+struct Header {
+    type_id: u8,
+    frame: Rect,
+    stroke: u8,
+    radius: i32,
+    fill: u8,
+    foreground: bool,
+    background: bool,
+    font_id: u16,
+    icon_id: u8,
+}
+
+impl Header {
+    fn from_bytes(data: &[u8]) -> Self {
+        Self{
+            type_id: data[0],
+            frame: Rect{
+                x: LittleEndian::read_i16(&data[2..4]) as i32,
+                y: LittleEndian::read_i16(&data[4..6]) as i32,
+                width: LittleEndian::read_u16(&data[6..8]) as i32,
+                height: LittleEndian::read_u16(&data[8..10]) as i32,
+            },
+            stroke: data[10],
+            radius: data[11] as i32,
+            fill: data[12],
+            foreground: LittleEndian::read_u32(&data[16..20]) != 0,
+            background: LittleEndian::read_u32(&data[20..24]) != 0,
+            font_id: LittleEndian::read_u16(&data[24..26]),
+            icon_id: data[26],
+        }
+    }
+}
+
+// The device glyphs aren't available on the host, so they're drawn with
+// the 5x7 font (at double size for the 16px font). Glyphs are advanced by
+// the widths of the device fonts so that text takes the same space as on
+// the device.
+struct Font {
+    text_font: TextFont,
+    scale: i32,
+    weight: i32,
+}
+
+impl Font {
+    fn from_id(font_id: u16) -> Self {
+        let text_font = TextFont::from_wire_format(font_id & FONT_ID_MASK)
+            .unwrap_or(TextFont::OpenSansRegular11px);
+        let (scale, weight) = match text_font {
+            TextFont::OpenSansLight16px => (2, 0),
+            TextFont::OpenSansRegular11px => (1, 0),
+            TextFont::OpenSansExtraBold11px => (1, 1),
+        };
+        Font{ text_font, scale, weight }
+    }
+
+    fn ascent(&self) -> i32 {
+        self.text_font.ascent() as i32
+    }
+
+    fn height(&self) -> i32 {
+        self.text_font.line_height() as i32
+    }
+
+    fn advance(&self, c: char) -> i32 {
+        self.text_font.width_for_char(c) as i32
+    }
+
+    fn width_for_text(&self, text: &str) -> i32 {
+        text.chars().map(|c| self.advance(c)).sum()
+    }
+}
+
+fn system_icon(icon_id: u8) -> Option<&'static [&'static str]> {
+    static CHECK: [&str; 6] = [
+        ".......#",
+        "......##",
+        "#....##.",
+        "##..##..",
+        ".####...",
+        "..##....",
+    ];
+    static CROSS: [&str; 7] = [
+        "#.....#",
+        ".#...#.",
+        "..#.#..",
+        "...#...",
+        "..#.#..",
+        ".#...#.",
+        "#.....#",
+    ];
+    static LEFT: [&str; 7] = [
+        "...#",
+        "..#.",
+        ".#..",
+        "#...",
+        ".#..",
+        "..#.",
+        "...#",
+    ];
+    static RIGHT: [&str; 7] = [
+        "#...",
+        ".#..",
+        "..#.",
+        "...#",
+        "..#.",
+        ".#..",
+        "#...",
+    ];
+    static UP: [&str; 4] = [
+        "...#...",
+        "..#.#..",
+        ".#...#.",
+        "#.....#",
+    ];
+    static DOWN: [&str; 4] = [
+        "#.....#",
+        ".#...#.",
+        "..#.#..",
+        "...#...",
+    ];
+    static DASHBOARD_BADGE: [&str; 14] = [
+        "....######....",
+        "..##......##..",
+        ".#..........#.",
+        ".#..........#.",
+        "#....####....#",
+        "#...#....#...#",
+        "#...#....#...#",
+        "#...#....#...#",
+        "#...#....#...#",
+        "#....####....#",
+        ".#..........#.",
+        ".#..........#.",
+        "..##......##..",
+        "....######....",
+    ];
+
+    match icon_id {
+        ICON_CHECK => Some(&CHECK),
+        ICON_CROSS => Some(&CROSS),
+        ICON_LEFT => Some(&LEFT),
+        ICON_RIGHT => Some(&RIGHT),
+        ICON_UP => Some(&UP),
+        ICON_DOWN => Some(&DOWN),
+        ICON_DASHBOARD_BADGE => Some(&DASHBOARD_BADGE),
+        _ => None,
+    }
+}
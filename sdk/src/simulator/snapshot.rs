@@ -0,0 +1,146 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::string::{String, ToString};
+use time::Duration;
+use ui;
+use super::{Simulator, Button, Framebuffer};
+
+/// Setting this environment variable makes `assert_snapshot` overwrite
+/// the snapshots with the current screen instead of comparing them
+pub const UPDATE_SNAPSHOTS_VAR: &str = "BOLOS_UPDATE_SNAPSHOTS";
+
+const UNUSED_EVENT_TAG: u8 = 0xFF;
+
+/// Runs a `ui::Delegate` through `ui::Middleware` in the simulator. Every
+/// interaction is processed until the screen is fully drawn.
+///
+/// ```ignore
+/// let mut runner = UiRunner::new(AppState::new());
+/// runner.press_button(Button::Right);
+/// assert_snapshot(&runner.screen(), "snapshots/settings_menu.txt");
+/// ```
+pub struct UiRunner<A, D> {
+    sim: Simulator,
+    middleware: ui::Middleware<A, D>,
+    delegate: D,
+}
+
+impl<A, D> UiRunner<A, D>
+    where A: Copy,
+          D: ui::Delegate<Action=A>,
+{
+    pub fn new(delegate: D) -> Self {
+        let mut runner = Self{
+            sim: Simulator::new(),
+            middleware: ui::Middleware::new(),
+            delegate,
+        };
+        runner.run();
+        runner
+    }
+
+    fn run(&mut self) {
+        let UiRunner{ ref mut sim, ref mut middleware, ref mut delegate } = *self;
+        sim.run(|ch| {
+            let _ch = Some(ch)
+                .and_then(|ch| middleware.process_event(ch, delegate))
+                .and_then(|ch| middleware.redraw_if_needed(ch, delegate));
+        });
+    }
+
+    pub fn delegate(&self) -> &D {
+        &self.delegate
+    }
+
+    /// Gives access to the delegate, the UI is redrawn afterwards if
+    /// the changes require it
+    pub fn update<F>(&mut self, f: F)
+        where F: FnOnce(&mut D)
+    {
+        f(&mut self.delegate);
+        // Any event gives the middleware a chance to redraw, use one that
+        // nothing reacts to so that timers aren't affected
+        self.sim.push_event(&[UNUSED_EVENT_TAG, 0, 0]);
+        self.run();
+    }
+
+    pub fn press_button(&mut self, button: Button) {
+        self.sim.press_button(button);
+        self.run();
+    }
+
+    pub fn hold_button(&mut self, button: Button, duration: Duration) {
+        self.sim.hold_button(button, duration);
+        self.run();
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.sim.advance(duration);
+        self.run();
+    }
+
+    pub fn screen(&self) -> Framebuffer {
+        self.sim.framebuffer()
+    }
+}
+
+/// Compares the screen against the ASCII art snapshot at `path`, relative
+/// paths are resolved from the crate that runs the tests. Missing
+/// snapshots fail the assertion unless `UPDATE_SNAPSHOTS_VAR` is set.
+pub fn assert_snapshot<P: AsRef<Path>>(screen: &Framebuffer, path: P) {
+    let path = snapshot_path(path.as_ref());
+    let actual = screen.to_string();
+
+    if env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        return write_snapshot(&path, &actual);
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(s) => s.replace("\r\n", "\n"),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => panic!(
+            "Snapshot {} doesn't exist (set {}=1 to create it)\nactual:\n{}",
+            path.display(), UPDATE_SNAPSHOTS_VAR, actual
+        ),
+        Err(err) => panic!("Unable to read snapshot {}: {}", path.display(), err),
+    };
+
+    if expected != actual {
+        panic!(
+            "Screen doesn't match snapshot {} (set {}=1 to update it)\n\
+             expected:\n{}\nactual:\n{}\ndifference:\n{}",
+            path.display(), UPDATE_SNAPSHOTS_VAR, expected, actual, diff(&expected, &actual)
+        );
+    }
+}
+
+fn snapshot_path(path: &Path) -> PathBuf {
+    if path.is_relative() {
+        if let Some(dir) = env::var_os("CARGO_MANIFEST_DIR") {
+            return Path::new(&dir).join(path);
+        }
+    }
+    path.to_path_buf()
+}
+
+fn write_snapshot(path: &Path, contents: &str) {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .unwrap_or_else(|err| panic!("Unable to create {}: {}", dir.display(), err));
+    }
+    fs::write(path, contents)
+        .unwrap_or_else(|err| panic!("Unable to write snapshot {}: {}", path.display(), err));
+}
+
+// Marks the pixels that differ with an 'X'
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::new();
+    for (expected_line, actual_line) in expected.lines().zip(actual.lines()) {
+        for (e, a) in expected_line.chars().zip(actual_line.chars()) {
+            out.push(if e == a { a } else { 'X' });
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,151 @@
+use std::vec::Vec;
+use crypto::{bip32, ecc};
+use syscall;
+
+/// Syscalls that the SDK makes, identified by their supervisor call id
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Syscall {
+    CheckApiLevel,
+    OsSchedExit,
+    OsUx,
+    NvmWrite,
+    CxRng,
+    CxHash,
+    CxRipemd160Init,
+    CxSha256Init,
+    CxSha512Init,
+    CxKeccakInit,
+    CxBlake2bInit,
+    CxHmacSha256Init,
+    CxHmacSha512Init,
+    CxHmac,
+    CxEcfpInitPrivateKey,
+    CxEcfpGeneratePair,
+    CxEcdsaSign,
+    CxEddsaSign,
+    OsPersoDeriveNodeBip32,
+    IoSeproxyhalSpiIsStatusSent,
+    IoSeproxyhalSpiRecv,
+    IoSeproxyhalSpiSend,
+}
+
+impl Syscall {
+    pub fn from_id(id: u32) -> Option<Self> {
+        if id == syscall::CHECK_API_LEVEL_ID.0 {
+            Some(Syscall::CheckApiLevel)
+        } else if id == syscall::OS_SCHED_EXIT_ID.0 {
+            Some(Syscall::OsSchedExit)
+        } else if id == syscall::OS_UX_ID.0 {
+            Some(Syscall::OsUx)
+        } else if id == syscall::NVM_WRITE_ID.0 {
+            Some(Syscall::NvmWrite)
+        } else if id == syscall::CX_RNG_ID.0 {
+            Some(Syscall::CxRng)
+        } else if id == syscall::CX_HASH_ID.0 {
+            Some(Syscall::CxHash)
+        } else if id == syscall::CX_RIPEMD160_INIT_ID.0 {
+            Some(Syscall::CxRipemd160Init)
+        } else if id == syscall::CX_SHA256_INIT_ID.0 {
+            Some(Syscall::CxSha256Init)
+        } else if id == syscall::CX_SHA512_INIT_ID.0 {
+            Some(Syscall::CxSha512Init)
+        } else if id == syscall::CX_KECCAK_INIT_ID.0 {
+            Some(Syscall::CxKeccakInit)
+        } else if id == syscall::CX_BLAKE2B_INIT_ID.0 {
+            Some(Syscall::CxBlake2bInit)
+        } else if id == syscall::CX_HMAC_SHA256_INIT_ID.0 {
+            Some(Syscall::CxHmacSha256Init)
+        } else if id == syscall::CX_HMAC_SHA512_INIT_ID.0 {
+            Some(Syscall::CxHmacSha512Init)
+        } else if id == syscall::CX_HMAC_ID.0 {
+            Some(Syscall::CxHmac)
+        } else if id == syscall::CX_ECFP_INIT_PRIVATE_KEY_ID.0 {
+            Some(Syscall::CxEcfpInitPrivateKey)
+        } else if id == syscall::CX_ECFP_GENERATE_PAIR_ID.0 {
+            Some(Syscall::CxEcfpGeneratePair)
+        } else if id == syscall::CX_ECDSA_SIGN_ID.0 {
+            Some(Syscall::CxEcdsaSign)
+        } else if id == syscall::CX_EDDSA_SIGN_ID.0 {
+            Some(Syscall::CxEddsaSign)
+        } else if id == syscall::OS_PERSO_DERIVE_NODE_BIP32_ID.0 {
+            Some(Syscall::OsPersoDeriveNodeBip32)
+        } else if id == syscall::IO_SEPROXYHAL_SPI_IS_STATUS_SENT_ID.0 {
+            Some(Syscall::IoSeproxyhalSpiIsStatusSent)
+        } else if id == syscall::IO_SEPROXYHAL_SPI_RECV_ID.0 {
+            Some(Syscall::IoSeproxyhalSpiRecv)
+        } else if id == syscall::IO_SEPROXYHAL_SPI_SEND_ID.0 {
+            Some(Syscall::IoSeproxyhalSpiSend)
+        } else {
+            None
+        }
+    }
+
+    // Output buffers as the indices of the parameters that point to them,
+    // along with their sizes. Digests aren't included, the firmware isn't
+    // told the size of their buffers and the simulator computes them.
+    pub(crate) fn output_params(&self) -> &'static [(usize, OutputSize)] {
+        match self {
+            &Syscall::CxRng => &[(0, OutputSize::Param(1))],
+            &Syscall::CxEcfpInitPrivateKey => &[(3, OutputSize::Fixed(ecc::RAW_PRIVATE_KEY_SIZE))],
+            &Syscall::CxEcfpGeneratePair => &[
+                (1, OutputSize::Fixed(ecc::RAW_PUBLIC_KEY_SIZE)),
+                (2, OutputSize::Fixed(ecc::RAW_PRIVATE_KEY_SIZE)),
+            ],
+            &Syscall::CxEcdsaSign => &[
+                (5, OutputSize::Fixed(ecc::SIGNATURE_MAX_SIZE)),
+                (6, OutputSize::Fixed(4)),
+            ],
+            &Syscall::CxEddsaSign => &[
+                (7, OutputSize::Fixed(ecc::SIGNATURE_MAX_SIZE)),
+                (8, OutputSize::Fixed(4)),
+            ],
+            &Syscall::OsPersoDeriveNodeBip32 => &[
+                (3, OutputSize::Fixed(bip32::NODE_KEY_SIZE)),
+                (4, OutputSize::Fixed(bip32::CHAIN_CODE_SIZE)),
+            ],
+            &Syscall::IoSeproxyhalSpiRecv => &[(0, OutputSize::Param(1))],
+            _ => &[],
+        }
+    }
+}
+
+// Size of an output buffer of a syscall
+#[derive(Clone, Copy)]
+pub(crate) enum OutputSize {
+    // Size is passed in another parameter
+    Param(usize),
+    // Buffers that the SDK passes in always have the same size
+    Fixed(usize),
+}
+
+impl OutputSize {
+    pub(crate) fn of(&self, params: &[usize]) -> usize {
+        match *self {
+            OutputSize::Param(param) => params[param],
+            OutputSize::Fixed(size) => size,
+        }
+    }
+}
+
+/// Syscall that the app made, the parameters are the raw values passed
+/// to the supervisor (pointers are only valid during the call)
+#[derive(Clone, Debug)]
+pub struct SyscallRecord {
+    pub syscall: Syscall,
+    pub params: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_syscall_ids() {
+        assert_eq!(Syscall::from_id(syscall::CHECK_API_LEVEL_ID.0), Some(Syscall::CheckApiLevel));
+        assert_eq!(Syscall::from_id(syscall::CX_HASH_ID.0), Some(Syscall::CxHash));
+        assert_eq!(Syscall::from_id(syscall::IO_SEPROXYHAL_SPI_SEND_ID.0), Some(Syscall::IoSeproxyhalSpiSend));
+        // Only the id of the call is recognized, not the one of the reply
+        assert_eq!(Syscall::from_id(syscall::CX_HASH_ID.1), None);
+        assert_eq!(Syscall::from_id(0), None);
+    }
+}
@@ -0,0 +1,208 @@
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr;
+use core::slice;
+use error::SystemError;
+use pic::Pic;
+use syscall;
+
+/// Declares a value that persists across application restarts. The value
+/// is placed in the NVRAM section of the application and can be updated
+/// at runtime with `Storage::set`. The `data-size` of the app has to cover
+/// the NVRAM section, `cargo bolos` refuses to package the app otherwise.
+///
+/// A value stored with a different `version` is passed to the `migrate`
+/// function on first access, without one it's reset to the initial value.
+///
+/// ```ignore
+/// persistent!(SETTINGS: Settings = Settings{ truncate_address: false }, version: 1);
+/// persistent!(SETTINGS: Settings = Settings{ truncate_address: false }, version: 2,
+///     migrate: migrate_settings);
+/// ```
+#[macro_export]
+macro_rules! persistent {
+    ($name:ident: $ty:ty = $value:expr, version: $version:expr) => {
+        persistent!($name: $ty = $value, version: $version, migrate: {
+            fn reset(_version: u32, _raw: &[u8]) -> $ty {
+                $value
+            }
+            reset
+        });
+    };
+    ($name:ident: $ty:ty = $value:expr, version: $version:expr, migrate: $migrate:expr) => {
+        #[link_section=".nvm_data"]
+        static $name: $crate::storage::Storage<$ty> =
+            $crate::storage::Storage::new($version, $value, $migrate);
+    };
+}
+
+// Each slot occupies its own flash page(s), so that writing one slot can
+// never corrupt the other one.
+#[repr(C, align(64))]
+struct Slot<T> {
+    // 0 marks a slot that is incomplete (or being written to), otherwise
+    // the slot with the highest sequence number holds the current value
+    sequence: u32,
+    version: u32,
+    value: T,
+}
+
+/// Persistent value with atomic update semantics. Updates are written to
+/// the inactive slot first and are only committed once fully written, so
+/// an interrupted update leaves the previous value intact.
+pub struct Storage<T: Copy> {
+    version: u32,
+    migrate: fn(u32, &[u8]) -> T,
+    // Only ever modified through nvm_write
+    slots: UnsafeCell<[Slot<T>; 2]>,
+}
+
+// Applications are single threaded
+unsafe impl<T: Copy> Sync for Storage<T> {}
+
+impl<T: Copy> Storage<T> {
+    pub const fn new(version: u32, value: T, migrate: fn(u32, &[u8]) -> T) -> Self {
+        Self{
+            version,
+            migrate,
+            slots: UnsafeCell::new([
+                Slot{ sequence: 1, version, value },
+                Slot{ sequence: 0, version, value },
+            ]),
+        }
+    }
+
+    fn slot(&self, idx: usize) -> *mut Slot<T> {
+        let this = self.pic();
+        let slots = this.slots.get() as *mut Slot<T>;
+        unsafe { slots.offset(idx as isize) }
+    }
+
+    fn active_slot(&self) -> (usize, u32) {
+        let seq_0 = unsafe { ptr::read_volatile(&(*self.slot(0)).sequence) };
+        let seq_1 = unsafe { ptr::read_volatile(&(*self.slot(1)).sequence) };
+
+        if seq_1 > seq_0 {
+            (1, seq_1)
+        } else {
+            (0, seq_0)
+        }
+    }
+
+    /// Version of the layout that the stored value was written with
+    pub fn stored_version(&self) -> u32 {
+        let (idx, _) = self.active_slot();
+        unsafe { ptr::read_volatile(&(*self.slot(idx)).version) }
+    }
+
+    /// Current value, a value stored with a different version is migrated
+    /// first and the result is stored in place of it
+    pub fn get(&self) -> T {
+        let this = self.pic();
+        let (idx, _) = self.active_slot();
+        let slot = self.slot(idx);
+        let stored_version = unsafe { ptr::read_volatile(&(*slot).version) };
+        if stored_version == this.version {
+            return unsafe { ptr::read_volatile(&(*slot).value) };
+        }
+
+        // Bytes of an older layout aren't necessarily a valid T
+        let raw = unsafe {
+            let ptr = &(*slot).value as *const T as *const u8;
+            slice::from_raw_parts(ptr, mem::size_of::<T>())
+        };
+        let migrate = this.migrate as *const u8;
+        let migrate: fn(u32, &[u8]) -> T = unsafe { mem::transmute(migrate.pic()) };
+        let value = migrate(stored_version, raw);
+        // Keep using the migrated value even when storing it fails, the
+        // migration is retried on the next access then
+        let _ = self.set(value);
+        value
+    }
+
+    pub fn set(&self, value: T) -> Result<(), SystemError> {
+        let this = self.pic();
+        let (active_idx, active_seq) = self.active_slot();
+        let slot = self.slot(1 - active_idx);
+
+        unsafe {
+            // Invalidate the slot before touching the contents
+            write_bytes(&mut (*slot).sequence, &0)?;
+            write_bytes(&mut (*slot).version, &this.version)?;
+            write_bytes(&mut (*slot).value, &value)?;
+            // Commit the slot as the most recent one
+            write_bytes(&mut (*slot).sequence, &(active_seq + 1))
+        }
+    }
+}
+
+fn write_bytes<V>(dst: *mut V, src: &V) -> Result<(), SystemError> {
+    let src = unsafe {
+        slice::from_raw_parts(src as *const V as *const u8, mem::size_of::<V>())
+    };
+    syscall::nvm_write(dst as *mut u8, src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simulator::{Simulator, Syscall};
+
+    #[test]
+    fn set_alternates_between_slots() {
+        persistent!(VALUE: u32 = 7, version: 1);
+        let mut sim = Simulator::new();
+        assert_eq!(VALUE.get(), 7);
+        assert_eq!(VALUE.active_slot(), (0, 1));
+
+        VALUE.set(8).unwrap();
+        assert_eq!(VALUE.get(), 8);
+        assert_eq!(VALUE.active_slot(), (1, 2));
+
+        VALUE.set(9).unwrap();
+        assert_eq!(VALUE.get(), 9);
+        assert_eq!(VALUE.active_slot(), (0, 3));
+
+        let writes = sim.take_syscalls().iter()
+            .filter(|record| record.syscall == Syscall::NvmWrite)
+            .count();
+        assert_eq!(writes, 8);
+    }
+
+    #[test]
+    fn failed_write_keeps_previous_value() {
+        persistent!(VALUE: u32 = 7, version: 1);
+        let mut sim = Simulator::new();
+        VALUE.set(8).unwrap();
+
+        sim.script_syscall(Syscall::NvmWrite, Err(SystemError::Exception));
+        assert!(VALUE.set(9).is_err());
+        assert_eq!(VALUE.get(), 8);
+    }
+
+    fn migrate(version: u32, raw: &[u8]) -> u32 {
+        assert_eq!(version, 1);
+        // Version 1 stored the value in the low byte only
+        raw[0] as u32 * 100
+    }
+
+    #[test]
+    fn get_migrates_older_version() {
+        static VALUE: Storage<u32> = Storage{
+            version: 2,
+            migrate,
+            slots: UnsafeCell::new([
+                Slot{ sequence: 1, version: 1, value: 3 },
+                Slot{ sequence: 0, version: 1, value: 0 },
+            ]),
+        };
+        let _sim = Simulator::new();
+        assert_eq!(VALUE.stored_version(), 1);
+        assert_eq!(VALUE.get(), 300);
+
+        // Migrated value replaces the stored one
+        assert_eq!(VALUE.stored_version(), 2);
+        assert_eq!(VALUE.active_slot(), (1, 2));
+        assert_eq!(VALUE.get(), 300);
+    }
+}
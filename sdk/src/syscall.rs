@@ -1,82 +1,272 @@
 use error::SystemError;
 
+// Supervisor call ids of the syscalls and the ids that the supervisor
+// replies with, the simulator recognizes the syscalls by them as well
+pub(crate) const CHECK_API_LEVEL_ID: (u32, u32) = (0x60000137, 0x900001c6);
+pub(crate) const OS_SCHED_EXIT_ID: (u32, u32) = (0x60005fe1, 0x90005f6f);
+pub(crate) const OS_UX_ID: (u32, u32) = (0x60006158, 0x9000611f);
+pub(crate) const NVM_WRITE_ID: (u32, u32) = (0x6000037f, 0x900003bc);
+pub(crate) const CX_RNG_ID: (u32, u32) = (0x6000052c, 0x90000567);
+pub(crate) const CX_HASH_ID: (u32, u32) = (0x6000073b, 0x900007ad);
+pub(crate) const CX_RIPEMD160_INIT_ID: (u32, u32) = (0x6000087f, 0x900008f8);
+pub(crate) const CX_SHA256_INIT_ID: (u32, u32) = (0x60000adb, 0x90000a64);
+pub(crate) const CX_SHA512_INIT_ID: (u32, u32) = (0x60000c3f, 0x90000cd6);
+pub(crate) const CX_KECCAK_INIT_ID: (u32, u32) = (0x60000fd1, 0x90000f3b);
+pub(crate) const CX_BLAKE2B_INIT_ID: (u32, u32) = (0x6000115c, 0x9000113d);
+pub(crate) const CX_HMAC_SHA256_INIT_ID: (u32, u32) = (0x6000142f, 0x900014c0);
+pub(crate) const CX_HMAC_SHA512_INIT_ID: (u32, u32) = (0x600015a4, 0x90001575);
+pub(crate) const CX_HMAC_ID: (u32, u32) = (0x6000163a, 0x900016cf);
+pub(crate) const CX_ECFP_INIT_PRIVATE_KEY_ID: (u32, u32) = (0x600029ed, 0x900029ae);
+pub(crate) const CX_ECFP_GENERATE_PAIR_ID: (u32, u32) = (0x60002b2f, 0x90002b63);
+pub(crate) const CX_ECDSA_SIGN_ID: (u32, u32) = (0x600038f3, 0x90003876);
+pub(crate) const CX_EDDSA_SIGN_ID: (u32, u32) = (0x60003a0c, 0x90003a93);
+pub(crate) const OS_PERSO_DERIVE_NODE_BIP32_ID: (u32, u32) = (0x600050ba, 0x9000501e);
+pub(crate) const IO_SEPROXYHAL_SPI_IS_STATUS_SENT_ID: (u32, u32) = (0x60006fcf, 0x90006f7f);
+pub(crate) const IO_SEPROXYHAL_SPI_RECV_ID: (u32, u32) = (0x600070d1, 0x9000702b);
+pub(crate) const IO_SEPROXYHAL_SPI_SEND_ID: (u32, u32) = (0x60006e1c, 0x90006ef3);
+
 pub fn check_api_level(api_level: u32) -> Result<(), SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x60000137, 0x900001c6);
     let params = [
-        api_level,
+        api_level as usize,
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(CHECK_API_LEVEL_ID, &params)
         .map(|_| ())
 }
 
 pub fn os_sched_exit(exit_code: u32) -> Result<(), SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x60005fe1, 0x90005f6f);
     let params = [
-        exit_code,
+        exit_code as usize,
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(OS_SCHED_EXIT_ID, &params)
         .map(|_| ())
 }
 
 pub fn os_ux(params_bytes: &[u8]) -> Result<u32, SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x60006158, 0x9000611f);
     let params = [
-        params_bytes.as_ptr() as u32,
+        params_bytes.as_ptr() as usize,
+    ];
+    supervisor_call(OS_UX_ID, &params)
+}
+
+pub fn nvm_write(dst: *mut u8, src: &[u8]) -> Result<(), SystemError> {
+    let params = [
+        dst as usize,
+        src.as_ptr() as usize,
+        src.len(),
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(NVM_WRITE_ID, &params)
+        .map(|_| ())
 }
 
 pub fn cx_rng(buf: &mut [u8]) -> Result<(), SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x6000052c, 0x90000567);
     let params = [
-        buf.as_ptr() as u32,
-        buf.len() as u32,
+        buf.as_ptr() as usize,
+        buf.len(),
+    ];
+    supervisor_call(CX_RNG_ID, &params)
+        .map(|_| ())
+}
+
+#[inline(always)]
+fn ptr_or_null(buf: &[u8]) -> usize {
+    if buf.len() > 0 {
+        buf.as_ptr() as usize
+    } else {
+        0
+    }
+}
+
+pub fn cx_hash(ctx: *mut u8, mode: u32, data: &[u8], out: &mut [u8]) -> Result<usize, SystemError> {
+    let params = [
+        ctx as usize,
+        mode as usize,
+        data.as_ptr() as usize,
+        data.len(),
+        ptr_or_null(out),
+    ];
+    supervisor_call(CX_HASH_ID, &params)
+        .map(|r| r as usize)
+}
+
+pub fn cx_ripemd160_init(ctx: *mut u8) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+    ];
+    supervisor_call(CX_RIPEMD160_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_sha256_init(ctx: *mut u8) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+    ];
+    supervisor_call(CX_SHA256_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_sha512_init(ctx: *mut u8) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+    ];
+    supervisor_call(CX_SHA512_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_keccak_init(ctx: *mut u8, size_bits: u32) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+        size_bits as usize,
+    ];
+    supervisor_call(CX_KECCAK_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_blake2b_init(ctx: *mut u8, size_bits: u32) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+        size_bits as usize,
+    ];
+    supervisor_call(CX_BLAKE2B_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_hmac_sha256_init(ctx: *mut u8, key: &[u8]) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+        key.as_ptr() as usize,
+        key.len(),
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(CX_HMAC_SHA256_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_hmac_sha512_init(ctx: *mut u8, key: &[u8]) -> Result<(), SystemError> {
+    let params = [
+        ctx as usize,
+        key.as_ptr() as usize,
+        key.len(),
+    ];
+    supervisor_call(CX_HMAC_SHA512_INIT_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_hmac(ctx: *mut u8, mode: u32, data: &[u8], out: &mut [u8]) -> Result<usize, SystemError> {
+    let params = [
+        ctx as usize,
+        mode as usize,
+        data.as_ptr() as usize,
+        data.len(),
+        ptr_or_null(out),
+    ];
+    supervisor_call(CX_HMAC_ID, &params)
+        .map(|r| r as usize)
+}
+
+pub fn cx_ecfp_init_private_key(curve: u32, raw_key: &[u8], key: *mut u8) -> Result<(), SystemError> {
+    let params = [
+        curve as usize,
+        raw_key.as_ptr() as usize,
+        raw_key.len(),
+        key as usize,
+    ];
+    supervisor_call(CX_ECFP_INIT_PRIVATE_KEY_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_ecfp_generate_pair(curve: u32, public_key: *mut u8, private_key: *mut u8, keep_private: bool) -> Result<(), SystemError> {
+    let params = [
+        curve as usize,
+        public_key as usize,
+        private_key as usize,
+        keep_private as usize,
+    ];
+    supervisor_call(CX_ECFP_GENERATE_PAIR_ID, &params)
+        .map(|_| ())
+}
+
+pub fn cx_ecdsa_sign(private_key: *const u8, mode: u32, hash_id: u32, hash: &[u8], sig: &mut [u8], info: &mut u32) -> Result<usize, SystemError> {
+    let params = [
+        private_key as usize,
+        mode as usize,
+        hash_id as usize,
+        hash.as_ptr() as usize,
+        hash.len(),
+        sig.as_mut_ptr() as usize,
+        info as *mut u32 as usize,
+    ];
+    supervisor_call(CX_ECDSA_SIGN_ID, &params)
+        .map(|r| r as usize)
+}
+
+pub fn cx_eddsa_sign(private_key: *const u8, mode: u32, hash_id: u32, message: &[u8], sig: &mut [u8], info: &mut u32) -> Result<usize, SystemError> {
+    let params = [
+        private_key as usize,
+        mode as usize,
+        hash_id as usize,
+        message.as_ptr() as usize,
+        message.len(),
+        0, // context
+        0, // context length
+        sig.as_mut_ptr() as usize,
+        info as *mut u32 as usize,
+    ];
+    supervisor_call(CX_EDDSA_SIGN_ID, &params)
+        .map(|r| r as usize)
+}
+
+pub fn os_perso_derive_node_bip32(curve: u32, path: &[u32], private_key: &mut [u8], chain_code: &mut [u8]) -> Result<(), SystemError> {
+    let params = [
+        curve as usize,
+        path.as_ptr() as usize,
+        path.len(),
+        private_key.as_mut_ptr() as usize,
+        ptr_or_null(chain_code),
+    ];
+    supervisor_call(OS_PERSO_DERIVE_NODE_BIP32_ID, &params)
         .map(|_| ())
 }
 
 pub fn io_seproxyhal_spi_is_status_sent() -> Result<bool, SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x60006fcf, 0x90006f7f);
     let params = [];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(IO_SEPROXYHAL_SPI_IS_STATUS_SENT_ID, &params)
         .map(|r| r != 0)
 }
 
 pub fn io_seproxyhal_spi_recv(buf: &mut [u8], flags: u32) -> Result<usize, SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x600070d1, 0x9000702b);
     let params = [
-        buf.as_ptr() as u32,
-        buf.len() as u32,
-        flags,
+        buf.as_ptr() as usize,
+        buf.len(),
+        flags as usize,
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(IO_SEPROXYHAL_SPI_RECV_ID, &params)
         .map(|r| r as usize)
 }
 
 pub fn io_seproxyhal_spi_send(buf: &[u8]) -> Result<(), SystemError> {
-    const SYSCALL_ID: (u32, u32) = (0x60006e1c, 0x90006ef3);
     let params = [
-        buf.as_ptr() as u32,
-        buf.len() as u32,
+        buf.as_ptr() as usize,
+        buf.len(),
     ];
-    supervisor_call(SYSCALL_ID, &params)
+    supervisor_call(IO_SEPROXYHAL_SPI_SEND_ID, &params)
         .map(|_| ())
 }
 
+#[cfg(target_arch = "arm")]
 #[repr(C)]
 struct try_context {
     jmp_buf: [u32; 10],
     exception: u16,
 }
 
+#[cfg(target_arch = "arm")]
 extern {
     #[link_name = "llvm.eh.sjlj.setjmp"]
     fn setjmp(jmp_buf: *mut u8) -> i32;
 }
 
+#[cfg(target_arch = "arm")]
 #[inline(always)]
-fn supervisor_call(syscall_id: (u32, u32), params: &[u32]) -> Result<u32, SystemError> {
+fn supervisor_call(syscall_id: (u32, u32), params: &[usize]) -> Result<u32, SystemError> {
     let ret_id: u32;
     let ret_val: u32;
     // Supervisor expects the exception handling context
@@ -122,3 +312,9 @@ fn supervisor_call(syscall_id: (u32, u32), params: &[u32]) -> Result<u32, System
         }
     }
 }
+
+// Off the device the syscalls are served by the in-process simulator
+#[cfg(not(target_arch = "arm"))]
+fn supervisor_call(syscall_id: (u32, u32), params: &[usize]) -> Result<u32, SystemError> {
+    ::simulator::supervisor_call(syscall_id, params)
+}
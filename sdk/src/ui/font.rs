@@ -0,0 +1,51 @@
+//! Glyph advance widths of the fonts built into the BOLOS UI, in pixels.
+//! The tables cover the printable ASCII range, the device renders text
+//! byte by byte and skips everything outside of it.
+//!
+//! The widths come from the `char_width` of the character tables in the
+//! BAGL font files of the Nano S SDK, `lib_bagl/src/bagl_font_*.inc`.
+
+use pic::Pic;
+
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+const CHAR_COUNT: usize = LAST_CHAR as usize - FIRST_CHAR as usize + 1;
+
+// OpenSansLight16px, bagl_font_open_sans_light_16px.inc
+pub static LIGHT_16PX: [u8; CHAR_COUNT] = [
+    4, 4, 6, 10, 9, 13, 11, 3, 4, 4, 8, 9, 4, 5, 4, 6, // 0x20..0x2F
+    9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 4, 4, 9, 9, 9, 7, // 0x30..0x3F
+    14, 10, 10, 10, 11, 9, 8, 11, 11, 4, 4, 10, 8, 14, 12, 12, // 0x40..0x4F
+    9, 12, 10, 9, 9, 11, 9, 14, 9, 9, 9, 5, 6, 5, 8, 7, // 0x50..0x5F
+    9, 9, 10, 8, 10, 9, 5, 9, 10, 4, 4, 8, 4, 15, 10, 9, // 0x60..0x6F
+    10, 10, 7, 8, 5, 10, 8, 11, 8, 8, 7, 6, 9, 6, 9, // 0x70..0x7E
+];
+
+// OpenSansRegular11px, bagl_font_open_sans_regular_11px.inc
+pub static REGULAR_11PX: [u8; CHAR_COUNT] = [
+    3, 3, 4, 7, 6, 9, 8, 2, 3, 3, 6, 6, 3, 4, 3, 4, // 0x20..0x2F
+    6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 3, 3, 6, 6, 6, 5, // 0x30..0x3F
+    10, 7, 7, 7, 8, 6, 6, 8, 8, 3, 3, 7, 6, 10, 8, 9, // 0x40..0x4F
+    7, 9, 7, 6, 6, 8, 6, 10, 6, 6, 6, 4, 4, 4, 6, 5, // 0x50..0x5F
+    7, 6, 7, 5, 7, 6, 4, 6, 7, 3, 3, 6, 3, 11, 7, 7, // 0x60..0x6F
+    7, 7, 5, 5, 4, 7, 6, 8, 6, 6, 5, 4, 6, 4, 6, // 0x70..0x7E
+];
+
+// OpenSansExtraBold11px, bagl_font_open_sans_extrabold_11px.inc
+pub static EXTRA_BOLD_11PX: [u8; CHAR_COUNT] = [
+    3, 3, 5, 8, 7, 10, 9, 3, 3, 3, 7, 7, 3, 4, 3, 5, // 0x20..0x2F
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 3, 3, 7, 7, 7, 5, // 0x30..0x3F
+    11, 8, 8, 8, 9, 7, 6, 9, 9, 3, 3, 8, 7, 11, 9, 10, // 0x40..0x4F
+    8, 10, 8, 7, 7, 9, 7, 11, 7, 7, 7, 4, 5, 4, 7, 5, // 0x50..0x5F
+    7, 7, 8, 6, 8, 7, 4, 7, 8, 3, 3, 7, 3, 12, 8, 8, // 0x60..0x6F
+    8, 8, 5, 6, 4, 8, 6, 9, 7, 6, 6, 5, 7, 5, 7, // 0x70..0x7E
+];
+
+/// Width of the character in the font, characters that the font doesn't
+/// have aren't drawn and take no space.
+pub fn char_width(table: &'static [u8; CHAR_COUNT], c: char) -> usize {
+    if c < FIRST_CHAR || c > LAST_CHAR {
+        return 0;
+    }
+    table.pic()[c as usize - FIRST_CHAR as usize] as usize
+}
@@ -285,4 +285,51 @@ impl<'a, I, A> Controller<'a, I, A> {
             ControllerState::Done => {},
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Menu;
+
+    impl Store for Menu {
+        type Action = BasicAction;
+    }
+
+    impl Delegate<u8> for Menu {
+        fn prepare_menu_item(&self, ctrl: &mut Controller<u8, BasicAction>) {
+            ctrl.add_item(0, || ItemSpec{
+                icon: Some(ui::SystemIcon::DashboardBadge.into()),
+                line_1: "Quit app",
+                ..Default::default()
+            });
+        }
+    }
+
+    fn view_at(index: usize) -> Option<ui::View<'static>> {
+        let mut ctrl = ui::Controller::new(index);
+        prepare_menu(0, &Menu, &mut ctrl);
+        ctrl.target_view
+    }
+
+    #[test]
+    fn centers_icon_and_text_together() {
+        // 14px icon, 7px of spacing and 51px of text centered in 100px
+        match view_at(1) {
+            Some(ui::View::Icon(ref icon)) => {
+                assert_eq!(icon.position.x, 14 + 14);
+                assert_eq!(icon.position.y, 9);
+            },
+            _ => panic!("Second view isn't the icon"),
+        }
+        match view_at(2) {
+            Some(ui::View::LabelLine(ref label)) => {
+                assert_eq!(label.text, "Quit app");
+                assert_eq!(label.frame.x, 14 + 14 + 21);
+                assert_eq!(label.frame.width, 100 - 14 - 21);
+            },
+            _ => panic!("Third view isn't the label"),
+        }
+    }
+}
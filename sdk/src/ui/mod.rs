@@ -1,5 +1,8 @@
 mod bolos;
+mod font;
 pub mod menu;
+pub mod progress;
+pub mod review;
 
 use core::cmp::{min, max};
 use core::marker::PhantomData;
@@ -10,7 +13,7 @@ use seproxyhal::Channel;
 use seproxyhal::event::{Event, ButtonPushEvent};
 use seproxyhal::status::{
     ScreenDisplayStatus, ScreenDisplayStatusTypeId, ScreenDisplayShapeStatus,
-    ScreenDisplayTextStatus, ScreenDisplaySystemIconStatus, ScreenDisplayCustomIconStatus,
+    ScreenDisplayTextStatus, ScreenDisplayButtonStatus, ScreenDisplaySystemIconStatus, ScreenDisplayCustomIconStatus,
 };
 use state::Store;
 
@@ -323,9 +326,12 @@ impl<'a, A> Controller<'a, A>
 
 pub trait Delegate: Store {
     fn ui_version(&self) -> u16;
-    fn prepare_ui(&self, ctrl: &mut Controller<Self::Action>);
+    /// Adds the views of the current screen to the controller, the views
+    /// can borrow text from the delegate
+    fn prepare_ui<'a>(&'a self, ctrl: &mut Controller<'a, Self::Action>);
 }
 
+#[derive(Copy, Clone)]
 pub enum FillMode {
     NoFill,
     Fill,
@@ -342,6 +348,7 @@ impl FillMode {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct Color(u32);
 
 impl Color {
@@ -447,6 +454,119 @@ impl<'a> Into<View<'a>> for RectangleView {
     }
 }
 
+/// Filled line, the frame determines the thickness of it as well
+pub struct LineView {
+    pub frame: Frame,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl LineView {
+    pub fn horizontal(x: i16, y: i16, width: u16) -> Self {
+        Self{
+            frame: Frame{ x, y, width, height: 1 },
+            ..Default::default()
+        }
+    }
+
+    pub fn vertical(x: i16, y: i16, height: u16) -> Self {
+        Self{
+            frame: Frame{ x, y, width: 1, height },
+            ..Default::default()
+        }
+    }
+
+    fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
+        ScreenDisplayShapeStatus{
+            type_id: ScreenDisplayStatusTypeId::Line,
+            user_id,
+            x: self.frame.x,
+            y: self.frame.y,
+            width: self.frame.width,
+            height: self.frame.height,
+            stroke: 0,
+            radius: 0,
+            fill: FillMode::Fill.to_wire_format(),
+            foreground_color: self.foreground.to_wire_format(),
+            background_color: self.background.to_wire_format(),
+        }.into()
+    }
+}
+
+impl Default for LineView {
+    fn default() -> Self {
+        Self{
+            frame: Default::default(),
+            foreground: Color::white(),
+            background: Color::black(),
+        }
+    }
+}
+
+impl<'a> Into<View<'a>> for LineView {
+    fn into(self) -> View<'a> {
+        View::Line(self)
+    }
+}
+
+pub struct CircleView {
+    pub center: Position,
+    pub radius: u8,
+    pub stroke: u8,
+    pub fill: FillMode,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl CircleView {
+    pub fn new(center: Position, radius: u8) -> Self {
+        Self{
+            center,
+            radius,
+            ..Default::default()
+        }
+    }
+
+    fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
+        // The circle is positioned by the top left corner of its bounds
+        let radius = self.radius as i16;
+        let size = 2 * self.radius as u16 + 1;
+
+        ScreenDisplayShapeStatus{
+            type_id: ScreenDisplayStatusTypeId::Circle,
+            user_id,
+            x: self.center.x - radius,
+            y: self.center.y - radius,
+            width: size,
+            height: size,
+            stroke: self.stroke,
+            radius: self.radius,
+            fill: self.fill.to_wire_format(),
+            foreground_color: self.foreground.to_wire_format(),
+            background_color: self.background.to_wire_format(),
+        }.into()
+    }
+}
+
+impl Default for CircleView {
+    fn default() -> Self {
+        Self{
+            center: Default::default(),
+            radius: 0,
+            stroke: 1,
+            fill: FillMode::NoFill,
+            foreground: Color::white(),
+            background: Color::black(),
+        }
+    }
+}
+
+impl<'a> Into<View<'a>> for CircleView {
+    fn into(self) -> View<'a> {
+        View::Circle(self)
+    }
+}
+
 pub enum SystemIcon {
     Check,
     Cross,
@@ -668,9 +788,48 @@ impl TextFont {
         }
     }
 
+    #[cfg(not(target_arch="arm"))]
+    pub(crate) fn from_wire_format(font_id: u16) -> Option<Self> {
+        if font_id == 9 {
+            Some(TextFont::OpenSansLight16px)
+        } else if font_id == 10 {
+            Some(TextFont::OpenSansRegular11px)
+        } else if font_id == 8 {
+            Some(TextFont::OpenSansExtraBold11px)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn width_for_char(&self, c: char) -> usize {
+        let widths = match self {
+            &TextFont::OpenSansLight16px => &font::LIGHT_16PX,
+            &TextFont::OpenSansRegular11px => &font::REGULAR_11PX,
+            &TextFont::OpenSansExtraBold11px => &font::EXTRA_BOLD_11PX,
+        };
+        font::char_width(widths, c)
+    }
+
     fn width_for_text(&self, text: &str) -> usize {
-        let avg_char_width = 7;
-        text.pic().chars().count() * avg_char_width
+        text.pic().chars().map(|c| self.width_for_char(c)).sum()
+    }
+
+    // Distance between the baselines of consecutive lines
+    pub(crate) fn line_height(&self) -> u16 {
+        match self {
+            &TextFont::OpenSansLight16px => 18,
+            &TextFont::OpenSansRegular11px => 12,
+            &TextFont::OpenSansExtraBold11px => 12,
+        }
+    }
+
+    // Distance from the top of the line to the baseline
+    pub(crate) fn ascent(&self) -> u16 {
+        match self {
+            &TextFont::OpenSansLight16px => 13,
+            &TextFont::OpenSansRegular11px => 9,
+            &TextFont::OpenSansExtraBold11px => 9,
+        }
     }
 }
 
@@ -753,18 +912,451 @@ impl<'a> Into<View<'a>> for LabelLineView<'a> {
     }
 }
 
+/// Text that is positioned within the frame, unlike `LabelLineView` which
+/// is positioned by the baseline of the text
+pub struct LabelView<'a> {
+    pub frame: Frame,
+    pub font: TextFont,
+    pub horizontal_alignment: TextHorizontalAlignment,
+    pub vertical_alignment: TextVerticalAlignment,
+    pub foreground: Color,
+    pub background: Color,
+    pub fill: FillMode,
+    pub text: &'a str,
+}
+
+impl<'a> LabelView<'a> {
+    pub fn new(frame: Frame, text: &'a str) -> Self {
+        Self{
+            frame,
+            text,
+            ..Default::default()
+        }
+    }
+
+    fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
+        let font_id = self.font.to_wire_format()
+            | self.horizontal_alignment.to_wire_format()
+            | self.vertical_alignment.to_wire_format();
+
+        ScreenDisplayTextStatus{
+            type_id: ScreenDisplayStatusTypeId::Label,
+            user_id,
+            x: self.frame.x,
+            y: self.frame.y,
+            width: self.frame.width,
+            height: self.frame.height,
+            scroll_delay: 0,
+            scroll_speed: 0,
+            fill: self.fill.to_wire_format(),
+            foreground_color: self.foreground.to_wire_format(),
+            background_color: self.background.to_wire_format(),
+            font_id,
+            text: self.text,
+        }.into()
+    }
+}
+
+impl<'a> Default for LabelView<'a> {
+    fn default() -> Self {
+        Self{
+            frame: Default::default(),
+            font: TextFont::OpenSansRegular11px,
+            horizontal_alignment: TextHorizontalAlignment::Left,
+            vertical_alignment: TextVerticalAlignment::Top,
+            foreground: Color::white(),
+            background: Color::black(),
+            fill: FillMode::NoFill,
+            text: "",
+        }
+    }
+}
+
+impl<'a> Into<View<'a>> for LabelView<'a> {
+    fn into(self) -> View<'a> {
+        View::Label(self)
+    }
+}
+
+/// Rectangle with the text centered in it. When filled, the text is drawn
+/// with the background color.
+pub struct ButtonView<'a> {
+    pub frame: Frame,
+    pub stroke: u8,
+    pub radius: u8,
+    pub fill: FillMode,
+    pub foreground: Color,
+    pub background: Color,
+    pub font: TextFont,
+    pub text: &'a str,
+}
+
+impl<'a> ButtonView<'a> {
+    pub fn new(frame: Frame, text: &'a str) -> Self {
+        Self{
+            frame,
+            text,
+            ..Default::default()
+        }
+    }
+
+    fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
+        let font_id = self.font.to_wire_format()
+            | TextHorizontalAlignment::Center.to_wire_format()
+            | TextVerticalAlignment::Middle.to_wire_format();
+
+        ScreenDisplayButtonStatus{
+            user_id,
+            x: self.frame.x,
+            y: self.frame.y,
+            width: self.frame.width,
+            height: self.frame.height,
+            stroke: self.stroke,
+            radius: self.radius,
+            fill: self.fill.to_wire_format(),
+            foreground_color: self.foreground.to_wire_format(),
+            background_color: self.background.to_wire_format(),
+            font_id,
+            text: self.text,
+        }.into()
+    }
+}
+
+impl<'a> Default for ButtonView<'a> {
+    fn default() -> Self {
+        Self{
+            frame: Default::default(),
+            stroke: 1,
+            radius: 0,
+            fill: FillMode::Outline,
+            foreground: Color::white(),
+            background: Color::black(),
+            font: TextFont::OpenSansExtraBold11px,
+            text: "",
+        }
+    }
+}
+
+impl<'a> Into<View<'a>> for ButtonView<'a> {
+    fn into(self) -> View<'a> {
+        View::Button(self)
+    }
+}
+
+/// Text that is word-wrapped to the width of the frame, each line is
+/// added to the controller as a separate `LabelLineView`. Lines that
+/// don't fit the height of the frame are left out, which the app can use
+/// to split long texts over several screens.
+///
+/// ```ignore
+/// let paragraph = ui::ParagraphView{
+///     frame: ui::Frame{ x: 4, y: 4, width: 120, height: 24 },
+///     text: self.message(),
+///     ..Default::default()
+/// };
+/// if let Some(offset) = paragraph.prepare(ctrl) {
+///     // Text from `offset` onwards goes to the next screen
+/// }
+/// ```
+pub struct ParagraphView<'a> {
+    pub frame: Frame,
+    pub font: TextFont,
+    pub horizontal_alignment: TextHorizontalAlignment,
+    pub foreground: Color,
+    pub background: Color,
+    pub fill: FillMode,
+    pub text: &'a str,
+}
+
+impl<'a> ParagraphView<'a> {
+    /// Adds the lines that fit the frame to the controller. Returns the
+    /// byte offset in the text where the overflowing part starts.
+    pub fn prepare<A>(&self, ctrl: &mut Controller<'a, A>) -> Option<usize>
+        where A: Copy
+    {
+        let line_height = self.font.line_height();
+        let mut lines = self.lines();
+
+        for index in 0..self.max_lines() {
+            let (start, end) = match lines.next() {
+                Some(line) => line,
+                None => return None,
+            };
+            // The offsets are valid for the untranslated text as well,
+            // which the views translate themselves
+            let text = &self.text[start..end];
+            let baseline = self.frame.y
+                + (self.font.ascent() + index * line_height) as i16;
+
+            ctrl.add_view(|| LabelLineView{
+                frame: Frame{
+                    x: self.frame.x,
+                    y: baseline,
+                    width: self.frame.width,
+                    height: line_height,
+                },
+                font: self.font,
+                horizontal_alignment: self.horizontal_alignment,
+                foreground: self.foreground,
+                background: self.background,
+                fill: self.fill,
+                text,
+                ..Default::default()
+            }.into());
+        }
+
+        lines.next().map(|(start, _)| start)
+    }
+
+    /// Byte offset in the text where the part that doesn't fit the frame
+    /// starts, the same value that `prepare` returns
+    pub fn overflow(&self) -> Option<usize> {
+        self.lines()
+            .nth(self.max_lines() as usize)
+            .map(|(start, _)| start)
+    }
+
+    fn max_lines(&self) -> u16 {
+        self.frame.height / self.font.line_height()
+    }
+
+    fn lines(&self) -> Lines {
+        Lines{
+            text: self.text.pic(),
+            font: self.font,
+            width: self.frame.width as usize,
+            offset: 0,
+            wrapped: false,
+        }
+    }
+}
+
+impl<'a> Default for ParagraphView<'a> {
+    fn default() -> Self {
+        Self{
+            frame: Default::default(),
+            font: TextFont::OpenSansRegular11px,
+            horizontal_alignment: TextHorizontalAlignment::Left,
+            foreground: Color::white(),
+            background: Color::black(),
+            fill: FillMode::NoFill,
+            text: "",
+        }
+    }
+}
+
+/// Splits the text into byte ranges of the lines that fit the width.
+/// Lines are broken at spaces and newlines, words that are too long to
+/// fit a line on their own are broken anywhere.
+struct Lines<'a> {
+    text: &'a str,
+    font: TextFont,
+    width: usize,
+    offset: usize,
+    // Whether the previous line was broken at a space
+    wrapped: bool,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.text;
+        let mut start = self.offset;
+        if self.wrapped {
+            // The spaces the line was broken at aren't shown at all
+            while text[start..].starts_with(' ') {
+                start += 1;
+            }
+        }
+        if start >= text.len() {
+            return None;
+        }
+
+        let mut width = 0;
+        let mut end = text.len();
+        let mut next = text.len();
+        let mut last_space = None;
+        self.wrapped = false;
+
+        for (i, c) in text[start..].char_indices() {
+            let i = start + i;
+            if c == '\n' {
+                end = i;
+                next = i + 1;
+                break;
+            }
+
+            let char_width = self.font.width_for_char(c);
+            // Always put at least one character on a line
+            if i > start && width + char_width > self.width {
+                end = match last_space {
+                    Some(space) if c != ' ' && space > start => space,
+                    _ => i,
+                };
+                next = end;
+                self.wrapped = true;
+                break;
+            }
+            if c == ' ' {
+                last_space = Some(i);
+            }
+            width += char_width;
+        }
+
+        // Trailing spaces would throw off the alignment
+        while end > start && text[..end].ends_with(' ') {
+            end -= 1;
+        }
+
+        self.offset = next;
+        Some((start, end))
+    }
+}
+
 pub enum View<'a> {
     Rectangle(RectangleView),
+    Line(LineView),
+    Circle(CircleView),
     Icon(IconView<'a>),
+    Label(LabelView<'a>),
     LabelLine(LabelLineView<'a>),
+    Button(ButtonView<'a>),
 }
 
 impl<'a> View<'a> {
-    fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
+    pub(crate) fn to_display_status(&self, user_id: u8) -> ScreenDisplayStatus {
         match self {
             &View::Rectangle(ref v) => v.to_display_status(user_id),
+            &View::Line(ref v) => v.to_display_status(user_id),
+            &View::Circle(ref v) => v.to_display_status(user_id),
             &View::Icon(ref v) => v.to_display_status(user_id),
+            &View::Label(ref v) => v.to_display_status(user_id),
             &View::LabelLine(ref v) => v.to_display_status(user_id),
+            &View::Button(ref v) => v.to_display_status(user_id),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    const FONT: TextFont = TextFont::OpenSansRegular11px;
+
+    fn paragraph(text: &str, width: usize, lines: u16) -> ParagraphView {
+        ParagraphView{
+            frame: Frame{ x: 0, y: 0, width: width as u16, height: lines * FONT.line_height() },
+            font: FONT,
+            text,
+            ..Default::default()
+        }
+    }
+
+    fn lines<'a>(text: &'a str, width: usize) -> Vec<&'a str> {
+        paragraph(text, width, 0).lines()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn text_widths_of_known_strings() {
+        // Summed from the font tables by hand
+        assert_eq!(TextFont::OpenSansRegular11px.width_for_text("Lorem ipsum"), 71);
+        assert_eq!(TextFont::OpenSansExtraBold11px.width_for_text("Quit app"), 51);
+        assert_eq!(TextFont::OpenSansLight16px.width_for_text("Demo"), 44);
+        // Characters outside of the fonts aren't drawn
+        assert_eq!(TextFont::OpenSansRegular11px.width_for_text("\u{e4}\t"), 0);
+    }
+
+    #[test]
+    fn scroll_time_of_known_string() {
+        let mut label = LabelLineView{
+            frame: Frame{ x: 0, y: 0, width: 50, height: 12 },
+            scroll: ScrollMode::Once{ delay: Duration::from_secs(1), speed: 26 },
+            text: "Lorem ipsum",
+            ..Default::default()
+        };
+        // Scrolls 21px there and back at 26px/s, waiting a second at both ends
+        assert_eq!(label.estimate_scroll_time(), Some(Duration::from_millis(3614)));
+
+        label.frame.width = 71;
+        assert_eq!(label.estimate_scroll_time(), Some(Duration::zero()));
+
+        label.scroll = ScrollMode::Infinite{ delay: Duration::from_secs(1), speed: 26 };
+        assert_eq!(label.estimate_scroll_time(), None);
+    }
+
+    #[test]
+    fn lines_break_at_spaces() {
+        let width = FONT.width_for_text("Lorem ipsum");
+        assert_eq!(lines("Lorem ipsum dolor sit", width), ["Lorem ipsum", "dolor sit"]);
+        assert_eq!(lines("Lorem", width), ["Lorem"]);
+        assert_eq!(lines("", width), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn lines_break_long_words_anywhere() {
+        let width = FONT.width_for_text("abcd");
+        assert_eq!(lines("abcdefghij", width), ["abcd", "efgh", "ij"]);
+        // Words after a space move to the next line before being broken
+        assert_eq!(lines("ab cdefghij", width), ["ab", "cdef", "ghij"]);
+
+        // Lines have at least one character, even if it doesn't fit
+        assert_eq!(lines("abc", 1), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn lines_skip_runs_of_spaces() {
+        let width = FONT.width_for_text("Lorem");
+        assert_eq!(lines("Lorem     ipsum", width), ["Lorem", "ipsum"]);
+        assert_eq!(lines("Lorem  ", width), ["Lorem"]);
+
+        // Spaces aren't dropped at the start of the text or a new line
+        let width = FONT.width_for_text("  Lorem ipsum");
+        assert_eq!(lines("  Lorem\n ipsum", width), ["  Lorem", " ipsum"]);
+    }
+
+    #[test]
+    fn lines_break_at_newlines() {
+        let width = FONT.width_for_text("Lorem ipsum dolor");
+        assert_eq!(lines("Lorem\nipsum", width), ["Lorem", "ipsum"]);
+        assert_eq!(lines("Lorem\n\nipsum\n", width), ["Lorem", "", "ipsum"]);
+    }
+
+    #[test]
+    fn overflow_starts_after_the_last_line() {
+        let text = "Lorem ipsum dolor sit amet";
+        let view = paragraph(text, FONT.width_for_text("Lorem ipsum"), 2);
+        assert_eq!(view.overflow(), Some(text.find("amet").unwrap()));
+
+        let mut ctrl = Controller::<()>::new(1);
+        assert_eq!(view.prepare(&mut ctrl), view.overflow());
+        assert_eq!(ctrl.current_index, 2);
+        match ctrl.target_view {
+            Some(View::LabelLine(ref line)) => {
+                assert_eq!(line.text, "dolor sit");
+                assert_eq!(line.frame.y, (FONT.ascent() + FONT.line_height()) as i16);
+            },
+            _ => panic!("Second line isn't a label"),
+        }
+
+        let view = paragraph(text, FONT.width_for_text(text), 2);
+        assert_eq!(view.overflow(), None);
+        assert_eq!(view.prepare(&mut Controller::<()>::new(0)), None);
+    }
+
+    #[test]
+    fn zero_height_frame_overflows_immediately() {
+        let view = paragraph("Lorem ipsum", 128, 0);
+        let mut ctrl = Controller::<()>::new(0);
+        assert_eq!(view.prepare(&mut ctrl), Some(0));
+        assert_eq!(view.overflow(), Some(0));
+        assert_eq!(ctrl.current_index, 0);
+
+        let view = paragraph("", 128, 0);
+        assert_eq!(view.prepare(&mut ctrl), None);
+        assert_eq!(view.overflow(), None);
+    }
+}
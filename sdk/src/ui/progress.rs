@@ -0,0 +1,223 @@
+use core::cmp::min;
+use time::Duration;
+use ui;
+
+const SCREEN_WIDTH: u16 = 128;
+const BAR_X: i16 = 14;
+const BAR_Y: i16 = 20;
+const BAR_WIDTH: u16 = 100;
+const BAR_HEIGHT: u16 = 7;
+const SPINNER_DOTS: u8 = 5;
+const SPINNER_Y: i16 = 23;
+const SPINNER_SPACING: i16 = 10;
+const SPINNER_INTERVAL_MS: usize = 200;
+
+pub trait ProgressAction {
+    /// Action that the delegate should respond to by calling
+    /// `Progress::advance` and redrawing the UI
+    fn action_for_progress_tick() -> Self;
+}
+
+#[derive(Copy, Clone)]
+enum Mode {
+    Spinner,
+    Bar{ completed: u32, total: u32 },
+}
+
+/// Progress of a long-running operation, kept in the app state. The
+/// spinner is animated with the auto action that `prepare_progress` sets
+/// up, the progress bar only changes when the app reports progress.
+///
+/// ```ignore
+/// // In Store::process_action
+/// match action {
+///     Action::ProgressTick => {
+///         self.progress.advance();
+///         self.ui_version += 1;
+///     },
+///     ...
+/// }
+///
+/// // In ui::Delegate::prepare_ui
+/// progress::prepare_progress(&self.progress, "Signing...", ctrl);
+/// ```
+pub struct Progress {
+    mode: Mode,
+    frame: u8,
+}
+
+impl Progress {
+    /// Progress of an operation that doesn't know how long it takes
+    pub fn spinner() -> Self {
+        Self{
+            mode: Mode::Spinner,
+            frame: 0,
+        }
+    }
+
+    /// Progress of an operation that consists of `total` steps, such as
+    /// the number of APDU chunks
+    pub fn bar(total: u32) -> Self {
+        Self{
+            mode: Mode::Bar{ completed: 0, total },
+            frame: 0,
+        }
+    }
+
+    /// Updates the number of completed steps, returns true when the bar
+    /// needs to be redrawn
+    pub fn set_completed(&mut self, completed: u32) -> bool {
+        match self.mode {
+            Mode::Bar{ completed: old_completed, total } => {
+                let completed = min(completed, total);
+                self.mode = Mode::Bar{ completed, total };
+                bar_width_for(old_completed, total) != bar_width_for(completed, total)
+            },
+            Mode::Spinner => false,
+        }
+    }
+
+    /// Moves the spinner to the next frame
+    pub fn advance(&mut self) {
+        self.frame = (self.frame + 1) % SPINNER_DOTS;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match self.mode {
+            Mode::Bar{ completed, total } => completed >= total,
+            Mode::Spinner => false,
+        }
+    }
+}
+
+// Width of the filled part of the bar, which is inset from the outline
+fn bar_width_for(completed: u32, total: u32) -> u16 {
+    let inner_width = BAR_WIDTH - 4;
+    if total == 0 {
+        return inner_width;
+    }
+    (completed as u64 * inner_width as u64 / total as u64) as u16
+}
+
+pub fn prepare_progress<'a, A>(progress: &'a Progress, text: &'a str, ui_ctrl: &mut ui::Controller<'a, A>)
+    where A: ProgressAction + Copy,
+{
+    ui_ctrl.add_view(|| ui::RectangleView{
+        frame: ui::Frame{ x: 0, y: 0, width: SCREEN_WIDTH, height: 32 },
+        fill: ui::FillMode::Fill,
+        ..Default::default()
+    }.into());
+
+    ui_ctrl.add_view(|| ui::LabelLineView{
+        frame: ui::Frame{ x: 0, y: 12, width: SCREEN_WIDTH, height: 12 },
+        font: ui::TextFont::OpenSansRegular11px,
+        horizontal_alignment: ui::TextHorizontalAlignment::Center,
+        text,
+        ..Default::default()
+    }.into());
+
+    match progress.mode {
+        Mode::Spinner => {
+            for dot in 0..SPINNER_DOTS {
+                let center_x = (SCREEN_WIDTH as i16 - (SPINNER_DOTS as i16 - 1) * SPINNER_SPACING) / 2
+                    + dot as i16 * SPINNER_SPACING;
+                let is_current = dot == progress.frame;
+
+                ui_ctrl.add_view(move || ui::CircleView{
+                    center: ui::Position{ x: center_x, y: SPINNER_Y },
+                    radius: if is_current { 3 } else { 1 },
+                    fill: ui::FillMode::Fill,
+                    ..Default::default()
+                }.into());
+            }
+
+            ui_ctrl.set_auto_action(ui::AutoAction::Countdown{
+                min_wait_time: None,
+                max_wait_time: None,
+                wait_time: Duration::from_millis(SPINNER_INTERVAL_MS),
+                wait_for_scroll: false,
+                action: A::action_for_progress_tick(),
+            });
+        },
+        Mode::Bar{ completed, total } => {
+            ui_ctrl.add_view(|| ui::RectangleView{
+                frame: ui::Frame{ x: BAR_X, y: BAR_Y, width: BAR_WIDTH, height: BAR_HEIGHT },
+                stroke: 1,
+                radius: 2,
+                fill: ui::FillMode::Outline,
+                foreground: ui::Color::white(),
+                background: ui::Color::black(),
+            }.into());
+
+            let bar_width = bar_width_for(completed, total);
+            if bar_width > 0 {
+                ui_ctrl.add_view(move || ui::RectangleView{
+                    frame: ui::Frame{ x: BAR_X + 2, y: BAR_Y + 2, width: bar_width, height: BAR_HEIGHT - 4 },
+                    fill: ui::FillMode::Fill,
+                    foreground: ui::Color::white(),
+                    background: ui::Color::black(),
+                    ..Default::default()
+                }.into());
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    #[test]
+    fn bar_width_is_proportional_to_completed_steps() {
+        assert_eq!(bar_width_for(0, 10), 0);
+        assert_eq!(bar_width_for(1, 3), 32);
+        assert_eq!(bar_width_for(5, 10), 48);
+        assert_eq!(bar_width_for(10, 10), 96);
+        assert_eq!(bar_width_for(u32::max_value(), u32::max_value()), 96);
+    }
+
+    #[test]
+    fn empty_bar_is_complete() {
+        let mut progress = Progress::bar(0);
+        assert!(progress.is_complete());
+        assert_eq!(bar_width_for(0, 0), 96);
+        assert!(!progress.set_completed(5));
+        assert!(progress.is_complete());
+    }
+
+    #[test]
+    fn set_completed_reports_visible_changes() {
+        let mut progress = Progress::bar(200);
+        assert!(!progress.is_complete());
+
+        // Less than a pixel of progress doesn't need a redraw
+        assert!(!progress.set_completed(2));
+        assert!(progress.set_completed(3));
+        assert!(!progress.set_completed(3));
+
+        // Steps past the total are clamped
+        assert!(progress.set_completed(500));
+        assert!(progress.is_complete());
+        assert!(!progress.set_completed(200));
+    }
+
+    #[test]
+    fn spinner_ignores_completed_steps() {
+        let mut progress = Progress::spinner();
+        assert!(!progress.set_completed(1));
+        assert!(!progress.is_complete());
+    }
+
+    #[test]
+    fn spinner_frames_wrap_around() {
+        let mut progress = Progress::spinner();
+        let frames: Vec<u8> = (0..2 * SPINNER_DOTS)
+            .map(|_| {
+                progress.advance();
+                progress.frame
+            })
+            .collect();
+        assert_eq!(frames, [1, 2, 3, 4, 0, 1, 2, 3, 4, 0]);
+    }
+}
@@ -0,0 +1,443 @@
+use core::cmp::max;
+use core::str;
+use pic::Pic;
+use state::BasicAction;
+use ui;
+
+const SCREEN_WIDTH: u16 = 128;
+const TITLE_FONT: ui::TextFont = ui::TextFont::OpenSansRegular11px;
+const VALUE_FONT: ui::TextFont = ui::TextFont::OpenSansExtraBold11px;
+const VALUE_X: i16 = 23;
+const VALUE_WIDTH: u16 = 82;
+// Space between an icon and the label next to it
+const ICON_SPACING: u16 = 6;
+
+// Fits "65535/65535"
+const COUNTER_SIZE: usize = 11;
+
+pub trait ReviewAction {
+    fn action_for_previous_step() -> Self;
+    fn action_for_next_step() -> Self;
+    fn action_for_decision() -> Self;
+}
+
+impl ReviewAction for BasicAction {
+    fn action_for_previous_step() -> Self {
+        BasicAction::Previous
+    }
+
+    fn action_for_next_step() -> Self {
+        BasicAction::Next
+    }
+
+    fn action_for_decision() -> Self {
+        BasicAction::Confirm
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Field<'a> {
+    pub title: &'a str,
+    pub value: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    Field{ index: usize, page: usize },
+    Accept,
+    Reject,
+}
+
+/// Position in a review flow, which shows every field over as many pages
+/// as its value needs and ends with the accept and reject screens.
+///
+/// The same fields have to be passed to every call, the review itself
+/// only keeps track of the current page.
+///
+/// ```ignore
+/// let fields = [
+///     review::Field{ title: "Amount", value: self.amount() },
+///     review::Field{ title: "Address", value: self.address() },
+/// ];
+/// review::prepare_review(&self.review, &fields, ctrl);
+/// ```
+pub struct Review {
+    step: Step,
+    counter: [u8; COUNTER_SIZE],
+    counter_len: usize,
+}
+
+impl Review {
+    pub fn new(fields: &[Field]) -> Self {
+        let mut review = Self{
+            step: Step::Accept,
+            counter: [0; COUNTER_SIZE],
+            counter_len: 0,
+        };
+        let first_step = first_step(fields);
+        review.set_step(first_step, fields);
+        review
+    }
+
+    pub fn step(&self) -> Step {
+        self.step
+    }
+
+    pub fn previous(&mut self, fields: &[Field]) {
+        let step = match self.step {
+            Step::Field{ index, page } if page > 0 => Step::Field{ index, page: page - 1 },
+            Step::Field{ index, .. } if index > 0 => last_page_of(fields, index - 1),
+            Step::Field{ .. } => self.step,
+            Step::Accept if fields.len() > 0 => last_page_of(fields, fields.len() - 1),
+            Step::Accept => Step::Accept,
+            Step::Reject => Step::Accept,
+        };
+        self.set_step(step, fields);
+    }
+
+    pub fn next(&mut self, fields: &[Field]) {
+        let step = match self.step {
+            Step::Field{ index, page } => {
+                if page + 1 < page_count(fields, index) {
+                    Step::Field{ index, page: page + 1 }
+                } else if index + 1 < fields.len() {
+                    Step::Field{ index: index + 1, page: 0 }
+                } else {
+                    Step::Accept
+                }
+            },
+            Step::Accept => Step::Reject,
+            Step::Reject => Step::Reject,
+        };
+        self.set_step(step, fields);
+    }
+
+    fn set_step(&mut self, step: Step, fields: &[Field]) {
+        self.step = step;
+        self.counter_len = match step {
+            Step::Field{ index, page } => {
+                let count = page_count(fields, index);
+                if count > 1 {
+                    write_counter(&mut self.counter, page + 1, count)
+                } else {
+                    0
+                }
+            },
+            _ => 0,
+        };
+    }
+
+    fn counter(&self) -> &str {
+        str::from_utf8(&self.counter[0..self.counter_len]).unwrap_or("")
+    }
+}
+
+pub fn prepare_review<'a, A>(review: &'a Review, fields: &[Field<'a>], ui_ctrl: &mut ui::Controller<'a, A>)
+    where A: ReviewAction + Copy,
+{
+    let step = review.step();
+    let has_previous = step != first_step(fields);
+    let has_next = step != Step::Reject;
+
+    ui_ctrl.set_button_actions(ui::ButtonAction::Map{
+        left: if has_previous {
+            Some(A::action_for_previous_step())
+        } else {
+            None
+        },
+        right: if has_next {
+            Some(A::action_for_next_step())
+        } else {
+            None
+        },
+        both: match step {
+            Step::Accept | Step::Reject => Some(A::action_for_decision()),
+            Step::Field{ .. } => None,
+        },
+    });
+
+    ui_ctrl.add_view(|| ui::RectangleView{
+        frame: ui::Frame{ x: 0, y: 0, width: SCREEN_WIDTH, height: 32 },
+        fill: ui::FillMode::Fill,
+        ..Default::default()
+    }.into());
+
+    if has_previous {
+        ui_ctrl.add_view(|| ui::IconView{
+            position: ui::Position{ x: 3, y: 12 },
+            icon: ui::SystemIcon::Left.into(),
+            ..Default::default()
+        }.into());
+    }
+    if has_next {
+        ui_ctrl.add_view(|| ui::IconView{
+            position: ui::Position{ x: 121, y: 12 },
+            icon: ui::SystemIcon::Right.into(),
+            ..Default::default()
+        }.into());
+    }
+
+    match step {
+        Step::Field{ index, page } => {
+            if let Some(field) = fields.get(index) {
+                prepare_field_page(review.counter(), field, page, ui_ctrl);
+            }
+        },
+        Step::Accept => prepare_decision(ui::SystemIcon::Check, "Accept", ui_ctrl),
+        Step::Reject => prepare_decision(ui::SystemIcon::Cross, "Reject", ui_ctrl),
+    }
+}
+
+fn prepare_field_page<'a, A>(counter: &'a str, field: &Field<'a>, page: usize, ui_ctrl: &mut ui::Controller<'a, A>)
+    where A: Copy,
+{
+    let title = field.title;
+
+    if counter.len() > 0 {
+        // Center the title together with the page counter next to it
+        let title_width = TITLE_FONT.width_for_text(title) as u16;
+        let space_width = TITLE_FONT.width_for_char(' ') as u16;
+        let counter_width = TITLE_FONT.width_for_text(counter) as u16;
+        let total_width = title_width + space_width + counter_width;
+        let offset_x = (max(SCREEN_WIDTH, total_width) - total_width) / 2;
+
+        ui_ctrl.add_view(move || ui::LabelLineView{
+            frame: ui::Frame{ x: offset_x as i16, y: 12, width: title_width, height: 12 },
+            font: TITLE_FONT,
+            text: title,
+            ..Default::default()
+        }.into());
+        ui_ctrl.add_view(move || ui::LabelLineView{
+            frame: ui::Frame{
+                x: (offset_x + title_width + space_width) as i16, y: 12,
+                width: counter_width, height: 12,
+            },
+            font: TITLE_FONT,
+            text: counter,
+            ..Default::default()
+        }.into());
+    } else {
+        ui_ctrl.add_view(move || ui::LabelLineView{
+            frame: ui::Frame{ x: 0, y: 12, width: SCREEN_WIDTH, height: 12 },
+            font: TITLE_FONT,
+            horizontal_alignment: ui::TextHorizontalAlignment::Center,
+            text: title,
+            ..Default::default()
+        }.into());
+    }
+
+    let value = page_text(field.value, page);
+    ui_ctrl.add_view(move || ui::LabelLineView{
+        frame: ui::Frame{ x: VALUE_X, y: 26, width: VALUE_WIDTH, height: 12 },
+        font: VALUE_FONT,
+        horizontal_alignment: ui::TextHorizontalAlignment::Center,
+        text: value,
+        ..Default::default()
+    }.into());
+}
+
+fn prepare_decision<'a, A>(icon: ui::SystemIcon, text: &'a str, ui_ctrl: &mut ui::Controller<'a, A>)
+    where A: Copy,
+{
+    let icon_size = icon.dimensions();
+    let icon_y = (32 - icon_size.height as i16) / 2;
+    let text_x = offset_x_for(icon_size.width + ICON_SPACING, text);
+    let offset_x = text_x - icon_size.width - ICON_SPACING;
+    let text_width = VALUE_FONT.width_for_text(text) as u16;
+
+    ui_ctrl.add_view(move || ui::IconView{
+        position: ui::Position{ x: offset_x as i16, y: icon_y },
+        icon: icon.into(),
+        ..Default::default()
+    }.into());
+    ui_ctrl.add_view(move || ui::LabelLineView{
+        frame: ui::Frame{ x: text_x as i16, y: 19, width: text_width, height: 12 },
+        font: VALUE_FONT,
+        text,
+        ..Default::default()
+    }.into());
+}
+
+// Horizontal offset of the text that centers it together with the
+// content of the given width in front of it
+fn offset_x_for(leading_width: u16, text: &str) -> u16 {
+    let total_width = leading_width + VALUE_FONT.width_for_text(text) as u16;
+    (max(SCREEN_WIDTH, total_width) - total_width) / 2 + leading_width
+}
+
+fn first_step(fields: &[Field]) -> Step {
+    if fields.len() > 0 {
+        Step::Field{ index: 0, page: 0 }
+    } else {
+        Step::Accept
+    }
+}
+
+fn last_page_of(fields: &[Field], index: usize) -> Step {
+    Step::Field{ index, page: page_count(fields, index) - 1 }
+}
+
+fn page_count(fields: &[Field], index: usize) -> usize {
+    let count = match fields.get(index) {
+        Some(field) => Pages::new(field.value).count(),
+        None => 0,
+    };
+    // Empty values still get a page of their own
+    max(count, 1)
+}
+
+fn page_text(value: &str, page: usize) -> &str {
+    match Pages::new(value).nth(page) {
+        // The offsets are valid for the untranslated text as well, which
+        // the views translate themselves
+        Some((start, end)) => &value[start..end],
+        None => "",
+    }
+}
+
+/// Splits the text into byte ranges that fit the value label
+struct Pages<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Pages<'a> {
+    fn new(text: &'a str) -> Self {
+        Self{
+            text: text.pic(),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Pages<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.offset;
+        if start >= self.text.len() {
+            return None;
+        }
+
+        let mut width = 0;
+        let mut end = self.text.len();
+        for (i, c) in self.text[start..].char_indices() {
+            let char_width = VALUE_FONT.width_for_char(c);
+            // Always put at least one character on a page
+            if i > 0 && width + char_width > VALUE_WIDTH as usize {
+                end = start + i;
+                break;
+            }
+            width += char_width;
+        }
+
+        self.offset = end;
+        Some((start, end))
+    }
+}
+
+// Writes "page/count" into the buffer, returning its length
+fn write_counter(buf: &mut [u8; COUNTER_SIZE], page: usize, count: usize) -> usize {
+    let mut len = write_number(&mut buf[..], page);
+    buf[len] = b'/';
+    len += 1;
+    len + write_number(&mut buf[len..], count)
+}
+
+fn write_number(buf: &mut [u8], number: usize) -> usize {
+    let mut digits = [0; 5];
+    let mut number = number;
+    let mut cnt = 0;
+    loop {
+        digits[cnt] = b'0' + (number % 10) as u8;
+        number /= 10;
+        cnt += 1;
+        if number == 0 || cnt == digits.len() {
+            break;
+        }
+    }
+    for i in 0..cnt {
+        buf[i] = digits[cnt - 1 - i];
+    }
+    cnt
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    fn pages(value: &str) -> Vec<&str> {
+        Pages::new(value).map(|(start, end)| &value[start..end]).collect()
+    }
+
+    fn fits(text: &str) -> bool {
+        VALUE_FONT.width_for_text(text) <= VALUE_WIDTH as usize
+    }
+
+    #[test]
+    fn short_values_fit_one_page() {
+        assert_eq!(pages("1.5 BTC"), ["1.5 BTC"]);
+        assert_eq!(pages(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn long_values_are_split_into_full_pages() {
+        let value = "4ecbde854d829816358041c8b393fa4d514ecbde854d829816358041c8b393fa4d51";
+        let pages = pages(value);
+        assert!(pages.len() > 2);
+        assert_eq!(pages.concat(), value);
+        for (i, page) in pages.iter().enumerate() {
+            assert!(fits(page), "Page {} doesn't fit: {}", i, page);
+            // Each page takes as much as fits
+            if let Some(next) = pages.get(i + 1) {
+                let more = format!("{}{}", page, next.chars().next().unwrap());
+                assert!(!fits(&more), "Page {} isn't full: {}", i, page);
+            }
+        }
+    }
+
+    #[test]
+    fn pages_keep_multibyte_characters_whole() {
+        // Characters outside of the font take no space
+        let value = "0ä".repeat(20);
+        let value = value.as_str();
+        let pages = pages(value);
+        assert!(pages.len() > 1);
+        assert_eq!(pages.concat(), value);
+    }
+
+    #[test]
+    fn counter_shows_page_of_multi_page_fields() {
+        let fields = [
+            Field{ title: "Amount", value: "1.5 BTC" },
+            Field{ title: "Address", value: "4ecbde854d829816" },
+        ];
+        assert_eq!(pages(fields[1].value).len(), 2);
+        let mut review = Review::new(&fields);
+        assert!(review.step() == Step::Field{ index: 0, page: 0 });
+        assert_eq!(review.counter(), "");
+
+        review.next(&fields);
+        assert!(review.step() == Step::Field{ index: 1, page: 0 });
+        assert_eq!(review.counter(), "1/2");
+        review.next(&fields);
+        assert!(review.step() == Step::Field{ index: 1, page: 1 });
+        assert_eq!(review.counter(), "2/2");
+
+        review.next(&fields);
+        assert!(review.step() == Step::Accept);
+        assert_eq!(review.counter(), "");
+        review.previous(&fields);
+        assert!(review.step() == Step::Field{ index: 1, page: 1 });
+    }
+
+    #[test]
+    fn counter_fits_largest_numbers() {
+        let mut buf = [0; COUNTER_SIZE];
+        let len = write_counter(&mut buf, 65535, 65535);
+        assert_eq!(&buf[..len], b"65535/65535");
+        let len = write_counter(&mut buf, 1, 10);
+        assert_eq!(&buf[..len], b"1/10");
+    }
+}
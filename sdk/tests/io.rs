@@ -0,0 +1,204 @@
+extern crate bolos;
+
+use bolos::apdu;
+use bolos::seproxyhal::io::{self, Loopback};
+use bolos::simulator::{Simulator, Syscall};
+use bolos::state::Store;
+
+struct App {
+    processed: u32,
+}
+
+impl Store for App {
+    type Action = u8;
+}
+
+impl apdu::Delegate for App {
+    fn class(&self) -> u8 {
+        0xE0
+    }
+
+    fn action_for_instruction(&self, ins: u8) -> Option<Self::Action> {
+        Some(ins)
+    }
+
+    fn process_command(
+        &mut self,
+        _action: Self::Action,
+        _command: &apdu::Command,
+        response: &mut apdu::Response,
+    ) -> Result<(), apdu::StatusWord> {
+        self.processed += 1;
+        response.append_u8(0x42)
+    }
+}
+
+fn run(sim: &mut Simulator, middleware: &mut io::Middleware<App>, loopback: &mut Loopback, app: &mut App) {
+    sim.run(|ch| {
+        let _ch = middleware.process_event(ch, loopback, app);
+    });
+}
+
+#[test]
+fn responds_during_the_same_event() {
+    let mut sim = Simulator::new();
+    let mut middleware = io::Middleware::new();
+    let mut loopback = Loopback::new();
+    let mut app = App{ processed: 0 };
+
+    loopback.push_apdu(&[0xE0, 0x01, 0, 0]).unwrap();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert_eq!(loopback.take_rapdu(), Some(&[0x42, 0x90, 0x00][..]));
+    assert!(!middleware.has_pending_response());
+}
+
+#[test]
+fn keeps_response_until_status_isnt_sent() {
+    let mut sim = Simulator::new();
+    let mut middleware = io::Middleware::new();
+    let mut loopback = Loopback::new();
+    let mut app = App{ processed: 0 };
+
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+
+    // Supervisor has already replied to the event
+    sim.script_syscall(Syscall::IoSeproxyhalSpiIsStatusSent, Ok(1));
+    loopback.push_apdu(&[0xE0, 0x01, 0, 0]).unwrap();
+    sim.tick();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(loopback.take_rapdu().is_none());
+    assert!(middleware.has_pending_response());
+
+    // Command isn't dispatched again while the response is pending
+    sim.tick();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert_eq!(loopback.take_rapdu(), Some(&[0x42, 0x90, 0x00][..]));
+    assert!(!middleware.has_pending_response());
+    assert_eq!(app.processed, 1);
+}
+
+#[test]
+fn drops_response_when_command_is_gone() {
+    let mut sim = Simulator::new();
+    let mut middleware = io::Middleware::new();
+    let mut app = App{ processed: 0 };
+
+    let mut loopback = Loopback::new();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+
+    sim.script_syscall(Syscall::IoSeproxyhalSpiIsStatusSent, Ok(1));
+    loopback.push_apdu(&[0xE0, 0x01, 0, 0]).unwrap();
+    sim.tick();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(middleware.has_pending_response());
+
+    // Transport was reset, there's nobody to respond to
+    let mut loopback = Loopback::new();
+    sim.tick();
+    run(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(loopback.take_rapdu().is_none());
+    assert!(!middleware.has_pending_response());
+}
+
+struct DeferringApp {
+    decision: Option<u8>,
+    resumes: u32,
+    cancelled: bool,
+}
+
+impl Store for DeferringApp {
+    type Action = u8;
+}
+
+impl apdu::Delegate for DeferringApp {
+    fn class(&self) -> u8 {
+        0xE0
+    }
+
+    fn action_for_instruction(&self, ins: u8) -> Option<Self::Action> {
+        Some(ins)
+    }
+
+    fn process_command(
+        &mut self,
+        _action: Self::Action,
+        _command: &apdu::Command,
+        response: &mut apdu::Response,
+    ) -> Result<(), apdu::StatusWord> {
+        response.defer();
+        Ok(())
+    }
+
+    fn resume_command(
+        &mut self,
+        _action: Self::Action,
+        _command: &apdu::Command,
+        response: &mut apdu::Response,
+    ) -> Result<(), apdu::StatusWord> {
+        self.resumes += 1;
+        match self.decision {
+            Some(value) => response.append_u8(value),
+            None => {
+                response.defer();
+                Ok(())
+            },
+        }
+    }
+
+    fn cancel_command(&mut self) {
+        self.cancelled = true;
+    }
+}
+
+fn run_deferring(sim: &mut Simulator, middleware: &mut io::Middleware<DeferringApp>, loopback: &mut Loopback, app: &mut DeferringApp) {
+    sim.run(|ch| {
+        let _ch = middleware.process_event(ch, loopback, app);
+    });
+}
+
+#[test]
+fn keeps_resumed_response_until_status_isnt_sent() {
+    let mut sim = Simulator::new();
+    let mut middleware = io::Middleware::new();
+    let mut loopback = Loopback::new();
+    let mut app = DeferringApp{ decision: None, resumes: 0, cancelled: false };
+
+    loopback.push_apdu(&[0xE0, 0x01, 0, 0]).unwrap();
+    run_deferring(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(middleware.has_deferred_command());
+
+    // Delegate responds during an event that already has a status
+    app.decision = Some(0x42);
+    sim.script_syscall(Syscall::IoSeproxyhalSpiIsStatusSent, Ok(1));
+    sim.tick();
+    run_deferring(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(loopback.take_rapdu().is_none());
+    assert!(!middleware.has_deferred_command());
+    assert!(middleware.has_pending_response());
+
+    // The response isn't lost and the delegate isn't asked again
+    sim.tick();
+    run_deferring(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert_eq!(loopback.take_rapdu(), Some(&[0x42, 0x90, 0x00][..]));
+    assert!(!middleware.has_pending_response());
+    assert_eq!(app.resumes, 1);
+    assert!(!app.cancelled);
+}
+
+#[test]
+fn cancels_deferred_command_when_transport_is_reset() {
+    let mut sim = Simulator::new();
+    let mut middleware = io::Middleware::new();
+    let mut app = DeferringApp{ decision: None, resumes: 0, cancelled: false };
+
+    let mut loopback = Loopback::new();
+    loopback.push_apdu(&[0xE0, 0x01, 0, 0]).unwrap();
+    run_deferring(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(middleware.has_deferred_command());
+
+    let mut loopback = Loopback::new();
+    sim.tick();
+    run_deferring(&mut sim, &mut middleware, &mut loopback, &mut app);
+    assert!(!middleware.has_deferred_command());
+    assert!(app.cancelled);
+}
@@ -0,0 +1,103 @@
+#[macro_use]
+extern crate bolos;
+
+use bolos::apdu::StatusWord;
+use bolos::crypto::bip32::{self, DerivationPath, HARDENED};
+use bolos::crypto::ecc::{self, Curve, PrivateKey};
+use bolos::error::SystemError;
+use bolos::manifest;
+use bolos::simulator::{Simulator, Syscall};
+
+app_manifest!{
+    name: "Demo",
+    version: "1.2.3",
+    flags: 0x40,
+    icon: None,
+    curves: [Secp256k1],
+    paths: [[44 | HARDENED, 0 | HARDENED], [13]],
+}
+
+fn derive_syscall_count(sim: &mut Simulator) -> usize {
+    sim.take_syscalls().iter()
+        .filter(|record| record.syscall == Syscall::OsPersoDeriveNodeBip32)
+        .count()
+}
+
+#[test]
+fn reads_manifest() {
+    let manifest = manifest::get().expect("Manifest is missing");
+    assert_eq!(manifest.name(), "Demo");
+    assert_eq!(manifest.version(), "1.2.3");
+    assert_eq!(manifest.flags(), 0x40);
+    assert!(manifest.icon().is_none());
+    assert!(manifest.curves() == &[Curve::Secp256k1]);
+    assert_eq!(manifest.paths(), &[&[44 | HARDENED, HARDENED][..], &[13][..]]);
+    assert_eq!(APP_MANIFEST.version(), "1.2.3");
+}
+
+#[test]
+fn derives_under_allowed_path() {
+    let mut sim = Simulator::new();
+    sim.script_syscall(Syscall::OsPersoDeriveNodeBip32, Ok(0));
+
+    let path = DerivationPath::parse("m/44'/0'/0'/0/1").unwrap();
+    let node = bip32::derive(Curve::Secp256k1, &path).expect("Derivation was refused");
+    assert!(node.curve() == Curve::Secp256k1);
+    assert_eq!(derive_syscall_count(&mut sim), 1);
+}
+
+#[test]
+fn refuses_path_outside_of_allowed_prefixes() {
+    let mut sim = Simulator::new();
+    for text in ["m/44'/60'/0'", "m/44'", "m", "m/13'"].iter() {
+        let path = DerivationPath::parse(text).unwrap();
+        match bip32::derive(Curve::Secp256k1, &path) {
+            Err(SystemError::Security) => {},
+            _ => panic!("Derivation of {} wasn't refused", text),
+        }
+    }
+    assert_eq!(derive_syscall_count(&mut sim), 0);
+}
+
+#[test]
+fn refuses_curve_that_isnt_allowed() {
+    let mut sim = Simulator::new();
+    let path = DerivationPath::parse("m/44'/0'/0'").unwrap();
+    match bip32::derive(Curve::Secp256r1, &path) {
+        Err(SystemError::Security) => {},
+        _ => panic!("Derivation on secp256r1 wasn't refused"),
+    }
+    assert_eq!(derive_syscall_count(&mut sim), 0);
+
+    sim.script_syscall(Syscall::CxEcfpInitPrivateKey, Ok(0));
+    let key = PrivateKey::from_bytes(Curve::Secp256r1, &[1; 32]).unwrap();
+    match ecc::ecdsa_sign(&key, &[0; 32]) {
+        Err(SystemError::Security) => {},
+        _ => panic!("Signing with a secp256r1 key wasn't refused"),
+    }
+
+    sim.script_syscall(Syscall::CxEcfpInitPrivateKey, Ok(0));
+    let key = PrivateKey::from_bytes(Curve::Ed25519, &[1; 32]).unwrap();
+    match ecc::eddsa_sign(&key, b"message") {
+        Err(SystemError::Security) => {},
+        _ => panic!("Signing with an ed25519 key wasn't refused"),
+    }
+    assert!(sim.take_syscalls().iter().all(|record| {
+        record.syscall != Syscall::CxEcdsaSign && record.syscall != Syscall::CxEddsaSign
+    }));
+}
+
+#[test]
+fn errors_map_to_status_words() {
+    let cases = [
+        (SystemError::Security as u16, 0x6982),
+        (SystemError::InvalidParameter as u16, 0x6A80),
+        (SystemError::InvalidState as u16, 0x6985),
+        (SystemError::Overflow as u16, 0x6F00),
+        (SystemError::IoReset as u16, 0x6F00),
+    ];
+    for &(err, sw) in cases.iter() {
+        let status: StatusWord = SystemError::from_u16(err).unwrap().into();
+        assert_eq!(status.to_wire_format(), sw);
+    }
+}
@@ -0,0 +1,20 @@
+extern crate bolos;
+
+use bolos::crypto::bip32::{self, DerivationPath};
+use bolos::crypto::ecc::Curve;
+use bolos::manifest;
+use bolos::simulator::{Simulator, Syscall};
+
+// Without a manifest the SDK leaves key derivation up to the install
+// parameters of the app, which the device enforces
+#[test]
+fn derivation_is_unrestricted_without_manifest() {
+    assert!(manifest::get().is_none());
+
+    let mut sim = Simulator::new();
+    for &curve in [Curve::Secp256k1, Curve::Secp256r1].iter() {
+        sim.script_syscall(Syscall::OsPersoDeriveNodeBip32, Ok(0));
+        let path = DerivationPath::parse("m/44'/60'/0'").unwrap();
+        assert!(bip32::derive(curve, &path).is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+extern crate bolos;
+
+use bolos::crypto::bip32::{self, DerivationPath};
+use bolos::crypto::ecc::{self, Curve};
+use bolos::error::SystemError;
+use bolos::simulator::{Simulator, Syscall};
+
+#[test]
+fn scripted_outputs_fill_buffers() {
+    let mut sim = Simulator::new();
+    let key = [0x11; 64];
+    let chain_code = [0x22; 32];
+    sim.script_syscall_output(Syscall::OsPersoDeriveNodeBip32, Ok(0), &[&key, &chain_code]);
+    sim.script_syscall(Syscall::CxEcfpInitPrivateKey, Ok(0));
+    let der = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02];
+    sim.script_syscall_output(Syscall::CxEcdsaSign, Ok(der.len() as u32), &[&der, &[1, 0, 0, 0]]);
+
+    let path = DerivationPath::parse("m/44'/0'/0'").unwrap();
+    let node = bip32::derive(Curve::Secp256k1, &path).unwrap();
+    assert_eq!(node.chain_code(), &chain_code);
+
+    let sig = ecc::ecdsa_sign(&node.private_key().unwrap(), &[0; 32]).unwrap();
+    assert_eq!(sig.as_bytes(), &der);
+    assert!(sig.is_parity_odd());
+}
+
+#[test]
+fn scripted_outputs_are_optional() {
+    let mut sim = Simulator::new();
+    sim.script_syscall(Syscall::OsPersoDeriveNodeBip32, Err(SystemError::InvalidParameter));
+
+    let path = DerivationPath::parse("m/44'/0'/0'").unwrap();
+    match bip32::derive(Curve::Secp256k1, &path) {
+        Err(SystemError::InvalidParameter) => {},
+        _ => panic!("Scripted error wasn't returned"),
+    }
+}
+
+#[test]
+#[should_panic(expected = "doesn't have 1 output buffers")]
+fn outputs_must_have_buffers() {
+    let mut sim = Simulator::new();
+    sim.script_syscall_output(Syscall::NvmWrite, Ok(0), &[&[0]]);
+}
+
+#[test]
+#[should_panic(expected = "doesn't fit the 72 byte buffer")]
+fn outputs_must_fit_buffers() {
+    let mut sim = Simulator::new();
+    sim.script_syscall(Syscall::CxEcfpInitPrivateKey, Ok(0));
+    sim.script_syscall_output(Syscall::CxEcdsaSign, Ok(73), &[&[0x30; 73]]);
+
+    let key = ecc::PrivateKey::from_bytes(Curve::Secp256k1, &[1; 32]).unwrap();
+    let _ = ecc::ecdsa_sign(&key, &[0; 32]);
+}
@@ -0,0 +1,50 @@
+extern crate bolos;
+
+use std::env;
+use std::fs;
+use std::panic;
+use bolos::simulator::Framebuffer;
+use bolos::simulator::snapshot::{assert_snapshot, UPDATE_SNAPSHOTS_VAR};
+
+#[test]
+fn missing_snapshot_fails() {
+    // Updating creates the snapshot instead
+    if env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        return;
+    }
+
+    let path = env::temp_dir().join("bolos-missing-snapshot.txt");
+    let _ = fs::remove_file(&path);
+
+    let screen = Framebuffer::new();
+    let check_path = path.clone();
+    let result = panic::catch_unwind(move || assert_snapshot(&screen, check_path));
+    assert!(result.is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn matching_snapshot_passes() {
+    let path = env::temp_dir().join("bolos-blank-snapshot.txt");
+    let screen = Framebuffer::new();
+    fs::write(&path, screen.to_string()).unwrap();
+    assert_snapshot(&screen, &path);
+
+    // Snapshots checked out with Windows line endings still match
+    fs::write(&path, screen.to_string().replace("\n", "\r\n")).unwrap();
+    assert_snapshot(&screen, &path);
+}
+
+#[test]
+fn different_snapshot_fails() {
+    if env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        return;
+    }
+
+    let path = env::temp_dir().join("bolos-different-snapshot.txt");
+    fs::write(&path, Framebuffer::new().to_string().replacen(".", "#", 1)).unwrap();
+
+    let screen = Framebuffer::new();
+    let result = panic::catch_unwind(move || assert_snapshot(&screen, path));
+    assert!(result.is_err());
+}
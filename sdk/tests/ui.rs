@@ -0,0 +1,234 @@
+extern crate bolos;
+
+use std::cell::Cell;
+use bolos::simulator::{Simulator, Button, Framebuffer};
+use bolos::simulator::snapshot::{UiRunner, assert_snapshot};
+use bolos::state::{Store, BasicAction};
+use bolos::ui;
+use bolos::ui::menu;
+
+fn has_lit_pixels(screen: &Framebuffer, x: usize, y: usize, width: usize, height: usize) -> bool {
+    (y..y + height).any(|y| (x..x + width).any(|x| screen.pixel(x, y)))
+}
+
+struct Counter {
+    value: i32,
+    ui_version: u16,
+    prepare_count: Cell<usize>,
+}
+
+impl Counter {
+    fn new() -> Self {
+        Self{
+            value: 0,
+            ui_version: 0,
+            prepare_count: Cell::new(0),
+        }
+    }
+}
+
+impl Store for Counter {
+    type Action = BasicAction;
+
+    fn process_action(&mut self, action: Self::Action) {
+        match action {
+            BasicAction::Previous => self.value -= 1,
+            BasicAction::Next => self.value += 1,
+            BasicAction::Confirm => self.value = 0,
+        }
+        self.ui_version += 1;
+    }
+}
+
+impl ui::Delegate for Counter {
+    fn ui_version(&self) -> u16 {
+        self.ui_version
+    }
+
+    fn prepare_ui(&self, ctrl: &mut ui::Controller<Self::Action>) {
+        self.prepare_count.set(self.prepare_count.get() + 1);
+        ctrl.set_button_actions(ui::ButtonAction::Map{
+            left: Some(BasicAction::Previous),
+            right: Some(BasicAction::Next),
+            both: Some(BasicAction::Confirm),
+        });
+        ctrl.add_view(|| ui::RectangleView{
+            frame: ui::Frame{ x: 0, y: 0, width: 128, height: 32 },
+            fill: ui::FillMode::Fill,
+            ..Default::default()
+        }.into());
+        if self.value > 0 {
+            ctrl.add_view(|| ui::LabelLineView{
+                frame: ui::Frame{ x: 0, y: 19, width: 128, height: 12 },
+                horizontal_alignment: ui::TextHorizontalAlignment::Center,
+                text: "Positive",
+                ..Default::default()
+            }.into());
+        }
+    }
+}
+
+fn run(sim: &mut Simulator, middleware: &mut ui::Middleware<BasicAction, Counter>, counter: &mut Counter) {
+    sim.run(|ch| {
+        let _ch = Some(ch)
+            .and_then(|ch| middleware.process_event(ch, counter))
+            .and_then(|ch| middleware.redraw_if_needed(ch, counter));
+    });
+}
+
+#[test]
+fn middleware_maps_buttons_to_actions() {
+    let mut sim = Simulator::new();
+    let mut middleware = ui::Middleware::new();
+    let mut counter = Counter::new();
+    run(&mut sim, &mut middleware, &mut counter);
+
+    sim.press_button(Button::Right);
+    run(&mut sim, &mut middleware, &mut counter);
+    sim.press_button(Button::Right);
+    run(&mut sim, &mut middleware, &mut counter);
+    assert_eq!(counter.value, 2);
+
+    sim.press_button(Button::Left);
+    run(&mut sim, &mut middleware, &mut counter);
+    assert_eq!(counter.value, 1);
+
+    sim.press_button(Button::Both);
+    run(&mut sim, &mut middleware, &mut counter);
+    assert_eq!(counter.value, 0);
+}
+
+#[test]
+fn middleware_redraws_when_ui_version_changes() {
+    let mut sim = Simulator::new();
+    let mut middleware = ui::Middleware::new();
+    let mut counter = Counter::new();
+    run(&mut sim, &mut middleware, &mut counter);
+    assert!(!has_lit_pixels(&sim.framebuffer(), 0, 0, 128, 32));
+    let prepare_count = counter.prepare_count.get();
+    assert!(prepare_count > 0);
+
+    // Nothing changed, the UI isn't prepared again
+    sim.tick();
+    run(&mut sim, &mut middleware, &mut counter);
+    assert_eq!(counter.prepare_count.get(), prepare_count);
+
+    sim.press_button(Button::Right);
+    run(&mut sim, &mut middleware, &mut counter);
+    assert!(counter.prepare_count.get() > prepare_count);
+    assert!(has_lit_pixels(&sim.framebuffer(), 0, 0, 128, 32));
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum MenuItem {
+    First,
+    Second,
+    Third,
+}
+
+struct MenuState {
+    item: MenuItem,
+    selected: Option<MenuItem>,
+}
+
+impl Store for MenuState {
+    type Action = BasicAction;
+
+    fn process_action(&mut self, action: Self::Action) {
+        match action {
+            BasicAction::Previous => {
+                self.item = menu::previous_item(self.item, self).unwrap_or(self.item);
+            },
+            BasicAction::Next => {
+                self.item = menu::next_item(self.item, self).unwrap_or(self.item);
+            },
+            BasicAction::Confirm => {
+                self.selected = Some(self.item);
+            },
+        }
+    }
+}
+
+impl menu::Delegate<MenuItem> for MenuState {
+    fn prepare_menu_item(&self, ctrl: &mut menu::Controller<MenuItem, Self::Action>) {
+        ctrl.add_item(MenuItem::First, || menu::ItemSpec{
+            line_1: "First",
+            action: Some(BasicAction::Confirm),
+            ..Default::default()
+        });
+        ctrl.add_item(MenuItem::Second, || menu::ItemSpec{
+            line_1: "Second",
+            line_2: "item",
+            action: Some(BasicAction::Confirm),
+            ..Default::default()
+        });
+        ctrl.add_item(MenuItem::Third, || menu::ItemSpec{
+            icon: Some(ui::SystemIcon::DashboardBadge.into()),
+            line_1: "Third",
+            ..Default::default()
+        });
+    }
+}
+
+impl ui::Delegate for MenuState {
+    fn ui_version(&self) -> u16 {
+        self.item as u16
+    }
+
+    fn prepare_ui(&self, ctrl: &mut ui::Controller<Self::Action>) {
+        menu::prepare_menu(self.item, self, ctrl);
+    }
+}
+
+// Areas of the up and down arrows that the menu shows next to the items
+fn has_up_arrow(screen: &Framebuffer) -> bool {
+    has_lit_pixels(screen, 3, 14, 7, 4)
+}
+
+fn has_down_arrow(screen: &Framebuffer) -> bool {
+    has_lit_pixels(screen, 118, 14, 7, 4)
+}
+
+#[test]
+fn menu_navigates_between_items() {
+    let mut runner = UiRunner::new(MenuState{ item: MenuItem::First, selected: None });
+    assert!(!has_up_arrow(&runner.screen()));
+    assert!(has_down_arrow(&runner.screen()));
+    assert_snapshot(&runner.screen(), "tests/snapshots/menu_first.txt");
+
+    // There's nothing before the first item
+    runner.press_button(Button::Left);
+    assert_eq!(runner.delegate().item, MenuItem::First);
+
+    runner.press_button(Button::Right);
+    assert_eq!(runner.delegate().item, MenuItem::Second);
+    assert!(has_up_arrow(&runner.screen()));
+    assert!(has_down_arrow(&runner.screen()));
+    assert_snapshot(&runner.screen(), "tests/snapshots/menu_second.txt");
+
+    runner.press_button(Button::Right);
+    assert_eq!(runner.delegate().item, MenuItem::Third);
+    assert!(has_up_arrow(&runner.screen()));
+    assert!(!has_down_arrow(&runner.screen()));
+    assert_snapshot(&runner.screen(), "tests/snapshots/menu_third.txt");
+
+    runner.press_button(Button::Right);
+    assert_eq!(runner.delegate().item, MenuItem::Third);
+
+    runner.press_button(Button::Left);
+    assert_eq!(runner.delegate().item, MenuItem::Second);
+}
+
+#[test]
+fn menu_confirms_item_with_both_buttons() {
+    let mut runner = UiRunner::new(MenuState{ item: MenuItem::First, selected: None });
+    runner.press_button(Button::Right);
+    runner.press_button(Button::Both);
+    assert_eq!(runner.delegate().selected, Some(MenuItem::Second));
+
+    // Items without an action ignore the confirmation
+    runner.update(|state| state.selected = None);
+    runner.press_button(Button::Right);
+    runner.press_button(Button::Both);
+    assert_eq!(runner.delegate().selected, None);
+}
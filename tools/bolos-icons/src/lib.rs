@@ -0,0 +1,450 @@
+//! Converts GIF and PNG images into `CustomIcon` constants for bolos-rs
+//! applications. Meant to be used from the build script of the app:
+//!
+//! ```ignore
+//! extern crate bolos_icons;
+//!
+//! fn main() {
+//!     bolos_icons::Generator::new()
+//!         .icon("BADGE_RUST", "icons/badge_rust.gif")
+//!         .write_to_out_dir("icons.rs")
+//!         .expect("Failed to convert icons");
+//! }
+//! ```
+//!
+//! The generated constants are then included in the app:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/icons.rs"));
+//! ```
+
+extern crate gif;
+extern crate png;
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Icons can use up to 4 bits per pixel
+pub const MAX_COLORS: usize = 16;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Gif(gif::DecodingError),
+    Png(png::DecodingError),
+    /// Only GIF and PNG files are supported
+    UnsupportedFormat(PathBuf),
+    /// GIF file doesn't contain any frames
+    NoFrames(PathBuf),
+    /// Image is too large to be described by a `CustomIcon`
+    TooLarge(PathBuf),
+    TooManyColors{ path: PathBuf, count: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Gif(ref err) => write!(f, "{}", err),
+            Error::Png(ref err) => write!(f, "{}", err),
+            Error::UnsupportedFormat(ref path) => {
+                write!(f, "{}: only GIF and PNG images are supported", path.display())
+            },
+            Error::NoFrames(ref path) => {
+                write!(f, "{}: image has no frames", path.display())
+            },
+            Error::TooLarge(ref path) => {
+                write!(f, "{}: image is too large", path.display())
+            },
+            Error::TooManyColors{ ref path, count } => {
+                write!(f, "{}: image has {} colors, at most {} are supported",
+                    path.display(), count, MAX_COLORS)
+            },
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_) => "failed to read the image",
+            Error::Gif(_) => "failed to decode the GIF image",
+            Error::Png(_) => "failed to decode the PNG image",
+            Error::UnsupportedFormat(_) => "unsupported image format",
+            Error::NoFrames(_) => "image has no frames",
+            Error::TooLarge(_) => "image is too large",
+            Error::TooManyColors{ .. } => "image has too many colors",
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<gif::DecodingError> for Error {
+    fn from(err: gif::DecodingError) -> Self {
+        Error::Gif(err)
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(err: png::DecodingError) -> Self {
+        Error::Png(err)
+    }
+}
+
+/// Image in the format that the firmware draws, the bitmap contains the
+/// palette indices of the pixels packed starting from the least
+/// significant bit of the first byte.
+pub struct Icon {
+    pub width: u16,
+    pub height: u16,
+    pub bits_per_pixel: u8,
+    pub colors: Vec<u32>,
+    pub bitmap: Vec<u8>,
+}
+
+impl Icon {
+    /// Loads the first frame of a GIF or PNG image. Transparent pixels
+    /// are treated as black, which is the background color of the screen.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        let (width, height, pixels) = match extension.as_deref() {
+            Some("gif") => load_gif(path)?,
+            Some("png") => load_png(path)?,
+            _ => return Err(Error::UnsupportedFormat(path.to_path_buf())),
+        };
+
+        Self::from_pixels(path, width, height, pixels)
+    }
+
+    // Pixels are 0xRRGGBB colors in row-major order
+    fn from_pixels(path: &Path, width: u16, height: u16, pixels: Vec<u32>) -> Result<Self, Error> {
+        // Darkest color comes first, so that the usual two color icons
+        // get the same palette as the system icons
+        let mut colors = pixels.clone();
+        colors.sort_by_key(|&color| (luminance(color), color));
+        colors.dedup();
+        if colors.len() > MAX_COLORS {
+            return Err(Error::TooManyColors{
+                path: path.to_path_buf(),
+                count: colors.len(),
+            });
+        }
+
+        let bits_per_pixel = match colors.len() {
+            0..=2 => 1,
+            3..=4 => 2,
+            _ => 4,
+        };
+        while colors.len() < 1 << bits_per_pixel {
+            let last = colors.last().cloned().unwrap_or(0);
+            colors.push(last);
+        }
+
+        let bpp = bits_per_pixel as usize;
+        let mut bitmap = vec![0; (pixels.len() * bpp).div_ceil(8)];
+        for (i, color) in pixels.iter().enumerate() {
+            let index = colors.iter().position(|c| c == color).unwrap_or(0);
+            let bit = i * bpp;
+            bitmap[bit / 8] |= (index << (bit % 8)) as u8;
+        }
+
+        Ok(Self{
+            width,
+            height,
+            bits_per_pixel,
+            colors,
+            bitmap,
+        })
+    }
+
+    /// Rust source of a `CustomIcon` constant with the given name
+    pub fn to_rust_const(&self, name: &str) -> String {
+        let colors: Vec<String> = self.colors.iter()
+            .map(|color| format!("0x{:08x}", color))
+            .collect();
+        let bitmap: Vec<String> = self.bitmap.iter()
+            .map(|byte| format!("0x{:02x}", byte))
+            .collect();
+
+        format!(
+            "pub const {}: ::bolos::ui::CustomIcon<'static> = ::bolos::ui::CustomIcon{{\n    \
+                width: {}, height: {}, bits_per_pixel: {},\n    \
+                colors: &[{}],\n    \
+                bitmap: &[{}],\n\
+            }};\n",
+            name, self.width, self.height, self.bits_per_pixel,
+            colors.join(", "), bitmap.join(", "),
+        )
+    }
+}
+
+/// Converts a set of images into a single Rust source file
+pub struct Generator {
+    icons: Vec<(String, PathBuf)>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self{
+            icons: Vec::new(),
+        }
+    }
+
+    /// Adds a constant with the `name` for the image at `path`, relative
+    /// paths are resolved from the current directory, which is the crate
+    /// root for build scripts
+    pub fn icon<P: AsRef<Path>>(mut self, name: &str, path: P) -> Self {
+        self.icons.push((name.to_string(), path.as_ref().to_path_buf()));
+        self
+    }
+
+    pub fn generate(&self) -> Result<String, Error> {
+        let mut source = String::from("// Generated by bolos-icons, do not edit\n");
+        for (name, path) in self.icons.iter() {
+            let icon = Icon::load(path)?;
+            source.push_str(&format!("\n// {}\n", path.display()));
+            source.push_str(&icon.to_rust_const(name));
+        }
+        Ok(source)
+    }
+
+    /// Writes the generated source to `file_name` in the `OUT_DIR` of the
+    /// build script and lets cargo know to rerun it when the images change
+    pub fn write_to_out_dir(&self, file_name: &str) -> Result<(), Error> {
+        let out_dir = env::var_os("OUT_DIR")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?;
+
+        for (_, path) in self.icons.iter() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+
+        let source = self.generate()?;
+        fs::write(Path::new(&out_dir).join(file_name), source)?;
+        Ok(())
+    }
+}
+
+fn load_gif(path: &Path) -> Result<(u16, u16, Vec<u32>), Error> {
+    let mut decoder = gif::Decoder::new(File::open(path)?);
+    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+    let mut reader = decoder.read_info()?;
+
+    let width = reader.width();
+    let height = reader.height();
+    let mut pixels = vec![0; width as usize * height as usize];
+
+    let frame = match reader.read_next_frame()? {
+        Some(frame) => frame,
+        None => return Err(Error::NoFrames(path.to_path_buf())),
+    };
+    // Frames can cover just a part of the image
+    for (i, rgba) in frame.buffer.chunks(4).enumerate() {
+        let x = frame.left as usize + i % frame.width as usize;
+        let y = frame.top as usize + i / frame.width as usize;
+        if x < width as usize && y < height as usize {
+            pixels[y * width as usize + x] = to_color(rgba[0], rgba[1], rgba[2], rgba[3]);
+        }
+    }
+
+    Ok((width, height, pixels))
+}
+
+fn load_png(path: &Path) -> Result<(u16, u16, Vec<u32>), Error> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    // Palettes and low bit depths are expanded to 8 bit channels
+    png::HasParameters::set(&mut decoder, png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+
+    if info.width > u16::MAX as u32 || info.height > u16::MAX as u32 {
+        return Err(Error::TooLarge(path.to_path_buf()));
+    }
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    let line_size = reader.output_line_size(info.width);
+    let channels = match reader.output_color_type().0 {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        png::ColorType::RGBA => 4,
+        png::ColorType::Indexed => return Err(Error::UnsupportedFormat(path.to_path_buf())),
+    };
+
+    let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize);
+    for row in buf.chunks(line_size).take(info.height as usize) {
+        for p in row.chunks(channels).take(info.width as usize) {
+            pixels.push(match channels {
+                1 => to_color(p[0], p[0], p[0], 0xFF),
+                2 => to_color(p[0], p[0], p[0], p[1]),
+                3 => to_color(p[0], p[1], p[2], 0xFF),
+                _ => to_color(p[0], p[1], p[2], p[3]),
+            });
+        }
+    }
+
+    Ok((info.width as u16, info.height as u16, pixels))
+}
+
+fn to_color(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    if a < 0x80 {
+        0
+    } else {
+        (r as u32) << 16 | (g as u32) << 8 | b as u32
+    }
+}
+
+fn luminance(color: u32) -> u32 {
+    let r = (color >> 16) & 0xFF;
+    let g = (color >> 8) & 0xFF;
+    let b = color & 0xFF;
+    299 * r + 587 * g + 114 * b
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use super::*;
+
+    fn demo_path(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../demos").join(file)
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn fixture_path(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(file)
+    }
+
+    #[test]
+    fn badge_rust_matches_hand_made_bitmap() {
+        let icon = Icon::load(demo_path("ui-patterns/icons/badge_rust.gif")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (14, 14, 1));
+        assert_eq!(icon.colors, vec![0x00000000, 0x00ffffff]);
+        assert_eq!(icon.bitmap, vec![
+            0xe0, 0x01, 0xfe, 0xc1, 0xff, 0xf8, 0x7f, 0x0e, 0xdf, 0x93, 0xff, 0xe4, 0x3f,
+            0xfc, 0xcf, 0xbe, 0x33, 0xe7, 0xff, 0xf1, 0x3f, 0xf8, 0x07, 0x78, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn badge_back_matches_hand_made_bitmap() {
+        let icon = Icon::load(demo_path("ui-patterns/icons/badge_back.gif")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (14, 14, 1));
+        assert_eq!(icon.colors, vec![0x00000000, 0x00ffffff]);
+        assert_eq!(icon.bitmap, vec![
+            0xe0, 0x01, 0xfe, 0xc1, 0xfd, 0x38, 0x7f, 0x06, 0xdf, 0x81, 0xff, 0xc4, 0x7f,
+            0xf3, 0xff, 0xbc, 0x1f, 0xe7, 0xe7, 0xf1, 0x3f, 0xf8, 0x07, 0x78, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn app_icon_matches_makefile_hex() {
+        let icon = Icon::load(demo_path("nanos_icon.gif")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (16, 16, 1));
+        assert_eq!(icon.colors, vec![0x00000000, 0x00ffffff]);
+        assert_eq!(icon.bitmap, from_hex(
+            "ffffffffffffffffffff1ffe9ffc9ffc1ffe9ffd9ff9ffffffffffffffffffff"));
+    }
+
+    #[test]
+    fn palette_is_sorted_darkest_first() {
+        let pixels = vec![0xffffff, 0x0000ff, 0xff0000, 0x000000];
+        let icon = Icon::from_pixels(Path::new("test"), 2, 2, pixels).unwrap();
+        assert_eq!(icon.bits_per_pixel, 2);
+        assert_eq!(icon.colors, vec![0x000000, 0x0000ff, 0xff0000, 0xffffff]);
+    }
+
+    #[test]
+    fn palette_is_padded_to_bits_per_pixel() {
+        let pixels = vec![0x000000, 0x808080, 0xffffff];
+        let icon = Icon::from_pixels(Path::new("test"), 3, 1, pixels).unwrap();
+        assert_eq!(icon.bits_per_pixel, 2);
+        assert_eq!(icon.colors, vec![0x000000, 0x808080, 0xffffff, 0xffffff]);
+    }
+
+    #[test]
+    fn palette_png_is_converted() {
+        let icon = Icon::load(fixture_path("palette.png")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (4, 2, 2));
+        assert_eq!(icon.colors, vec![0x000000, 0xff0000, 0xffffff, 0xffffff]);
+        assert_eq!(icon.bitmap, vec![0x12, 0x60]);
+    }
+
+    #[test]
+    fn gray_png_is_converted() {
+        let icon = Icon::load(fixture_path("gray.png")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (10, 1, 1));
+        assert_eq!(icon.colors, vec![0x000000, 0xffffff]);
+        assert_eq!(icon.bitmap, vec![0x01, 0x02]);
+    }
+
+    #[test]
+    fn rgb_png_is_converted() {
+        let icon = Icon::load(fixture_path("rgb.png")).unwrap();
+        assert_eq!((icon.width, icon.height, icon.bits_per_pixel), (3, 1, 2));
+        assert_eq!(icon.colors, vec![0x000000, 0x0000ff, 0xffffff, 0xffffff]);
+        assert_eq!(icon.bitmap, vec![0x21]);
+    }
+
+    #[test]
+    fn png_with_too_many_colors_is_rejected() {
+        match Icon::load(fixture_path("too_many_colors.png")) {
+            Err(Error::TooManyColors{ count, .. }) => assert_eq!(count, MAX_COLORS + 1),
+            _ => panic!("expected too many colors"),
+        }
+    }
+
+    #[test]
+    fn pixels_are_packed_lsb_first() {
+        // 1 bpp: pixel 0 is bit 0 of the first byte
+        let pixels = vec![0xffffff, 0, 0, 0, 0, 0, 0, 0, 0, 0xffffff];
+        let icon = Icon::from_pixels(Path::new("test"), 10, 1, pixels).unwrap();
+        assert_eq!(icon.bitmap, vec![0x01, 0x02]);
+
+        // 2 bpp: pixel 1 is bits 2-3 of the first byte
+        let pixels = vec![0x000000, 0xffffff, 0x808080, 0x000000, 0x808080];
+        let icon = Icon::from_pixels(Path::new("test"), 5, 1, pixels).unwrap();
+        assert_eq!(icon.bitmap, vec![0b0001_1000, 0b0000_0001]);
+    }
+
+    #[test]
+    fn too_many_colors_are_rejected() {
+        let pixels: Vec<u32> = (0..MAX_COLORS as u32 + 1).map(|i| i * 0x010101).collect();
+        match Icon::from_pixels(Path::new("test"), pixels.len() as u16, 1, pixels) {
+            Err(Error::TooManyColors{ count, .. }) => assert_eq!(count, MAX_COLORS + 1),
+            _ => panic!("expected too many colors"),
+        }
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        match Icon::load("icon.bmp") {
+            Err(Error::UnsupportedFormat(_)) => (),
+            _ => panic!("expected unsupported format"),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+#!/usr/bin/env python3
+# Writes the PNG test images with just the standard library, so that each
+# of them uses exactly the color type and bit depth that's being tested.
+import os
+import struct
+import zlib
+
+
+def chunk(kind, data):
+    body = kind + data
+    return struct.pack('>I', len(data)) + body + struct.pack('>I', zlib.crc32(body))
+
+
+def write_png(name, width, height, bit_depth, color_type, rows, palette=None):
+    png = b'\x89PNG\r\n\x1a\n'
+    png += chunk(b'IHDR', struct.pack('>IIBBBBB', width, height, bit_depth, color_type, 0, 0, 0))
+    if palette:
+        png += chunk(b'PLTE', b''.join(struct.pack('>BBB', *rgb) for rgb in palette))
+    # Every row starts with filter type 0 (none)
+    png += chunk(b'IDAT', zlib.compress(b''.join(b'\x00' + bytes(row) for row in rows)))
+    png += chunk(b'IEND', b'')
+    with open(name, 'wb') as f:
+        f.write(png)
+
+
+os.chdir(os.path.dirname(os.path.abspath(__file__)))
+
+# 8-bit palette of white, black and red
+write_png('palette.png', 4, 2, 8, 3, [[0, 1, 2, 1], [1, 1, 0, 2]],
+          palette=[(0xff, 0xff, 0xff), (0, 0, 0), (0xff, 0, 0)])
+# 1-bit grayscale, white pixels at both ends
+write_png('gray.png', 10, 1, 1, 0, [[0b10000000, 0b01000000]])
+# 8-bit RGB of blue, black and white
+write_png('rgb.png', 3, 1, 8, 2, [[0, 0, 0xff, 0, 0, 0, 0xff, 0xff, 0xff]])
+# 17 shades of gray, one more than the firmware supports
+write_png('too_many_colors.png', 17, 1, 8, 2, [[i * 0x0f for i in range(17) for _ in range(3)]])
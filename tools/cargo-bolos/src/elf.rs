@@ -0,0 +1,208 @@
+use error::Error;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u32 = 0x2;
+
+/// Contents of a section at its physical (load) address
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+pub struct Elf {
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    /// Size of the persistent data at the end of the app, between the
+    /// `_nvm_data` and `_envm_data` symbols of the linker script
+    pub data_size: Option<u32>,
+}
+
+/// Reads the allocated sections of a 32-bit little-endian ELF file, which
+/// is what ends up in the flash of the device. Same as `objcopy`, the
+/// sections are placed at the load address of the segment they're in and
+/// the ones without any file contents, such as .bss, are skipped.
+pub fn parse(bytes: &[u8]) -> Result<Elf, Error> {
+    if bytes.len() < 52 || &bytes[0..4] != b"\x7fELF" {
+        return Err(Error::Elf("not an ELF file"));
+    }
+    if bytes[4] != 1 || bytes[5] != 1 {
+        return Err(Error::Elf("only 32-bit little-endian ELF files are supported"));
+    }
+
+    let entry = read_u32(bytes, 0x18)?;
+    let ph_offset = read_u32(bytes, 0x1C)? as usize;
+    let sh_offset = read_u32(bytes, 0x20)? as usize;
+    let ph_size = read_u16(bytes, 0x2A)? as usize;
+    let ph_count = read_u16(bytes, 0x2C)? as usize;
+    let sh_size = read_u16(bytes, 0x2E)? as usize;
+    let sh_count = read_u16(bytes, 0x30)? as usize;
+
+    // File offset ranges of the loadable segments and their load addresses
+    let mut loads = Vec::new();
+    for i in 0..ph_count {
+        let ph = table_entry(bytes, ph_offset, i, ph_size)?;
+        if read_u32(ph, 0)? == PT_LOAD {
+            let offset = read_u32(ph, 4)? as usize;
+            let address = read_u32(ph, 12)?;
+            let file_size = read_u32(ph, 16)? as usize;
+            let end = offset.checked_add(file_size)
+                .ok_or(Error::Elf("segment is outside of the file"))?;
+            loads.push((offset, end, address));
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut nvm_data = None;
+    let mut envm_data = None;
+    for i in 0..sh_count {
+        let sh = table_entry(bytes, sh_offset, i, sh_size)?;
+        let kind = read_u32(sh, 4)?;
+        let flags = read_u32(sh, 8)?;
+        let offset = read_u32(sh, 16)? as usize;
+        let size = read_u32(sh, 20)? as usize;
+        let end = offset.checked_add(size)
+            .ok_or(Error::Elf("section is outside of the file"))?;
+
+        if kind == SHT_SYMTAB {
+            let link = read_u32(sh, 24)? as usize;
+            let entry_size = read_u32(sh, 36)? as usize;
+            let names = table_entry(bytes, sh_offset, link, sh_size)?;
+            let names_offset = read_u32(names, 16)? as usize;
+            let symbols = bytes.get(offset..end)
+                .ok_or(Error::Elf("symbol table is outside of the file"))?;
+            for sym in symbols.chunks(entry_size.max(1)) {
+                let name_offset = names_offset.checked_add(read_u32(sym, 0)? as usize)
+                    .ok_or(Error::Elf("symbol name is outside of the file"))?;
+                match read_str(bytes, name_offset)? {
+                    b"_nvm_data" => nvm_data = Some(read_u32(sym, 4)?),
+                    b"_envm_data" => envm_data = Some(read_u32(sym, 4)?),
+                    _ => {},
+                }
+            }
+            continue;
+        }
+        if kind == SHT_NOBITS || flags & SHF_ALLOC == 0 || size == 0 {
+            continue;
+        }
+        let &(start, _, load_address) = loads.iter()
+            .find(|&&(start, segment_end, _)| start <= offset && end <= segment_end)
+            .ok_or(Error::Elf("section is outside of the loadable segments"))?;
+        let address = load_address.checked_add((offset - start) as u32)
+            .ok_or(Error::Elf("section is outside of the address space"))?;
+        let data = bytes.get(offset..end)
+            .ok_or(Error::Elf("section is outside of the file"))?;
+        segments.push(Segment{
+            address,
+            data: data.to_vec(),
+        });
+    }
+
+    segments.sort_by_key(|s| s.address);
+    let data_size = match (nvm_data, envm_data) {
+        (Some(start), Some(end)) if start <= end => Some(end - start),
+        (None, None) => None,
+        _ => return Err(Error::Elf("invalid persistent data symbols")),
+    };
+    Ok(Elf{ entry, segments, data_size })
+}
+
+// Entry `index` of a table of `entry_size` byte entries at `offset`
+fn table_entry(bytes: &[u8], offset: usize, index: usize, entry_size: usize) -> Result<&[u8], Error> {
+    index.checked_mul(entry_size)
+        .and_then(|start| start.checked_add(offset))
+        .and_then(|start| bytes.get(start..)?.get(..entry_size))
+        .ok_or(Error::Elf("unexpected end of file"))
+}
+
+// Null terminated string at `offset`
+fn read_str(bytes: &[u8], offset: usize) -> Result<&[u8], Error> {
+    let tail = bytes.get(offset..).ok_or(Error::Elf("unexpected end of file"))?;
+    match tail.iter().position(|&b| b == 0) {
+        Some(len) => Ok(&tail[..len]),
+        None => Err(Error::Elf("unexpected end of file")),
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    match bytes.get(offset..).and_then(|b| b.get(..2)) {
+        Some(b) => Ok(b[0] as u16 | (b[1] as u16) << 8),
+        None => Err(Error::Elf("unexpected end of file")),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    match bytes.get(offset..).and_then(|b| b.get(..4)) {
+        Some(b) => Ok(
+            b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+        ),
+        None => Err(Error::Elf("unexpected end of file")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP: &[u8] = include_bytes!("../tests/fixtures/app.elf");
+
+    #[test]
+    fn reads_data_size() {
+        let elf = parse(APP).unwrap();
+        assert_eq!(elf.data_size, Some(128));
+    }
+
+    #[test]
+    fn places_sections_at_load_addresses() {
+        let elf = parse(APP).unwrap();
+        assert_eq!(elf.entry, 0xc0d0_ff80);
+
+        // .data is copied to RAM from right after the code, .bss has no
+        // contents in the file
+        let layout: Vec<(u32, usize)> = elf.segments.iter()
+            .map(|s| (s.address, s.data.len()))
+            .collect();
+        assert_eq!(layout, vec![(0xc0d0_ff80, 0x140), (0xc0d1_00c0, 8)]);
+        assert_eq!(&elf.segments[0].data[0..4], &[0x10, 0x32, 0x54, 0x76]);
+        assert_eq!(elf.segments[1].data, vec![0x44, 0x33, 0x22, 0x11, 0x88, 0x77, 0x66, 0x55]);
+    }
+
+    #[test]
+    fn refuses_unsupported_files() {
+        assert!(parse(b"not an elf file at all").is_err());
+
+        let mut elf64 = APP.to_vec();
+        elf64[4] = 2;
+        assert!(parse(&elf64).is_err());
+
+        assert!(parse(&APP[0..0x100]).is_err());
+    }
+
+    #[test]
+    fn refuses_malformed_files() {
+        let ph_offset = read_u32(APP, 0x1C).unwrap() as usize;
+        let sh_offset = read_u32(APP, 0x20).unwrap() as usize;
+
+        // Sections past the end of the address space, the first segment
+        // only has the headers in it
+        let mut elf = APP.to_vec();
+        elf[ph_offset + 12..ph_offset + 16].copy_from_slice(&[0xF0, 0xFF, 0xFF, 0xFF]);
+        elf[ph_offset + 16..ph_offset + 20].copy_from_slice(&[0x00, 0x20, 0x00, 0x00]);
+        assert!(parse(&elf).is_err());
+
+        // Tables and sections past the end of the file
+        let mut elf = APP.to_vec();
+        elf[0x1C..0x20].copy_from_slice(&[0xFF; 4]);
+        assert!(parse(&elf).is_err());
+
+        let mut elf = APP.to_vec();
+        elf[0x2C..0x2E].copy_from_slice(&[0xFF; 2]);
+        assert!(parse(&elf).is_err());
+
+        let mut elf = APP.to_vec();
+        let sh = sh_offset + read_u16(APP, 0x2E).unwrap() as usize;
+        elf[sh + 16..sh + 24].copy_from_slice(&[0xFF; 8]);
+        assert!(parse(&elf).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use std::error;
+use std::fmt;
+use std::io;
+use bolos_icons;
+use serde_json;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Icon(bolos_icons::Error),
+    /// App metadata in Cargo.toml is missing or invalid
+    Metadata(String),
+    /// Built app isn't in the expected format
+    Elf(&'static str),
+    /// External command exited with an error
+    Command(String),
+    Usage,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Json(ref err) => write!(f, "invalid cargo metadata: {}", err),
+            Error::Icon(ref err) => write!(f, "{}", err),
+            Error::Metadata(ref msg) => write!(f, "{}", msg),
+            Error::Elf(msg) => write!(f, "invalid app binary: {}", msg),
+            Error::Command(ref cmd) => write!(f, "`{}` failed", cmd),
+            Error::Usage => write!(f, "invalid arguments"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_) => "I/O error",
+            Error::Json(_) => "invalid cargo metadata",
+            Error::Icon(_) => "failed to convert the icon",
+            Error::Metadata(_) => "invalid app metadata",
+            Error::Elf(_) => "invalid app binary",
+            Error::Command(_) => "command failed",
+            Error::Usage => "invalid arguments",
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<bolos_icons::Error> for Error {
+    fn from(err: bolos_icons::Error) -> Self {
+        Error::Icon(err)
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Write;
+use elf::Segment;
+
+const RECORD_DATA: u8 = 0x00;
+const RECORD_END_OF_FILE: u8 = 0x01;
+const RECORD_EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const RECORD_START_LINEAR_ADDRESS: u8 = 0x05;
+
+const BYTES_PER_RECORD: usize = 16;
+
+/// Encodes the segments as Intel HEX, the same way `objcopy -O ihex`
+/// does. The entry point is stored in the start address record, which
+/// is where the loader takes the boot address from.
+pub fn encode(segments: &[Segment], entry: u32) -> String {
+    let mut out = String::new();
+    let mut upper_address = None;
+
+    for segment in segments {
+        let mut address = segment.address;
+        let mut data = &segment.data[..];
+
+        while !data.is_empty() {
+            let upper = (address >> 16) as u16;
+            if upper_address != Some(upper) {
+                upper_address = Some(upper);
+                write_record(&mut out, RECORD_EXTENDED_LINEAR_ADDRESS, 0, &[
+                    (upper >> 8) as u8, upper as u8,
+                ]);
+            }
+
+            // Records can't cross the 64K boundary of the upper address
+            let to_boundary = 0x10000 - (address & 0xFFFF) as usize;
+            let cnt = *[BYTES_PER_RECORD, to_boundary, data.len()].iter().min().unwrap();
+            write_record(&mut out, RECORD_DATA, address as u16, &data[0..cnt]);
+
+            address += cnt as u32;
+            data = &data[cnt..];
+        }
+    }
+
+    write_record(&mut out, RECORD_START_LINEAR_ADDRESS, 0, &[
+        (entry >> 24) as u8, (entry >> 16) as u8, (entry >> 8) as u8, entry as u8,
+    ]);
+    write_record(&mut out, RECORD_END_OF_FILE, 0, &[]);
+    out
+}
+
+fn write_record(out: &mut String, kind: u8, address: u16, data: &[u8]) {
+    let mut checksum = (data.len() as u8)
+        .wrapping_add((address >> 8) as u8)
+        .wrapping_add(address as u8)
+        .wrapping_add(kind);
+
+    write!(out, ":{:02X}{:04X}{:02X}", data.len(), address, kind).unwrap();
+    for b in data {
+        checksum = checksum.wrapping_add(*b);
+        write!(out, "{:02X}", b).unwrap();
+    }
+    write!(out, "{:02X}\r\n", checksum.wrapping_neg()).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elf;
+
+    fn segment(address: u32, data: &[u8]) -> Segment {
+        Segment{ address, data: data.to_vec() }
+    }
+
+    #[test]
+    fn matches_objcopy() {
+        let elf = elf::parse(include_bytes!("../tests/fixtures/app.elf")).unwrap();
+        let expected = include_str!("../tests/fixtures/app.hex");
+        assert_eq!(encode(&elf.segments, elf.entry), expected);
+    }
+
+    #[test]
+    fn writes_record_checksums() {
+        let mut out = String::new();
+        write_record(&mut out, RECORD_DATA, 0x0030, &[0x02, 0x33, 0x7A]);
+        assert_eq!(out, ":0300300002337A1E\r\n");
+
+        let mut out = String::new();
+        write_record(&mut out, RECORD_END_OF_FILE, 0, &[]);
+        assert_eq!(out, ":00000001FF\r\n");
+    }
+
+    #[test]
+    fn splits_records_at_64k_boundary() {
+        let data: Vec<u8> = (0..20).collect();
+        let hex = encode(&[segment(0xc0d0_fff8, &data)], 0xc0d0_fff8);
+        assert_eq!(hex, "\
+            :02000004C0D06A\r\n\
+            :08FFF8000001020304050607E5\r\n\
+            :02000004C0D169\r\n\
+            :0C00000008090A0B0C0D0E0F1011121352\r\n\
+            :04000005C0D0FFF870\r\n\
+            :00000001FF\r\n\
+        ");
+    }
+
+    #[test]
+    fn splits_segments_into_records() {
+        let data = [0xAA; 20];
+        let hex = encode(&[segment(0xc0d0_0000, &data[0..20]), segment(0xc0d0_0040, &data[0..2])], 0xc0d0_0000);
+        assert_eq!(hex, "\
+            :02000004C0D06A\r\n\
+            :10000000AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA50\r\n\
+            :04001000AAAAAAAA44\r\n\
+            :02004000AAAA6A\r\n\
+            :04000005C0D0000067\r\n\
+            :00000001FF\r\n\
+        ");
+    }
+}
@@ -0,0 +1,13 @@
+//! Packaging of bolos-rs applications, used by the `cargo bolos`
+//! subcommand and by the build scripts of the apps for generating their
+//! manifest.
+
+extern crate bolos_icons;
+extern crate serde_json;
+
+pub mod elf;
+pub mod error;
+pub mod ihex;
+pub mod manifest;
+pub mod metadata;
+pub mod params;
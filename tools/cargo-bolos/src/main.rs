@@ -0,0 +1,220 @@
+//! Cargo subcommand for building and packaging bolos-rs applications. The
+//! app is described in the `[package.metadata.bolos]` table of its
+//! Cargo.toml, see `metadata::App` for the supported keys.
+
+extern crate bolos_icons;
+extern crate cargo_bolos;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use bolos_icons::Icon;
+use cargo_bolos::{elf, ihex};
+use cargo_bolos::error::Error;
+use cargo_bolos::metadata::App;
+use cargo_bolos::params::{Derivation, InstallParams};
+
+const USAGE: &str = "\
+Build, package and load bolos-rs applications
+
+Usage:
+    cargo bolos <command> [--manifest-path <path>]
+
+Commands:
+    build      Build the app in release mode and package it
+    package    Package the already built app
+    load       Build and package the app, then load it onto the device
+    delete     Delete the app from the device
+
+Packaging writes the Intel HEX file of the app and the install parameters
+next to the built binary. Loading and deleting apps is done with the
+ledgerblue Python tools, set BOLOS_PYTHON to use a specific interpreter.
+";
+
+const ICON_SIZE: u16 = 16;
+const ROOT_KEY_FILE: &str = "customCA.key";
+
+/// Files produced by packaging the app
+struct Package {
+    hex: PathBuf,
+    icon: Option<Vec<u8>>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Cargo passes the name of the subcommand as the first argument
+    let args = match args.first() {
+        Some(arg) if arg == "bolos" => &args[1..],
+        _ => &args[..],
+    };
+
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        if let Error::Usage = err {
+            eprint!("\n{}", USAGE);
+        }
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Error> {
+    let mut command = None;
+    let mut manifest_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(());
+            },
+            "--manifest-path" => {
+                manifest_path = Some(PathBuf::from(args.next().ok_or(Error::Usage)?));
+            },
+            _ if command.is_none() => command = Some(arg.as_str()),
+            _ => return Err(Error::Usage),
+        }
+    }
+
+    let app = App::load(manifest_path.as_deref())?;
+    match command {
+        Some("build") => {
+            build(&app)?;
+            package(&app)?;
+        },
+        Some("package") => {
+            package(&app)?;
+        },
+        Some("load") => {
+            build(&app)?;
+            let package = package(&app)?;
+            load(&app, &package)?;
+        },
+        Some("delete") => delete(&app)?,
+        _ => return Err(Error::Usage),
+    }
+    Ok(())
+}
+
+fn build(app: &App) -> Result<(), Error> {
+    let cargo = env::var_os("CARGO").unwrap_or("cargo".into());
+    let mut cmd = Command::new(cargo);
+    cmd.args(["build", "--release", "--manifest-path"])
+        .arg(&app.manifest_path);
+    run_command(cmd, "cargo build")
+}
+
+fn package(app: &App) -> Result<Package, Error> {
+    let elf = elf::parse(&fs::read(&app.binary)?)?;
+    // The device only lets the app write to its data area
+    let data_size = elf.data_size.unwrap_or(0);
+    if data_size != app.data_size {
+        return Err(Error::Metadata(format!(
+            "data-size is {} but the persistent! values of the app take {} bytes",
+            app.data_size, data_size,
+        )));
+    }
+    let hex = with_extension(&app.binary, "hex");
+    fs::write(&hex, ihex::encode(&elf.segments, elf.entry))?;
+
+    let icon = match app.icon {
+        Some(ref path) => Some(icon_to_bytes(&Icon::load(path)?)?),
+        None => None,
+    };
+    let derivation = Derivation::parse(&app.curves, &app.paths)?;
+    let install_params = InstallParams{
+        name: &app.name,
+        version: &app.version,
+        icon: icon.as_ref().map(|icon| &icon[..]),
+        derivation: &derivation,
+    }.to_bytes()?;
+    let params = with_extension(&app.binary, "params");
+    fs::write(&params, install_params)?;
+
+    println!("Packaged {} {} into {} and {}",
+        app.name, app.version, hex.display(), params.display());
+    Ok(Package{ hex, icon })
+}
+
+fn load(app: &App, package: &Package) -> Result<(), Error> {
+    let mut cmd = ledgerblue(app, "ledgerblue.loadApp")?;
+    cmd.args(["--tlv", "--delete", "--fileName"])
+        .arg(&package.hex)
+        .args(["--appName", &app.name])
+        .args(["--appVersion", &app.version])
+        .args(["--dataSize", &app.data_size.to_string()])
+        .args(["--appFlags", &format!("{:#x}", app.flags)]);
+    if let Some(ref icon) = package.icon {
+        cmd.args(["--icon", &to_hex(icon)]);
+    }
+    for curve in app.curves.iter() {
+        cmd.args(["--curve", curve]);
+    }
+    for path in app.paths.iter() {
+        cmd.args(["--path", path]);
+    }
+    run_command(cmd, "ledgerblue.loadApp")
+}
+
+fn delete(app: &App) -> Result<(), Error> {
+    let mut cmd = ledgerblue(app, "ledgerblue.deleteApp")?;
+    cmd.args(["--appName", &app.name]);
+    run_command(cmd, "ledgerblue.deleteApp")
+}
+
+// Command for running a ledgerblue script with the arguments that are
+// common to all of them
+fn ledgerblue(app: &App, module: &str) -> Result<Command, Error> {
+    let python = env::var_os("BOLOS_PYTHON").unwrap_or("python".into());
+    let mut cmd = Command::new(python);
+    cmd.args(["-m", module])
+        .args(["--targetId", &format!("{:#x}", app.target_id)]);
+
+    // Devices set up with a custom certificate authority accept apps
+    // signed with its key without asking for confirmation
+    let root_key_path = app.workspace_root.join(ROOT_KEY_FILE);
+    if root_key_path.exists() {
+        let root_key = fs::read_to_string(root_key_path)?;
+        cmd.args(["--rootPrivateKey", root_key.trim()]);
+    }
+    Ok(cmd)
+}
+
+fn run_command(mut cmd: Command, name: &str) -> Result<(), Error> {
+    if cmd.status()?.success() {
+        Ok(())
+    } else {
+        Err(Error::Command(name.to_string()))
+    }
+}
+
+// Dashboard icons are stored as the bits per pixel, the palette and the
+// bitmap, the size is always the same
+fn icon_to_bytes(icon: &Icon) -> Result<Vec<u8>, Error> {
+    if icon.width != ICON_SIZE || icon.height != ICON_SIZE {
+        return Err(Error::Metadata(format!("app icon has to be {}x{} pixels", ICON_SIZE, ICON_SIZE)));
+    }
+
+    let mut out = vec![icon.bits_per_pixel];
+    for color in icon.colors.iter() {
+        out.extend_from_slice(&[
+            *color as u8, (color >> 8) as u8, (color >> 16) as u8, (color >> 24) as u8,
+        ]);
+    }
+    out.extend_from_slice(&icon.bitmap);
+    Ok(out)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Appends the extension, the binary names can contain dots
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
@@ -0,0 +1,142 @@
+//! Generates the `app_manifest!` declaration of an app from its
+//! `[package.metadata.bolos]` table, so that the manifest that the app
+//! sees at runtime matches the install parameters.
+//!
+//! In the build script of the app:
+//!
+//! ```ignore
+//! extern crate cargo_bolos;
+//!
+//! fn main() {
+//!     cargo_bolos::manifest::write_to_out_dir("app_manifest.rs")
+//!         .expect("Failed to generate the app manifest");
+//! }
+//! ```
+//!
+//! And in the root of the app crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/app_manifest.rs"));
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use bolos_icons::Icon;
+use error::Error;
+use metadata::App;
+use params::Derivation;
+
+/// Rust source of the manifest, the icon is declared as `APP_ICON`
+pub fn generate(app: &App) -> Result<String, Error> {
+    let derivation = Derivation::parse(&app.curves, &app.paths)?;
+
+    let mut source = String::from("// Generated by cargo-bolos from Cargo.toml, do not edit\n\n");
+    let icon = match app.icon {
+        Some(ref path) => {
+            source.push_str(&Icon::load(path)?.to_rust_const("APP_ICON"));
+            source.push('\n');
+            "Some(APP_ICON)"
+        },
+        None => "None",
+    };
+
+    let curves = app.curves.iter()
+        .map(|curve| curve_variant(curve))
+        .collect::<Result<Vec<_>, _>>()?;
+    let paths: Vec<String> = derivation.paths.iter()
+        .map(|path| {
+            let indices: Vec<String> = path.iter()
+                .map(|index| format!("{:#010x}", index))
+                .collect();
+            format!("[{}]", indices.join(", "))
+        })
+        .collect();
+
+    // The name and version are printable ASCII, so the debug format is
+    // a valid string literal
+    source.push_str(&format!(
+        "app_manifest!{{\n    \
+            name: {:?},\n    \
+            version: {:?},\n    \
+            flags: {:#x},\n    \
+            icon: {},\n    \
+            curves: [{}],\n    \
+            paths: [{}],\n\
+        }}\n",
+        app.name, app.version, app.flags, icon, curves.join(", "), paths.join(", "),
+    ));
+    Ok(source)
+}
+
+/// Writes the manifest of the package whose build script is running to
+/// `file_name` in the `OUT_DIR` and lets cargo know to rerun the script
+/// when the metadata or the icon changes
+pub fn write_to_out_dir(file_name: &str) -> Result<(), Error> {
+    let out_dir = env::var_os("OUT_DIR")
+        .ok_or_else(|| Error::Metadata("OUT_DIR is not set".to_string()))?;
+    let app = App::from_build_script()?;
+
+    println!("cargo:rerun-if-changed={}", app.manifest_path.display());
+    if let Some(ref icon) = app.icon {
+        println!("cargo:rerun-if-changed={}", icon.display());
+    }
+
+    fs::write(Path::new(&out_dir).join(file_name), generate(&app)?)?;
+    Ok(())
+}
+
+// Names of the `bolos::crypto::ecc::Curve` variants for the curve names
+// that the loader accepts
+fn curve_variant(curve: &str) -> Result<&'static str, Error> {
+    match curve {
+        "secp256k1" => Ok("Secp256k1"),
+        "prime256r1" => Ok("Secp256r1"),
+        "ed25519" => Ok("Ed25519"),
+        _ => Err(Error::Metadata(format!("unknown curve {}", curve))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    fn app() -> App {
+        App{
+            manifest_path: PathBuf::from("Cargo.toml"),
+            workspace_root: PathBuf::from("."),
+            binary: PathBuf::from("app"),
+            name: "Demo".to_string(),
+            version: "1.2.3".to_string(),
+            icon: None,
+            target_id: 0x3110_0003,
+            data_size: 0,
+            flags: 0x40,
+            curves: vec!["secp256k1".to_string(), "ed25519".to_string()],
+            paths: vec!["44'/0'".to_string(), "m/13".to_string()],
+        }
+    }
+
+    #[test]
+    fn generates_manifest() {
+        assert_eq!(generate(&app()).unwrap(), "\
+            // Generated by cargo-bolos from Cargo.toml, do not edit\n\
+            \n\
+            app_manifest!{\n    \
+                name: \"Demo\",\n    \
+                version: \"1.2.3\",\n    \
+                flags: 0x40,\n    \
+                icon: None,\n    \
+                curves: [Secp256k1, Ed25519],\n    \
+                paths: [[0x8000002c, 0x80000000], [0x0000000d]],\n\
+            }\n");
+    }
+
+    #[test]
+    fn rejects_unknown_curve() {
+        let mut app = app();
+        app.curves = vec!["secp384r1".to_string()];
+        assert!(generate(&app).is_err());
+    }
+}
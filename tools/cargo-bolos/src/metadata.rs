@@ -0,0 +1,202 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde_json::{self, Value};
+use error::Error;
+
+const TARGET: &str = "thumbv6m-none-eabi";
+/// Ledger Nano S running firmware 1.4
+const DEFAULT_TARGET_ID: u32 = 0x3110_0003;
+/// Longest app name and version that the dashboard has room for
+const MAX_TEXT_LEN: usize = 32;
+/// Application flags that apps can request, the rest are managed by the
+/// OS. Same as `bolos::manifest::APP_FLAGS`.
+pub const APP_FLAGS: u32 = 0x0FF0;
+
+/// App described by the `[package.metadata.bolos]` table of Cargo.toml:
+///
+/// ```toml
+/// [package.metadata.bolos]
+/// name = "Demo"
+/// icon = "../nanos_icon.gif"
+/// target-id = "0x31100003"
+/// data-size = 0
+/// curves = ["secp256k1"]
+/// paths = ["44'/0'"]
+/// ```
+///
+/// Everything is optional, the name and version default to the ones of
+/// the package. The name and version have to be printable ASCII and the
+/// flags are limited to `APP_FLAGS`.
+pub struct App {
+    pub manifest_path: PathBuf,
+    pub workspace_root: PathBuf,
+    /// Path of the ELF file that cargo builds for the app
+    pub binary: PathBuf,
+    pub name: String,
+    pub version: String,
+    pub icon: Option<PathBuf>,
+    pub target_id: u32,
+    /// Size of the NVRAM section with the `persistent!` values
+    pub data_size: u32,
+    pub flags: u32,
+    pub curves: Vec<String>,
+    pub paths: Vec<String>,
+}
+
+impl App {
+    /// Reads the metadata of the package at `manifest_path`, or the one
+    /// in the current directory
+    pub fn load(manifest_path: Option<&Path>) -> Result<Self, Error> {
+        let manifest_path = match manifest_path {
+            Some(path) => path.to_path_buf(),
+            None => env::current_dir()?.join("Cargo.toml"),
+        };
+        let manifest_path = manifest_path.canonicalize()?;
+
+        let cargo = env::var_os("CARGO").unwrap_or("cargo".into());
+        let output = Command::new(cargo)
+            .args(["metadata", "--format-version", "1", "--no-deps", "--manifest-path"])
+            .arg(&manifest_path)
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::Command("cargo metadata".to_string()));
+        }
+        let metadata: Value = serde_json::from_slice(&output.stdout)?;
+
+        let package = metadata["packages"].as_array()
+            .and_then(|packages| packages.iter().find(|package| {
+                package["manifest_path"].as_str()
+                    .map(|path| Path::new(path) == manifest_path)
+                    .unwrap_or(false)
+            }))
+            .ok_or_else(|| Error::Metadata(format!("no package at {}", manifest_path.display())))?;
+
+        let binary_name = package["targets"].as_array()
+            .and_then(|targets| targets.iter().find(|target| {
+                target["kind"].as_array()
+                    .map(|kinds| kinds.iter().any(|kind| kind == "bin"))
+                    .unwrap_or(false)
+            }))
+            .and_then(|target| target["name"].as_str())
+            .ok_or_else(|| Error::Metadata("package doesn't have a binary target".to_string()))?;
+
+        let target_dir = metadata["target_directory"].as_str()
+            .ok_or_else(|| Error::Metadata("cargo didn't report the target directory".to_string()))?;
+        let workspace_root = metadata["workspace_root"].as_str()
+            .ok_or_else(|| Error::Metadata("cargo didn't report the workspace root".to_string()))?;
+
+        let manifest_dir = manifest_path.parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+        let bolos = &package["metadata"]["bolos"];
+
+        let app = Self{
+            binary: Path::new(target_dir).join(TARGET).join("release").join(binary_name),
+            workspace_root: PathBuf::from(workspace_root),
+            name: string_or(&bolos["name"], &package["name"])?,
+            version: string_or(&bolos["version"], &package["version"])?,
+            icon: bolos["icon"].as_str().map(|icon| manifest_dir.join(icon)),
+            target_id: number_or(&bolos["target-id"], "target-id", DEFAULT_TARGET_ID)?,
+            data_size: number_or(&bolos["data-size"], "data-size", 0)?,
+            flags: number_or(&bolos["flags"], "flags", 0)?,
+            curves: strings(&bolos["curves"], "curves")?,
+            paths: strings(&bolos["paths"], "paths")?,
+            manifest_path,
+        };
+        app.validate()?;
+        Ok(app)
+    }
+
+    /// Reads the metadata of the package whose build script is running
+    pub fn from_build_script() -> Result<Self, Error> {
+        let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")
+            .ok_or_else(|| Error::Metadata("CARGO_MANIFEST_DIR is not set".to_string()))?;
+        Self::load(Some(&Path::new(&manifest_dir).join("Cargo.toml")))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        check_text(&self.name, "name")?;
+        check_text(&self.version, "version")?;
+        if self.flags & !APP_FLAGS != 0 {
+            return Err(Error::Metadata(format!(
+                "flags {:#x} aren't allowed, apps can only request {:#x}",
+                self.flags & !APP_FLAGS, APP_FLAGS
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn check_text(value: &str, key: &str) -> Result<(), Error> {
+    if value.is_empty() || value.len() > MAX_TEXT_LEN {
+        return Err(Error::Metadata(format!("{} has to be 1 to {} characters long", key, MAX_TEXT_LEN)));
+    }
+    if !value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return Err(Error::Metadata(format!("{} has to be printable ASCII", key)));
+    }
+    Ok(())
+}
+
+fn string_or(value: &Value, default: &Value) -> Result<String, Error> {
+    value.as_str()
+        .or_else(|| default.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| Error::Metadata("package name and version have to be strings".to_string()))
+}
+
+/// Numbers can be given as integers or as strings, which allows writing
+/// them in hex
+fn number_or(value: &Value, key: &str, default: u32) -> Result<u32, Error> {
+    let number = match *value {
+        Value::Null => return Ok(default),
+        Value::Number(ref n) => n.as_u64(),
+        Value::String(ref s) if s.starts_with("0x") => u64::from_str_radix(&s[2..], 16).ok(),
+        Value::String(ref s) => s.parse().ok(),
+        _ => None,
+    };
+    match number {
+        Some(n) if n <= u32::MAX as u64 => Ok(n as u32),
+        _ => Err(Error::Metadata(format!("{} has to be a 32-bit number", key))),
+    }
+}
+
+fn strings(value: &Value, key: &str) -> Result<Vec<String>, Error> {
+    match *value {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(ref items) => {
+            items.iter()
+                .map(|item| item.as_str().map(|s| s.to_string()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| Error::Metadata(format!("{} has to be a list of strings", key)))
+        },
+        _ => Err(Error::Metadata(format!("{} has to be a list of strings", key))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_text() {
+        assert!(check_text("Demo", "name").is_ok());
+        assert!(check_text("1.2.3-beta+1", "version").is_ok());
+        assert!(check_text(&"x".repeat(MAX_TEXT_LEN), "name").is_ok());
+        assert!(check_text(&"x".repeat(MAX_TEXT_LEN + 1), "name").is_err());
+        assert!(check_text("", "version").is_err());
+        assert!(check_text("Dem\u{f6}", "name").is_err());
+        assert!(check_text("1.0\n", "version").is_err());
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(number_or(&Value::Null, "flags", 7).unwrap(), 7);
+        assert_eq!(number_or(&Value::from(64), "flags", 0).unwrap(), 64);
+        assert_eq!(number_or(&Value::from("0x31100003"), "target-id", 0).unwrap(), 0x3110_0003);
+        assert_eq!(number_or(&Value::from("1024"), "data-size", 0).unwrap(), 1024);
+        assert!(number_or(&Value::from("0x100000000"), "flags", 0).is_err());
+        assert!(number_or(&Value::from(-1), "flags", 0).is_err());
+        assert!(number_or(&Value::from(true), "flags", 0).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use error::Error;
+
+const TAG_APP_NAME: u8 = 0x01;
+const TAG_APP_VERSION: u8 = 0x02;
+const TAG_ICON: u8 = 0x03;
+const TAG_DERIVATION_PATH: u8 = 0x04;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Curves and derivation paths that the app is allowed to use
+pub struct Derivation {
+    pub curve_mask: u8,
+    pub paths: Vec<Vec<u32>>,
+}
+
+impl Derivation {
+    /// Curve names are the ones the loader accepts, the paths are in
+    /// the `44'/0'/0'` notation. Without any curves all of them are
+    /// allowed.
+    pub fn parse(curves: &[String], paths: &[String]) -> Result<Self, Error> {
+        let mut curve_mask = if !curves.is_empty() { 0x00 } else { 0xFF };
+        for curve in curves {
+            curve_mask |= match curve.as_str() {
+                "secp256k1" => 0x01,
+                "prime256r1" => 0x02,
+                "ed25519" => 0x04,
+                _ => return Err(Error::Metadata(format!("unknown curve {}", curve))),
+            };
+        }
+
+        let paths = paths.iter()
+            .map(|path| parse_path(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self{ curve_mask, paths })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.curve_mask];
+        for path in self.paths.iter() {
+            out.push(path.len() as u8);
+            for element in path {
+                out.extend_from_slice(&[
+                    (element >> 24) as u8, (element >> 16) as u8,
+                    (element >> 8) as u8, *element as u8,
+                ]);
+            }
+        }
+        out
+    }
+}
+
+/// Install parameters that the dashboard shows and enforces for the
+/// app, stored after the app code on the device
+pub struct InstallParams<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub icon: Option<&'a [u8]>,
+    pub derivation: &'a Derivation,
+}
+
+impl<'a> InstallParams<'a> {
+    /// Encodes the parameters as the TLV blob the loader appends to the
+    /// app when installing it with `--tlv`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        write_tlv(&mut out, TAG_APP_NAME, self.name.as_bytes())?;
+        write_tlv(&mut out, TAG_APP_VERSION, self.version.as_bytes())?;
+        if let Some(icon) = self.icon {
+            write_tlv(&mut out, TAG_ICON, icon)?;
+        }
+        write_tlv(&mut out, TAG_DERIVATION_PATH, &self.derivation.to_bytes())?;
+        Ok(out)
+    }
+}
+
+fn write_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<(), Error> {
+    out.push(tag);
+    // DER style length
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else if len < 0x100 {
+        out.extend_from_slice(&[0x81, len as u8]);
+    } else if len < 0x10000 {
+        out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]);
+    } else {
+        return Err(Error::Metadata(format!("install parameter {:#04x} is too large", tag)));
+    }
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+fn parse_path(path: &str) -> Result<Vec<u32>, Error> {
+    let path = path.strip_prefix("m/").unwrap_or(path);
+    path.split('/')
+        .map(|element| {
+            let (index, hardened) = if element.ends_with('\'') || element.ends_with('h') {
+                (&element[0..element.len() - 1], true)
+            } else {
+                (element, false)
+            };
+            match index.parse::<u32>() {
+                Ok(index) if index < HARDENED => {
+                    Ok(if hardened { index | HARDENED } else { index })
+                },
+                _ => Err(Error::Metadata(format!("invalid derivation path {}", path))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_paths() {
+        assert_eq!(parse_path("44'/0'").unwrap(), vec![0x8000_002C, 0x8000_0000]);
+        assert_eq!(parse_path("m/44h/60'/0/1").unwrap(), vec![0x8000_002C, 0x8000_003C, 0, 1]);
+        assert_eq!(parse_path("2147483647'").unwrap(), vec![0xFFFF_FFFF]);
+    }
+
+    #[test]
+    fn refuses_invalid_paths() {
+        assert!(parse_path("").is_err());
+        assert!(parse_path("44'/").is_err());
+        assert!(parse_path("44'/x").is_err());
+        assert!(parse_path("2147483648").is_err());
+        assert!(parse_path("-1").is_err());
+    }
+
+    #[test]
+    fn encodes_derivation() {
+        let derivation = Derivation::parse(
+            &["secp256k1".to_string(), "ed25519".to_string()],
+            &["44'/0'".to_string(), "m/13".to_string()],
+        ).unwrap();
+        assert_eq!(derivation.to_bytes(), vec![
+            0x05,
+            2, 0x80, 0x00, 0x00, 0x2C, 0x80, 0x00, 0x00, 0x00,
+            1, 0x00, 0x00, 0x00, 0x0D,
+        ]);
+
+        let any = Derivation::parse(&[], &[]).unwrap();
+        assert_eq!(any.to_bytes(), vec![0xFF]);
+        assert!(Derivation::parse(&["secp256r1".to_string()], &[]).is_err());
+    }
+}
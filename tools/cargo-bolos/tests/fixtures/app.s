@@ -0,0 +1,17 @@
+.section .text, "ax"
+.globl _start
+_start:
+.rept 48
+.byte 0x10, 0x32, 0x54, 0x76
+.endr
+
+.section .nvm_data, "aw"
+.long 1, 1, 0x2a
+.balign 64
+.long 0, 1, 0x2a
+
+.section .data, "aw"
+.long 0x11223344, 0x55667788
+
+.section .bss, "aw", @nobits
+.skip 32
@@ -0,0 +1,11 @@
+#!/bin/sh
+# Rebuilds the test app with the host binutils. The layout mimics the SDK
+# linker script: code at the start of the flash, the persistent data in
+# its own pages at the end of it and .data loaded from the flash. The
+# Intel HEX file is the reference that the encoder output is compared to.
+set -e
+cd "$(dirname "$0")"
+as --32 -o app.o app.s
+ld -m elf_i386 -T app.ld -o app.elf app.o
+rm app.o
+objcopy -O ihex -S app.elf app.hex